@@ -0,0 +1,123 @@
+//! Animation Timing
+//!
+//! Shared stepping for the overlay's animations (idle fade, attention
+//! pulse, resize to content). Each animation has a fixed duration and is
+//! stepped at the configured frame rate. Reduced motion turns transitions
+//! into instant changes, and low-power mode caps the rate and skips
+//! purely decorative animations:
+//! - macOS: Low Power Mode (NSProcessInfo)
+//! - Windows: Battery saver, or running on battery
+//! - Linux: Running on battery (sysfs power supplies)
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Highest frame rate accepted by `set_animation_fps`.
+pub const MAX_FPS: u32 = 240;
+
+/// Frame rate cap while the system is in low-power mode.
+const LOW_POWER_FPS: u32 = 15;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnimationSettings {
+    /// Target frame rate (1-`MAX_FPS`).
+    pub fps: u32,
+    /// Replace animated transitions with instant changes.
+    pub reduced_motion: bool,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        Self { fps: 60, reduced_motion: false }
+    }
+}
+
+/// How to run one animation, resolved when it starts.
+#[derive(Debug, Clone, Copy)]
+pub struct Motion {
+    fps: u32,
+    instant: bool,
+    /// Skip animations that only draw the eye, such as the attention blink.
+    pub skip_decorative: bool,
+}
+
+impl Motion {
+    pub fn resolve(settings: &AnimationSettings, low_power: bool) -> Self {
+        let fps = settings.fps.clamp(1, MAX_FPS);
+        Self {
+            fps: if low_power { fps.min(LOW_POWER_FPS) } else { fps },
+            instant: settings.reduced_motion,
+            skip_decorative: settings.reduced_motion || low_power,
+        }
+    }
+
+    /// Frames for an animation of `duration`; 1 when motion is reduced, so
+    /// the end state is applied right away.
+    pub fn steps(&self, duration: Duration) -> u32 {
+        if self.instant {
+            return 1;
+        }
+        ((duration.as_secs_f64() * self.fps as f64).round() as u32).max(1)
+    }
+}
+
+/// Whether the system asks apps to save power.
+pub fn low_power() -> bool {
+    #[cfg(target_os = "macos")]
+    return low_power_macos();
+
+    #[cfg(target_os = "windows")]
+    return low_power_windows();
+
+    #[cfg(target_os = "linux")]
+    return on_battery_linux();
+}
+
+#[cfg(target_os = "macos")]
+fn low_power_macos() -> bool {
+    use cocoa::base::{id, BOOL, NO};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    unsafe {
+        let info: id = msg_send![class!(NSProcessInfo), processInfo];
+        // isLowPowerModeEnabled is macOS 12+
+        let supported: BOOL = msg_send![info, respondsToSelector: sel!(isLowPowerModeEnabled)];
+        if supported == NO {
+            return false;
+        }
+        let enabled: BOOL = msg_send![info, isLowPowerModeEnabled];
+        enabled != NO
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn low_power_windows() -> bool {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status = SYSTEM_POWER_STATUS::default();
+    if unsafe { GetSystemPowerStatus(&mut status) }.is_err() {
+        return false;
+    }
+    // SystemStatusFlag 1 is battery saver; ACLineStatus 0 is on battery
+    status.SystemStatusFlag == 1 || status.ACLineStatus == 0
+}
+
+#[cfg(target_os = "linux")]
+fn on_battery_linux() -> bool {
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let read = |dir: &std::path::Path, name: &str| std::fs::read_to_string(dir.join(name)).unwrap_or_default();
+    let (mut battery, mut mains_online) = (false, false);
+    for supply in supplies.flatten() {
+        let dir = supply.path();
+        match read(&dir, "type").trim() {
+            "Battery" => battery = true,
+            "Mains" => mains_online |= read(&dir, "online").trim() == "1",
+            _ => {}
+        }
+    }
+    battery && !mains_online
+}
@@ -0,0 +1,120 @@
+//! App State Snapshot
+//!
+//! One file, `app_state.json` in the app config dir, holding the UI state
+//! that isn't tied to a connection: preferences, glass settings, the pinned
+//! theme, the main window's layout, the overlay mode and the event target.
+//! It is written whenever the preferences are saved and every
+//! `SAVE_INTERVAL` to catch other changes, and can be exported and imported
+//! as a whole. At launch the parts without a file of their own are restored
+//! from it; preferences and layout still load from theirs. The auth token
+//! is never included.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::Theme;
+
+use crate::liquid_glass::{self, GlassConfig, WindowBorder};
+use crate::overlay_mode::OverlayMode;
+use crate::prefs::Preferences;
+use crate::window_layout::SavedLayout;
+
+/// File name of the snapshot inside the app config dir.
+pub const SNAPSHOT_FILE: &str = "app_state.json";
+
+/// Format of snapshots written by this version.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// How often the snapshot is saved in the background.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppStateSnapshot {
+    pub version: u32,
+    pub preferences: Preferences,
+    pub glass: GlassSnapshot,
+    /// Pinned theme; `None` follows the OS.
+    pub theme: Option<Theme>,
+    pub window_layout: Option<SavedLayout>,
+    pub overlay_mode: OverlayMode,
+    pub event_target: Option<String>,
+}
+
+/// The user-set part of `GlassConfig`; dark and opacity follow the theme
+/// and idle dimming.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlassSnapshot {
+    pub tint: Option<(u8, u8, u8, u8)>,
+    pub tint_alpha: Option<u8>,
+    pub corner_radius: f64,
+    pub shadow: bool,
+    pub border: Option<WindowBorder>,
+    pub inset: f64,
+}
+
+impl Default for GlassSnapshot {
+    fn default() -> Self {
+        Self::from(&GlassConfig::default())
+    }
+}
+
+impl From<&GlassConfig> for GlassSnapshot {
+    fn from(config: &GlassConfig) -> Self {
+        Self {
+            tint: config.tint,
+            tint_alpha: config.tint_alpha,
+            corner_radius: config.corner_radius,
+            shadow: config.shadow,
+            border: config.border,
+            inset: config.inset,
+        }
+    }
+}
+
+impl GlassSnapshot {
+    /// Copy into `config`, clamped to the limits the commands enforce.
+    pub fn apply_to(&self, config: &mut GlassConfig) {
+        match self.tint {
+            Some((r, g, b, a)) => config.set_tint(r, g, b, a),
+            None => config.tint = None,
+        }
+        config.tint_alpha = self.tint_alpha.map(|alpha| alpha.min(liquid_glass::MAX_TINT_ALPHA));
+        let defaults = GlassConfig::default();
+        config.corner_radius = limit(self.corner_radius, liquid_glass::MAX_CORNER_RADIUS, defaults.corner_radius);
+        config.shadow = self.shadow;
+        config.border = self
+            .border
+            .filter(|border| border.width > 0.0)
+            .map(|border| WindowBorder { width: border.width.min(liquid_glass::MAX_BORDER_WIDTH), ..border });
+        config.inset = limit(self.inset, liquid_glass::MAX_GLASS_INSET, defaults.inset);
+    }
+}
+
+/// `value` clamped to `0..=max`, or `default` when it isn't a number.
+fn limit(value: f64, max: f64, default: f64) -> f64 {
+    if value.is_finite() {
+        value.clamp(0.0, max)
+    } else {
+        default
+    }
+}
+
+/// Load the snapshot; `None` when the file is missing or invalid.
+pub fn load(path: &Path) -> Option<AppStateSnapshot> {
+    let text = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text)
+        .inspect_err(|e| tracing::warn!(path = %path.display(), error = %e, "Invalid app state snapshot, ignoring it"))
+        .ok()
+}
+
+pub fn save(path: &Path, json: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, json)
+}
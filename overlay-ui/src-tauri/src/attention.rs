@@ -0,0 +1,56 @@
+//! Attention Pulse
+//!
+//! Draws the eye to the overlay when the agent finishes a long task or
+//! needs input: the window's opacity blinks a few times and the OS is asked
+//! for user attention (dock bounce, taskbar flash, urgency hint).
+
+use std::time::Duration;
+
+use tauri::async_runtime::JoinHandle;
+use tauri::{UserAttentionType, WebviewWindow};
+
+use crate::animation::Motion;
+use crate::liquid_glass;
+
+/// Upper bound on blinks per request, so a bad value can't blink forever.
+pub const MAX_PULSES: u32 = 10;
+
+/// Length and smoothness of one blink.
+const PULSE_DURATION: Duration = Duration::from_millis(400);
+
+/// Blink the window `times` times around its current opacity `base`, then
+/// leave it at `base`. Must not overlap an opacity fade. The blink is
+/// decorative, so reduced motion and low-power mode leave only the OS request.
+pub fn pulse(window: WebviewWindow, times: u32, base: f64, motion: Motion) -> Option<JoinHandle<()>> {
+    if let Err(e) = window.request_user_attention(Some(UserAttentionType::Informational)) {
+        tracing::warn!(error = %e, "Failed to request user attention");
+    }
+
+    // Window opacity is a no-op on Linux, leaving only the urgency hint
+    if cfg!(target_os = "linux") {
+        tracing::warn!("Opacity pulse is not supported on Linux");
+        return None;
+    }
+    if motion.skip_decorative {
+        return None;
+    }
+
+    // Blink toward full opacity when dimmed, toward half otherwise
+    let peak = if base < 0.75 { 1.0 } else { base * 0.5 };
+    Some(tauri::async_runtime::spawn(async move {
+        let steps = motion.steps(PULSE_DURATION);
+        let mut tick = tokio::time::interval(PULSE_DURATION / steps);
+        for _ in 0..times.min(MAX_PULSES) {
+            for step in 1..=steps {
+                tick.tick().await;
+                // Rises from base to peak and back within one blink
+                let phase = (step as f64 / steps as f64 * std::f64::consts::PI).sin();
+                let value = base + (peak - base) * phase;
+                let target_window = window.clone();
+                let _ = window.run_on_main_thread(move || liquid_glass::set_opacity(&target_window, value));
+            }
+        }
+        let target_window = window.clone();
+        let _ = window.run_on_main_thread(move || liquid_glass::set_opacity(&target_window, base));
+    }))
+}
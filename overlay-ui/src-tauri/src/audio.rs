@@ -0,0 +1,92 @@
+//! Audio Streaming
+//!
+//! Microphone audio captured by the webview is forwarded to the agent as
+//! binary `audio` frames (see binary.rs) whose metadata carries the stream
+//! id, a sequence number and the format. Chunks wait in a bounded queue;
+//! when the agent falls behind the oldest are dropped, since stale audio is
+//! worth less than keeping up.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Chunks held while the agent is slow; at 20 ms per chunk about 1.3 s.
+pub const QUEUE_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// Signed 16-bit little-endian PCM, interleaved.
+    Pcm,
+    /// Opus packets, one per chunk.
+    Opus,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub codec: Codec,
+}
+
+/// One stream from `start_audio_stream` to `stop_audio_stream`.
+pub struct AudioStream {
+    pub id: String,
+    pub format: AudioFormat,
+    next_seq: u64,
+    queue: VecDeque<(u64, Vec<u8>)>,
+    /// Chunks dropped so far because the queue was full.
+    pub dropped: u64,
+}
+
+impl AudioStream {
+    pub fn new(id: String, format: AudioFormat) -> Self {
+        Self { id, format, next_seq: 0, queue: VecDeque::new(), dropped: 0 }
+    }
+
+    /// Queue a chunk, dropping the oldest when full. Returns whether one
+    /// was dropped.
+    pub fn push(&mut self, chunk: Vec<u8>) -> bool {
+        let dropped = self.queue.len() >= QUEUE_CAPACITY;
+        if dropped {
+            self.queue.pop_front();
+            self.dropped += 1;
+        }
+        self.queue.push_back((self.next_seq, chunk));
+        self.next_seq += 1;
+        dropped
+    }
+
+    /// The next chunk to send, with its frame metadata.
+    pub fn pop(&mut self) -> Option<(Value, Vec<u8>)> {
+        let (seq, chunk) = self.queue.pop_front()?;
+        Some((self.metadata(seq, false), chunk))
+    }
+
+    /// Metadata of the empty chunk that tells the agent the stream ended.
+    pub fn end_metadata(&self) -> Value {
+        self.metadata(self.next_seq, true)
+    }
+
+    fn metadata(&self, seq: u64, end: bool) -> Value {
+        json!({
+            "stream_id": self.id,
+            "seq": seq,
+            "sample_rate": self.format.sample_rate,
+            "channels": self.format.channels,
+            "codec": self.format.codec,
+            "end": end,
+        })
+    }
+}
+
+/// Payload of `audio-stream` events.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AudioStreamEvent {
+    Started { stream_id: String, format: AudioFormat },
+    /// `dropped` is the total for the stream so far.
+    Dropped { stream_id: String, dropped: u64 },
+    Stopped { stream_id: String, dropped: u64 },
+}
@@ -0,0 +1,129 @@
+//! WebSocket Auth Token
+//!
+//! A random token is generated at startup and written to `~/.jarvis/ws_token`
+//! with owner-only permissions. Agents read it from there and must send it
+//! in an `auth` frame before the overlay accepts anything else.
+//!
+//! `rotate` replaces the token; the previous one stays valid for
+//! `ROTATION_OVERLAP` so an agent that read the file just before can still
+//! authenticate.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use rand::RngCore;
+
+/// How long a rotated-out token is still accepted.
+pub const ROTATION_OVERLAP: Duration = Duration::from_secs(30);
+
+/// The active token and the file it was written to.
+pub struct WsToken {
+    pub token: String,
+    pub path: PathBuf,
+    /// The token before the last rotation, and when it stops being accepted.
+    pub previous: Option<(String, Instant)>,
+}
+
+impl WsToken {
+    pub fn new(token: String, path: PathBuf) -> Self {
+        Self { token, path, previous: None }
+    }
+
+    /// Whether `presented` is the current token, or the previous one
+    /// within its overlap.
+    pub fn accepts(&self, presented: &str, now: Instant) -> bool {
+        tokens_match(&self.token, presented)
+            || self
+                .previous
+                .as_ref()
+                .is_some_and(|(previous, expires)| now < *expires && tokens_match(previous, presented))
+    }
+
+    /// Switch to a fresh token and rewrite the token file. On a write
+    /// failure the old token stays in place.
+    pub fn rotate(&mut self, now: Instant) -> io::Result<()> {
+        let token = generate_token();
+        write_secret_file(&self.path, &token)?;
+        let previous = std::mem::replace(&mut self.token, token);
+        self.previous = Some((previous, now + ROTATION_OVERLAP));
+        Ok(())
+    }
+}
+
+/// Path of the token file relative to the user's home directory.
+pub fn token_path(home: &Path) -> PathBuf {
+    home.join(".jarvis").join("ws_token")
+}
+
+/// Generate a random 256-bit token, hex-encoded.
+pub fn generate_token() -> String {
+    random_hex(32)
+}
+
+/// `len` random bytes, hex-encoded.
+pub fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Write a secret file (token, private key) readable only by the current
+/// user (0600 on Unix). The contents go to a temp file that is renamed
+/// over `path`, so readers never see a partly written secret.
+pub fn write_secret_file(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("tmp");
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // mode() only applies on create, so tighten a leftover temp file too
+        if temp.exists() {
+            fs::set_permissions(&temp, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    let mut file = options.open(&temp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp, path)
+}
+
+/// Compare tokens in constant time so timing doesn't leak the secret.
+pub fn tokens_match(expected: &str, presented: &str) -> bool {
+    let (a, b) = (expected.as_bytes(), presented.as_bytes());
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_keeps_the_old_token_for_the_overlap() {
+        let dir = std::env::temp_dir().join(format!("jarvis-auth-{}", random_hex(8)));
+        let mut token = WsToken::new(generate_token(), token_path(&dir));
+        let old = token.token.clone();
+        let now = Instant::now();
+        token.rotate(now).unwrap();
+        let new = token.token.clone();
+
+        assert_ne!(old, new);
+        assert_eq!(fs::read_to_string(&token.path).unwrap(), new);
+        assert!(token.accepts(&old, now + ROTATION_OVERLAP - Duration::from_millis(1)));
+        assert!(!token.accepts(&old, now + ROTATION_OVERLAP));
+        for at in [now, now + ROTATION_OVERLAP, now + ROTATION_OVERLAP * 10] {
+            assert!(token.accepts(&new, at));
+        }
+        assert!(!token.accepts("not-a-token", now));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
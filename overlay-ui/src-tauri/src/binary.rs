@@ -0,0 +1,123 @@
+//! Binary Frame Format
+//!
+//! Images and audio travel as WebSocket binary frames in both directions:
+//!
+//! ```text
+//! +--------+------------------+---------------+---------+
+//! | kind   | metadata length  | metadata      | payload |
+//! | 1 byte | u32, big-endian  | UTF-8 JSON    | rest    |
+//! +--------+------------------+---------------+---------+
+//! ```
+//!
+//! Kind tags: `1` = image, `2` = audio, `3` = screenshot (PNG or JPEG,
+//! delivered to the webview as a thumbnail; see screenshot.rs). Metadata is a JSON object (use
+//! `{}` when there is none) describing the payload, e.g. its MIME type.
+
+use std::fmt;
+
+use serde_json::Value;
+
+const HEADER_LEN: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryKind {
+    Image,
+    Audio,
+    Screenshot,
+}
+
+impl BinaryKind {
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(Self::Image),
+            2 => Some(Self::Audio),
+            3 => Some(Self::Screenshot),
+            _ => None,
+        }
+    }
+
+    pub fn tag(self) -> u8 {
+        match self {
+            Self::Image => 1,
+            Self::Audio => 2,
+            Self::Screenshot => 3,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "image" => Some(Self::Image),
+            "audio" => Some(Self::Audio),
+            "screenshot" => Some(Self::Screenshot),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Image => "image",
+            Self::Audio => "audio",
+            Self::Screenshot => "screenshot",
+        }
+    }
+}
+
+/// A decoded binary frame.
+#[derive(Debug, Clone)]
+pub struct BinaryFrame {
+    pub kind: BinaryKind,
+    pub metadata: Value,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    TooShort,
+    UnknownKind(u8),
+    BadMetadata(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort => write!(f, "frame shorter than its header"),
+            Self::UnknownKind(tag) => write!(f, "unknown kind tag {}", tag),
+            Self::BadMetadata(e) => write!(f, "invalid metadata: {}", e),
+        }
+    }
+}
+
+pub fn decode(data: &[u8]) -> Result<BinaryFrame, DecodeError> {
+    if data.len() < HEADER_LEN {
+        return Err(DecodeError::TooShort);
+    }
+    let kind = BinaryKind::from_tag(data[0]).ok_or(DecodeError::UnknownKind(data[0]))?;
+    let meta_len = u32::from_be_bytes([data[1], data[2], data[3], data[4]]) as usize;
+
+    let meta_end = HEADER_LEN.checked_add(meta_len).filter(|end| *end <= data.len());
+    let meta_end = meta_end.ok_or(DecodeError::TooShort)?;
+
+    let metadata = if meta_len == 0 {
+        Value::Object(Default::default())
+    } else {
+        serde_json::from_slice(&data[HEADER_LEN..meta_end])
+            .map_err(|e| DecodeError::BadMetadata(e.to_string()))?
+    };
+
+    Ok(BinaryFrame {
+        kind,
+        metadata,
+        payload: data[meta_end..].to_vec(),
+    })
+}
+
+pub fn encode(kind: BinaryKind, metadata: &Value, payload: &[u8]) -> Vec<u8> {
+    let meta = serde_json::to_vec(metadata).unwrap_or_else(|_| b"{}".to_vec());
+
+    let mut out = Vec::with_capacity(HEADER_LEN + meta.len() + payload.len());
+    out.push(kind.tag());
+    out.extend_from_slice(&(meta.len() as u32).to_be_bytes());
+    out.extend_from_slice(&meta);
+    out.extend_from_slice(payload);
+    out
+}
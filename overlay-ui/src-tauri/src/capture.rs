@@ -0,0 +1,150 @@
+//! Overlay Capture
+//!
+//! Grabs the main window's current frame, glass background included, for
+//! bug reports. Pixels come back as straight (non-premultiplied) RGBA so
+//! the saved PNG keeps the window's transparency.
+//! - macOS: CGWindowListCreateImage
+//! - Windows: PrintWindow into a 32-bit DIB
+//! - Linux: gdk_pixbuf_get_from_window (XGetImage), X11 only
+
+use image::RgbaImage;
+use tauri::WebviewWindow;
+
+/// Directory under the app data dir for captures saved without a path.
+pub const CAPTURE_DIR: &str = "captures";
+
+/// Grab the window's pixels. Must run on the main thread.
+pub fn grab(window: &WebviewWindow) -> Result<RgbaImage, String> {
+    #[cfg(target_os = "macos")]
+    return grab_macos(window);
+
+    #[cfg(target_os = "windows")]
+    return grab_windows(window);
+
+    #[cfg(target_os = "linux")]
+    return grab_linux(window);
+}
+
+#[cfg(target_os = "macos")]
+fn grab_macos(window: &WebviewWindow) -> Result<RgbaImage, String> {
+    use cocoa::base::id;
+    use core_graphics::display::CGRectNull;
+    use core_graphics::window::{create_image, kCGWindowImageBoundsIgnoreFraming, kCGWindowListOptionIncludingWindow};
+    use objc::{msg_send, sel, sel_impl};
+
+    let ns_window = window.ns_window().map_err(|e| e.to_string())? as id;
+    let window_id: i64 = unsafe { msg_send![ns_window, windowNumber] };
+    let image = create_image(
+        unsafe { CGRectNull },
+        kCGWindowListOptionIncludingWindow,
+        window_id as u32,
+        kCGWindowImageBoundsIgnoreFraming,
+    )
+    .ok_or("CGWindowListCreateImage returned no image")?;
+
+    if image.bits_per_pixel() != 32 {
+        return Err(format!("Unsupported pixel format: {} bpp", image.bits_per_pixel()));
+    }
+    let (width, height, stride) = (image.width(), image.height(), image.bytes_per_row());
+    let data = image.data();
+    // Window images are BGRA, premultiplied alpha first, little-endian
+    Ok(from_bgra_premultiplied(data.bytes(), width as u32, height as u32, stride))
+}
+
+#[cfg(target_os = "windows")]
+fn grab_windows(window: &WebviewWindow) -> Result<RgbaImage, String> {
+    use windows::Win32::Graphics::Gdi::{
+        CreateCompatibleDC, CreateDIBSection, DeleteDC, DeleteObject, GetDC, ReleaseDC, SelectObject, BITMAPINFO,
+        BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+    };
+    use windows::Win32::Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS, PW_CLIENTONLY};
+
+    // PW_RENDERFULLCONTENT, needed to capture DirectComposition (WebView2) content
+    const PW_RENDERFULLCONTENT: u32 = 2;
+
+    let hwnd = window.hwnd().map_err(|e| e.to_string())?;
+    let size = window.inner_size().map_err(|e| e.to_string())?;
+    let (width, height) = (size.width, size.height);
+
+    let info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width as i32,
+            // Negative height makes the DIB top-down
+            biHeight: -(height as i32),
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    unsafe {
+        let screen = GetDC(Some(hwnd));
+        let dc = CreateCompatibleDC(Some(screen));
+        let mut bits = std::ptr::null_mut();
+        let result = CreateDIBSection(Some(dc), &info, DIB_RGB_COLORS, &mut bits, None, 0)
+            .map_err(|e| e.to_string())
+            .and_then(|bitmap| {
+                let previous = SelectObject(dc, bitmap.into());
+                let printed = PrintWindow(hwnd, dc, PRINT_WINDOW_FLAGS(PW_CLIENTONLY.0 | PW_RENDERFULLCONTENT)).as_bool();
+                let pixels = std::slice::from_raw_parts(bits as *const u8, (width * height * 4) as usize).to_vec();
+                SelectObject(dc, previous);
+                let _ = DeleteObject(bitmap.into());
+                if printed {
+                    Ok(pixels)
+                } else {
+                    Err("PrintWindow failed".to_string())
+                }
+            });
+        let _ = DeleteDC(dc);
+        ReleaseDC(Some(hwnd), screen);
+
+        let mut pixels = result?;
+        // GDI leaves alpha at zero for windows it draws opaque; treat those as opaque
+        if pixels.chunks_exact(4).all(|p| p[3] == 0) {
+            pixels.chunks_exact_mut(4).for_each(|p| p[3] = 255);
+        }
+        Ok(from_bgra_premultiplied(&pixels, width, height, width as usize * 4))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn grab_linux(window: &WebviewWindow) -> Result<RgbaImage, String> {
+    use gtk::gdk::prelude::*;
+    use gtk::prelude::*;
+
+    let gtk_window = window.gtk_window().map_err(|e| e.to_string())?;
+    let gdk_window = gtk_window.window().ok_or("Window is not realized")?;
+    if !gdk_window.display().backend().is_x11() {
+        return Err("Capturing the overlay is only supported on X11".to_string());
+    }
+
+    let (width, height) = (gdk_window.width(), gdk_window.height());
+    let pixbuf = gdk_window
+        .pixbuf(0, 0, width, height)
+        .ok_or("gdk_pixbuf_get_from_window returned no image")?;
+
+    let (width, height) = (pixbuf.width() as u32, pixbuf.height() as u32);
+    let channels = pixbuf.n_channels() as usize;
+    let stride = pixbuf.rowstride() as usize;
+    let bytes = pixbuf.read_pixel_bytes();
+    // Pixbufs hold straight RGB(A); without an alpha channel the window is opaque
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        let i = y as usize * stride + x as usize * channels;
+        let alpha = if channels == 4 { bytes[i + 3] } else { 255 };
+        image::Rgba([bytes[i], bytes[i + 1], bytes[i + 2], alpha])
+    }))
+}
+
+/// Convert premultiplied BGRA rows to straight RGBA.
+#[cfg(not(target_os = "linux"))]
+fn from_bgra_premultiplied(bytes: &[u8], width: u32, height: u32, stride: usize) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let i = y as usize * stride + x as usize * 4;
+        let (b, g, r, a) = (bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]);
+        let unpremultiply = |c: u8| if a == 0 { 0 } else { (u32::from(c) * 255 / u32::from(a)).min(255) as u8 };
+        image::Rgba([unpremultiply(r), unpremultiply(g), unpremultiply(b), a])
+    })
+}
@@ -0,0 +1,77 @@
+//! Click-Through
+//!
+//! Lets clicks fall through the overlay to the app behind it. On macOS
+//! Tauri's `set_ignore_cursor_events` maps to `setIgnoresMouseEvents:`,
+//! which keeps working at the floating window level.
+//!
+//! An optional hit region (e.g. a toggle button) stays interactive: a
+//! background task watches the cursor and only ignores cursor events while
+//! it is outside the region.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::WebviewWindow;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Window area that stays interactive, in logical pixels relative to the
+/// window's top-left corner.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct HitRegion {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl HitRegion {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ClickThrough {
+    pub enabled: bool,
+    pub hit_region: Option<HitRegion>,
+}
+
+/// Toggle cursor passthrough for the whole window. With a hit region,
+/// returns the task that keeps the region interactive; abort it to stop.
+pub fn apply(window: &WebviewWindow, state: ClickThrough) -> tauri::Result<Option<JoinHandle<()>>> {
+    match (state.enabled, state.hit_region) {
+        (true, Some(region)) => Ok(Some(watch_hit_region(window.clone(), region))),
+        (enabled, _) => {
+            window.set_ignore_cursor_events(enabled)?;
+            Ok(None)
+        }
+    }
+}
+
+fn watch_hit_region(window: WebviewWindow, region: HitRegion) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut ignoring = None;
+        let mut tick = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tick.tick().await;
+            // Pass clicks through unless the cursor is over the hit region
+            let ignore = !cursor_in_region(&window, &region).unwrap_or(false);
+            if ignoring != Some(ignore) {
+                let _ = window.set_ignore_cursor_events(ignore);
+                ignoring = Some(ignore);
+            }
+        }
+    })
+}
+
+fn cursor_in_region(window: &WebviewWindow, region: &HitRegion) -> Option<bool> {
+    let cursor = window.cursor_position().ok()?;
+    let origin = window.inner_position().ok()?;
+    let scale = window.scale_factor().ok()?;
+
+    let x = (cursor.x - origin.x as f64) / scale;
+    let y = (cursor.y - origin.y as f64) / scale;
+    Some(region.contains(x, y))
+}
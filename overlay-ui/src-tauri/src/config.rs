@@ -0,0 +1,172 @@
+//! Server Configuration
+//!
+//! Optional overrides loaded from `server.json` in the app config dir.
+//! Missing fields (or a missing file) fall back to the defaults below.
+
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// File name of the config inside the app config dir.
+pub const CONFIG_FILE: &str = "server.json";
+
+/// What happens to a connection over `max_connections`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitMode {
+    /// Close it with "try again later" and warn.
+    #[default]
+    Reject,
+    /// Hold it until another connection closes.
+    Queue,
+}
+
+/// What the server listens on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// Loopback TCP on the configured port. Works everywhere.
+    #[default]
+    Tcp,
+    /// Unix domain socket in the runtime dir; Unix only, falls back to TCP elsewhere.
+    Unix,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Loopback TCP or a Unix domain socket.
+    pub transport: Transport,
+    /// Largest inbound message (and frame) accepted, in bytes.
+    pub max_message_size: usize,
+    /// Interval between keepalive pings, in milliseconds.
+    pub ping_interval_ms: u64,
+    /// Unanswered pings before the connection is reported as degraded.
+    pub degraded_after_missed: u32,
+    /// Unanswered pings before the connection is closed.
+    pub ping_timeout_missed: u32,
+    /// Longest time streaming deltas are buffered before reaching the webview.
+    pub delta_flush_ms: u64,
+    /// Completed messages kept for `get_message_history` / `replay_history`.
+    pub history_size: usize,
+    /// Serve `wss://` with a self-signed certificate instead of plain `ws://`.
+    pub tls: bool,
+    /// Sustained messages per second allowed in each direction per connection.
+    pub rate_limit_per_sec: f64,
+    /// Messages allowed in a burst above the sustained rate.
+    pub rate_limit_burst: u32,
+    /// Longest a single send to an agent may block.
+    pub send_timeout_ms: u64,
+    /// Consecutive send timeouts after which the agent is dropped as dead.
+    pub send_timeouts_before_drop: u32,
+    /// Long side of screenshot thumbnails sent to the webview, in pixels.
+    pub thumbnail_max_px: u32,
+    /// Age after which stored screenshots are deleted.
+    pub screenshot_ttl_secs: u64,
+    /// Screenshots whose difference hash is fewer than this many bits (of
+    /// 64) from the last one shown are suppressed; 0 shows every capture.
+    pub screenshot_dedup_distance: u32,
+    /// How long the agent has to acknowledge a user input before it counts as failed.
+    pub ack_timeout_ms: u64,
+    /// Interval between server-health events; 0 turns them off.
+    pub health_interval_secs: u64,
+    /// Longest user input or context accepted from the UI, in characters.
+    pub max_input_chars: usize,
+    /// How long a disconnected agent's session waits for it to reconnect with
+    /// the same `session_id`; 0 turns resuming off.
+    pub session_idle_secs: u64,
+    /// Time without agent traffic after which a busy agent is assumed idle;
+    /// 0 turns this off.
+    pub busy_timeout_secs: u64,
+    /// Unacknowledged user inputs allowed at once; further input is refused.
+    pub max_in_flight: usize,
+    /// Enable developer commands such as `send_raw_to_agent`. On by default
+    /// only in debug builds.
+    pub dev_commands: bool,
+    /// Agent connections served at once; 0 means unlimited.
+    pub max_connections: usize,
+    pub connection_limit_mode: LimitMode,
+    /// Interface the TCP server binds. Anything but loopback is refused
+    /// unless `allow_remote` is set.
+    pub bind_address: IpAddr,
+    /// Accept agents on other machines; `0.0.0.0` listens on every interface.
+    pub allow_remote: bool,
+    /// Completed messages shown per second during a burst such as a
+    /// history replay; 0 shows them as fast as they arrive.
+    pub replay_rate_per_sec: u32,
+    /// Tries at binding the TCP port before giving up, to ride out a
+    /// previous instance that still holds it.
+    pub bind_attempts: u32,
+    /// Delay before the first bind retry; doubles on each one after.
+    pub bind_retry_base_ms: u64,
+    /// Close an agent connection after this long without data frames in
+    /// either direction or pings from the agent; 0 never does. The
+    /// overlay's own keepalive pings don't count as activity.
+    pub idle_disconnect_ms: u64,
+    /// URL schemes `open_url` may open. Add `file` to let agents open
+    /// local files.
+    pub open_url_schemes: Vec<String>,
+    /// How long an `open_url` waits for the user to confirm it.
+    pub open_confirm_timeout_ms: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            transport: Transport::Tcp,
+            max_message_size: 4 * 1024 * 1024,
+            ping_interval_ms: 5000,
+            degraded_after_missed: 2,
+            ping_timeout_missed: 6,
+            delta_flush_ms: 30,
+            history_size: 200,
+            tls: false,
+            rate_limit_per_sec: 100.0,
+            rate_limit_burst: 200,
+            send_timeout_ms: 2000,
+            send_timeouts_before_drop: 3,
+            thumbnail_max_px: 480,
+            screenshot_ttl_secs: 3600,
+            screenshot_dedup_distance: 5,
+            ack_timeout_ms: 10_000,
+            health_interval_secs: 10,
+            max_input_chars: 32_000,
+            session_idle_secs: 30,
+            busy_timeout_secs: 300,
+            max_in_flight: 64,
+            dev_commands: cfg!(debug_assertions),
+            max_connections: 8,
+            connection_limit_mode: LimitMode::Reject,
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            allow_remote: false,
+            replay_rate_per_sec: 0,
+            bind_attempts: 5,
+            bind_retry_base_ms: 200,
+            idle_disconnect_ms: 0,
+            open_url_schemes: crate::links::default_schemes(),
+            open_confirm_timeout_ms: 60_000,
+        }
+    }
+}
+
+/// Load the config, falling back to defaults when the file is missing or invalid.
+pub fn load(path: &Path) -> ServerConfig {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => return ServerConfig::default(),
+    };
+
+    let mut config: ServerConfig = serde_json::from_str(&text).unwrap_or_else(|e| {
+        tracing::warn!(path = %path.display(), error = %e, "Invalid config, using defaults");
+        ServerConfig::default()
+    });
+    // A rate of zero would hold every frame forever
+    if !(config.rate_limit_per_sec.is_finite() && config.rate_limit_per_sec > 0.0) {
+        let default = ServerConfig::default().rate_limit_per_sec;
+        tracing::warn!(rate = config.rate_limit_per_sec, default, "Invalid rate_limit_per_sec, using default");
+        config.rate_limit_per_sec = default;
+    }
+    config
+}
@@ -0,0 +1,105 @@
+//! Connection Limit
+//!
+//! Caps how many agent connections are served at once, so a buggy agent
+//! reconnecting in a loop can't exhaust sockets and tasks. Each served
+//! connection holds a `Slot`; dropping it frees the slot for the next one.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+/// Counts served connections against a maximum that can change at runtime.
+/// A maximum of 0 means unlimited.
+#[derive(Default)]
+pub struct ConnectionLimit {
+    max: AtomicUsize,
+    active: AtomicUsize,
+    freed: Notify,
+}
+
+/// A held connection slot, released on drop.
+pub struct Slot(Arc<ConnectionLimit>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::AcqRel);
+        self.0.freed.notify_waiters();
+    }
+}
+
+impl ConnectionLimit {
+    pub fn max(&self) -> usize {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Change the maximum. Connections over a lowered maximum are kept;
+    /// new ones wait or are refused until enough of them close.
+    pub fn set_max(&self, max: usize) {
+        self.max.store(max, Ordering::Relaxed);
+        self.freed.notify_waiters();
+    }
+
+    /// Take a slot if one is free.
+    pub fn try_acquire(self: &Arc<Self>) -> Option<Slot> {
+        let max = self.max();
+        self.active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| (max == 0 || active < max).then_some(active + 1))
+            .ok()
+            .map(|_| Slot(self.clone()))
+    }
+
+    /// Wait for a free slot.
+    pub async fn acquire(self: &Arc<Self>) -> Slot {
+        loop {
+            // Registered before the check so a slot freed in between still wakes us
+            let freed = self.freed.notified();
+            if let Some(slot) = self.try_acquire() {
+                return slot;
+            }
+            freed.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_past_the_maximum_until_a_slot_frees() {
+        let limit = Arc::new(ConnectionLimit::default());
+        limit.set_max(2);
+        let first = limit.try_acquire().expect("first slot");
+        let second = limit.try_acquire().expect("second slot");
+        assert!(limit.try_acquire().is_none());
+
+        drop(first);
+        let third = limit.try_acquire().expect("freed slot");
+        assert!(limit.try_acquire().is_none());
+        drop((second, third));
+        assert_eq!(limit.active.load(Ordering::Acquire), 0);
+    }
+
+    #[test]
+    fn zero_is_unlimited() {
+        let limit = Arc::new(ConnectionLimit::default());
+        let slots: Vec<_> = (0..100).map(|_| limit.try_acquire()).collect();
+        assert!(slots.iter().all(Option::is_some));
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_a_release() {
+        let limit = Arc::new(ConnectionLimit::default());
+        limit.set_max(1);
+        let held = limit.try_acquire().unwrap();
+        let waiter = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.acquire().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+        drop(held);
+        let _slot = tokio::time::timeout(std::time::Duration::from_secs(1), waiter).await.unwrap().unwrap();
+    }
+}
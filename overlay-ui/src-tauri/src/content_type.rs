@@ -0,0 +1,60 @@
+//! Content Types
+//!
+//! How the webview should render a message's content. Agents tag messages
+//! with `content_type`: `"text"`, `"markdown"`, `"code"` or an object such
+//! as `{"kind": "code", "language": "rust"}`. Anything unrecognised becomes
+//! plain text, the safe default, since agent markdown is untrusted HTML.
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Longest code language name kept.
+const MAX_LANGUAGE_LEN: usize = 32;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ContentType {
+    #[default]
+    Text,
+    Markdown,
+    Code {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+    },
+}
+
+impl ContentType {
+    fn from_value(value: &serde_json::Value) -> Self {
+        let (kind, language) = match value {
+            serde_json::Value::String(kind) => (kind.as_str(), None),
+            serde_json::Value::Object(fields) => (
+                fields.get("kind").and_then(|kind| kind.as_str()).unwrap_or_default(),
+                fields.get("language").and_then(|language| language.as_str()),
+            ),
+            _ => ("", None),
+        };
+        match kind.to_ascii_lowercase().as_str() {
+            "text" => Self::Text,
+            "markdown" => Self::Markdown,
+            "code" => Self::Code { language: language.and_then(normalize_language) },
+            _ => {
+                tracing::debug!(%value, "Unknown content type, using text");
+                Self::Text
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from_value(&serde_json::Value::deserialize(deserializer)?))
+    }
+}
+
+/// Lowercase language name, or `None` if it isn't safe to put in a class name.
+fn normalize_language(language: &str) -> Option<String> {
+    let language = language.trim().to_ascii_lowercase();
+    let valid = !language.is_empty()
+        && language.len() <= MAX_LANGUAGE_LEN
+        && language.chars().all(|c| c.is_ascii_alphanumeric() || "+#._-".contains(c));
+    valid.then_some(language)
+}
@@ -0,0 +1,136 @@
+//! Delta Coalescing
+//!
+//! Streaming deltas arrive token-by-token. Emitting one Tauri event per token
+//! floods the IPC channel, so deltas are buffered per message id and flushed
+//! at most once per flush interval, or immediately when a message is done.
+
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+
+/// Streaming fragment from the agent:
+/// `{"type": "delta", "message_id": "...", "delta": "...", "done": false}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeltaMessage {
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub message_id: String,
+    #[serde(default)]
+    pub delta: String,
+    #[serde(default)]
+    pub done: bool,
+}
+
+/// Payload of `agent-message-delta` events; `delta` may hold several
+/// coalesced fragments.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeltaEvent {
+    pub message_id: String,
+    pub delta: String,
+    pub done: bool,
+}
+
+pub struct DeltaBuffer {
+    interval: Duration,
+    // Pending text per message id, in arrival order
+    pending: Vec<(String, String)>,
+    deadline: Option<Instant>,
+    // Full text so far per message id, kept until the message is done
+    joined: Vec<(String, String)>,
+    completed: Option<String>,
+}
+
+impl DeltaBuffer {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            pending: Vec::new(),
+            deadline: None,
+            joined: Vec::new(),
+            completed: None,
+        }
+    }
+
+    /// When the buffered deltas should be flushed, if any are pending.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Buffer a delta. A `done` delta flushes its message immediately and the
+    /// returned event carries everything still pending for it.
+    pub fn push(&mut self, msg: DeltaMessage) -> Option<DeltaEvent> {
+        let index = self.pending.iter().position(|(id, _)| *id == msg.message_id);
+
+        let joined = self.joined.iter().position(|(id, _)| *id == msg.message_id);
+        if msg.done {
+            let mut full = joined.map(|i| self.joined.remove(i).1).unwrap_or_default();
+            full.push_str(&msg.delta);
+            self.completed = Some(full);
+        } else {
+            match joined {
+                Some(i) => self.joined[i].1.push_str(&msg.delta),
+                None => self.joined.push((msg.message_id.clone(), msg.delta.clone())),
+            }
+        }
+
+        if msg.done {
+            let mut text = index.map(|i| self.pending.remove(i).1).unwrap_or_default();
+            text.push_str(&msg.delta);
+            if self.pending.is_empty() {
+                self.deadline = None;
+            }
+            return Some(DeltaEvent { message_id: msg.message_id, delta: text, done: true });
+        }
+
+        match index {
+            Some(i) => self.pending[i].1.push_str(&msg.delta),
+            None => self.pending.push((msg.message_id, msg.delta)),
+        }
+        self.deadline.get_or_insert_with(|| Instant::now() + self.interval);
+        None
+    }
+
+    /// Full text of the message last completed by `push`, taken once.
+    pub fn take_completed(&mut self) -> Option<String> {
+        self.completed.take()
+    }
+
+    /// Drain everything buffered, one event per message id.
+    pub fn take_all(&mut self) -> Vec<DeltaEvent> {
+        self.deadline = None;
+        self.pending
+            .drain(..)
+            .map(|(message_id, delta)| DeltaEvent { message_id, delta, done: false })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(text: &str, done: bool) -> DeltaMessage {
+        DeltaMessage { msg_type: "delta".into(), message_id: "m1".into(), delta: text.into(), done }
+    }
+
+    #[test]
+    fn coalesces_deltas_within_the_window() {
+        let mut buffer = DeltaBuffer::new(Duration::from_secs(60));
+        let tokens: Vec<String> = (0..100).map(|i| format!("t{i} ")).collect();
+
+        let mut events = Vec::new();
+        for token in &tokens[..99] {
+            events.extend(buffer.push(delta(token, false)));
+        }
+        assert!(events.is_empty());
+        assert!(buffer.deadline().is_some());
+        events.extend(buffer.take_all());
+        events.extend(buffer.push(delta(&tokens[99], true)));
+
+        assert_eq!(events.len(), 2);
+        let text: String = events.iter().map(|event| event.delta.as_str()).collect();
+        assert_eq!(text, tokens.concat());
+        assert!(events[1].done);
+        assert_eq!(buffer.take_completed(), Some(tokens.concat()));
+        assert!(buffer.deadline().is_none());
+    }
+}
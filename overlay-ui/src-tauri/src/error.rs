@@ -0,0 +1,98 @@
+//! Command Errors
+//!
+//! Error type returned by every Tauri command. It serializes as
+//! `{ kind, message }` so the frontend can switch on `kind` and still show
+//! `message` to the user.
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+
+use crate::SendError;
+
+#[derive(Debug)]
+pub enum CommandError {
+    /// No agent is connected.
+    NotConnected,
+    /// The agent stopped reading and the send timed out.
+    SendTimeout,
+    /// The outbound rate limit was hit.
+    RateLimited,
+    /// A payload could not be encoded or decoded.
+    Serialize(String),
+    /// An argument was malformed or out of range.
+    InvalidArg(String),
+    /// A window or other named resource does not exist.
+    NotFound(String),
+    /// A socket, file or platform call failed.
+    Io(String),
+}
+
+impl CommandError {
+    /// Discriminator the frontend switches on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NotConnected => "not_connected",
+            Self::SendTimeout => "send_timeout",
+            Self::RateLimited => "rate_limited",
+            Self::Serialize(_) => "serialize",
+            Self::InvalidArg(_) => "invalid_arg",
+            Self::NotFound(_) => "not_found",
+            Self::Io(_) => "io",
+        }
+    }
+
+    pub fn window_not_found(label: &str) -> Self {
+        Self::NotFound(format!("Window not found: {}", label))
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConnected => f.write_str("Not connected to agent"),
+            Self::SendTimeout => f.write_str("Send timed out"),
+            Self::RateLimited => f.write_str("Rate limited"),
+            Self::Serialize(e) => write!(f, "Serialization failed: {}", e),
+            Self::InvalidArg(e) | Self::NotFound(e) | Self::Io(e) => f.write_str(e),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CommandError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<SendError> for CommandError {
+    fn from(e: SendError) -> Self {
+        match e {
+            SendError::NotConnected => Self::NotConnected,
+            SendError::TimedOut { .. } => Self::SendTimeout,
+            SendError::Failed(e) => Self::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for CommandError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serialize(e.to_string())
+    }
+}
+
+impl From<tauri::Error> for CommandError {
+    fn from(e: tauri::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
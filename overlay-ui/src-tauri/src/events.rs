@@ -0,0 +1,44 @@
+//! Agent Event Sink
+//!
+//! Agent events go through `EventSink` rather than straight to
+//! `AppHandle::emit`, so the connection handling can be driven by anything
+//! that records events, not only a running webview. The sink also carries
+//! the `AppState` the connection works against; window and path side
+//! effects only happen when it is backed by a running app.
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::{AppState, DEFAULT_EVENT_TARGET};
+
+pub trait EventSink: Clone + Send + Sync + 'static {
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S);
+
+    fn app_state(&self) -> &AppState;
+
+    /// The running app, or `None` when events are only recorded.
+    fn app_handle(&self) -> Option<&AppHandle>;
+}
+
+/// Emits to the event target window, or to every window when that window
+/// doesn't exist.
+impl EventSink for AppHandle {
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        let target = self.state::<AppState>().event_target.lock().unwrap().clone();
+        let target = target.as_deref().unwrap_or(DEFAULT_EVENT_TARGET);
+        if self.get_webview_window(target).is_some() {
+            let _ = self.emit_to(target, event, payload);
+        } else {
+            tracing::debug!(target, event, "Event target window not found, broadcasting");
+            let _ = self.emit(event, payload);
+        }
+    }
+
+    fn app_state(&self) -> &AppState {
+        self.state::<AppState>().inner()
+    }
+
+    fn app_handle(&self) -> Option<&AppHandle> {
+        Some(self)
+    }
+}
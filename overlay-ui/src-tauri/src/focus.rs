@@ -0,0 +1,76 @@
+//! Focus Policy
+//!
+//! Whether the overlay takes keyboard focus. An always-on-top window that
+//! grabs focus whenever it appears interrupts typing in other apps, so it
+//! can be kept from ever becoming the key window, or only be focused when
+//! the agent asks for the user's attention.
+//!
+//! Non-focusable maps to `canBecomeKeyWindow` returning NO on macOS and to
+//! `WS_EX_NOACTIVATE` on Windows, both through Tauri's `set_focusable`.
+
+use serde::{Deserialize, Serialize};
+use tauri::WebviewWindow;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FocusPolicy {
+    /// Never focused, even when clicked; shown without activating.
+    NeverSteal,
+    /// Focused whenever it is shown.
+    #[default]
+    FocusOnShow,
+    /// Shown without focus; focused when the agent requests attention.
+    FocusOnMessage,
+}
+
+impl FocusPolicy {
+    /// Parse a policy name such as `NeverSteal` (case-insensitive).
+    pub fn parse(name: &str) -> Option<Self> {
+        const ALL: [(&str, FocusPolicy); 3] = [
+            ("NeverSteal", FocusPolicy::NeverSteal),
+            ("FocusOnShow", FocusPolicy::FocusOnShow),
+            ("FocusOnMessage", FocusPolicy::FocusOnMessage),
+        ];
+        ALL.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, p)| *p)
+    }
+}
+
+pub fn apply(window: &WebviewWindow, policy: FocusPolicy) -> tauri::Result<()> {
+    window.set_focusable(policy != FocusPolicy::NeverSteal)
+}
+
+/// Show the window, focusing it only if the policy allows.
+pub fn show(window: &WebviewWindow, policy: FocusPolicy) -> tauri::Result<()> {
+    window.show()?;
+    match policy {
+        FocusPolicy::FocusOnShow => window.set_focus(),
+        FocusPolicy::NeverSteal => {
+            // Bring it forward even while another app is active, without activating us
+            #[cfg(target_os = "macos")]
+            order_front_regardless(window);
+            Ok(())
+        }
+        FocusPolicy::FocusOnMessage => Ok(()),
+    }
+}
+
+/// Focus the window because the agent asked for the user, if the policy allows.
+pub fn on_attention(window: &WebviewWindow, policy: FocusPolicy) -> tauri::Result<()> {
+    if policy != FocusPolicy::FocusOnMessage {
+        return Ok(());
+    }
+    window.show()?;
+    window.set_focus()
+}
+
+#[cfg(target_os = "macos")]
+fn order_front_regardless(window: &WebviewWindow) {
+    use cocoa::base::id;
+    use objc::{msg_send, sel, sel_impl};
+
+    unsafe {
+        if let Ok(ns_window_ptr) = window.ns_window() {
+            let ns_window: id = ns_window_ptr as id;
+            let _: () = msg_send![ns_window, orderFrontRegardless];
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Glass Presets
+//!
+//! Named snapshots of the glass look (tint, theme, window level) so users
+//! can switch between looks in one step. Saved presets are JSON files in
+//! `glass_presets/` under the app config dir; two built-ins ship with the
+//! app and a saved preset of the same name takes precedence.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tauri::Theme;
+
+use crate::window_level::WindowLevel;
+
+/// Directory of saved presets inside the app config dir.
+pub const PRESET_DIR: &str = "glass_presets";
+
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlassPreset {
+    /// RGBA tint; `None` uses the theme default.
+    pub tint: Option<(u8, u8, u8, u8)>,
+    /// Pinned theme; `None` follows the OS.
+    pub theme: Option<Theme>,
+    pub window_level: WindowLevel,
+    pub current_space_only: bool,
+}
+
+impl Default for GlassPreset {
+    fn default() -> Self {
+        Self { tint: None, theme: None, window_level: WindowLevel::Floating, current_space_only: false }
+    }
+}
+
+/// Built-in presets: a dense tint for reading long output, and a faint one
+/// that stays out of the way.
+fn builtin(name: &str) -> Option<GlassPreset> {
+    match name {
+        "reading" => Some(GlassPreset { tint: Some((18, 18, 22, 180)), theme: Some(Theme::Dark), ..Default::default() }),
+        "ambient" => Some(GlassPreset { tint: Some((20, 20, 20, 30)), ..Default::default() }),
+        _ => None,
+    }
+}
+
+const BUILTIN_NAMES: [&str; 2] = ["reading", "ambient"];
+
+/// Names are used as file names: 1-64 ASCII letters, digits, `-` or `_`.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Load a preset, saved first and then built-in. `None` if neither exists.
+pub fn load(dir: &Path, name: &str) -> io::Result<Option<GlassPreset>> {
+    match fs::read_to_string(dir.join(format!("{}.json", name))) {
+        Ok(text) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(builtin(name)),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn save(dir: &Path, name: &str, preset: &GlassPreset) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let json = serde_json::to_string_pretty(preset).map_err(io::Error::other)?;
+    fs::write(dir.join(format!("{}.json", name)), json)
+}
+
+/// Built-in and saved preset names, sorted.
+pub fn list(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN_NAMES.iter().map(|name| name.to_string()).collect();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    if valid_name(stem) {
+                        names.push(stem.to_string());
+                    }
+                }
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
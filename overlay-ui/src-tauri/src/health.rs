@@ -0,0 +1,95 @@
+//! Server Health
+//!
+//! Counters behind the `server-health` event and `get_server_health`.
+//! They are plain atomics so the read loop and the send commands can bump
+//! them without touching the connection mutex.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use serde::Serialize;
+
+pub struct ServerStats {
+    started_at: Instant,
+    connections: AtomicUsize,
+    messages_in: AtomicU64,
+    messages_out: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    frames_suppressed: AtomicU64,
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            connections: AtomicUsize::new(0),
+            messages_in: AtomicU64::new(0),
+            messages_out: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            frames_suppressed: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ServerStats {
+    pub fn connected(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn disconnected(&self) {
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Count a text or binary frame received from an agent.
+    pub fn record_in(&self, bytes: usize) {
+        self.messages_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count a text or binary frame sent to an agent.
+    pub fn record_out(&self, bytes: usize) {
+        self.messages_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count a screenshot dropped as a near-duplicate of the last one.
+    pub fn record_suppressed_frame(&self) {
+        self.frames_suppressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, queue_depth: usize, listening: bool, port: Option<u16>) -> ServerHealth {
+        ServerHealth {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            connections: self.connections.load(Ordering::Relaxed),
+            messages_in: self.messages_in.load(Ordering::Relaxed),
+            messages_out: self.messages_out.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            frames_suppressed: self.frames_suppressed.load(Ordering::Relaxed),
+            queue_depth,
+            listening,
+            port,
+        }
+    }
+}
+
+/// Payload of server-health events and the result of `get_server_health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerHealth {
+    pub uptime_secs: u64,
+    pub connections: usize,
+    pub messages_in: u64,
+    pub messages_out: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Screenshots not shown because they matched the previous one.
+    pub frames_suppressed: u64,
+    /// User inputs sent but not yet acknowledged by the agent.
+    pub queue_depth: usize,
+    /// Whether the accept loop is running, on a port or a Unix socket.
+    pub listening: bool,
+    /// `None` when listening on a Unix socket or not listening.
+    pub port: Option<u16>,
+}
@@ -0,0 +1,95 @@
+//! History Filter
+//!
+//! Narrows `get_message_history` to the turns being debugged. Message
+//! timestamps are free-form strings: agents send either RFC 3339 or the
+//! bare `HH:MM:SS` the Jarvis client uses, so both are understood here.
+
+use chrono::{DateTime, FixedOffset, Local, NaiveTime};
+use serde::Deserialize;
+
+/// Every field is optional; an empty filter matches everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryFilter {
+    pub role: Option<String>,
+    /// Inclusive lower bound, RFC 3339 or `HH:MM:SS`.
+    pub since: Option<String>,
+    /// Inclusive upper bound, RFC 3339 or `HH:MM:SS`.
+    pub until: Option<String>,
+    /// Keep only the most recent matches.
+    pub limit: Option<usize>,
+}
+
+/// A parsed message timestamp. A bare time of day can only be compared
+/// with the time-of-day part of a full timestamp.
+#[derive(Debug, Clone, Copy)]
+enum Stamp {
+    Full(DateTime<FixedOffset>),
+    TimeOfDay(NaiveTime),
+}
+
+impl Stamp {
+    fn parse(text: &str) -> Option<Stamp> {
+        let text = text.trim();
+        if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+            return Some(Stamp::Full(time));
+        }
+        NaiveTime::parse_from_str(text, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+            .ok()
+            .map(Stamp::TimeOfDay)
+    }
+
+    fn time_of_day(self) -> NaiveTime {
+        match self {
+            Stamp::Full(time) => time.with_timezone(&Local).time(),
+            Stamp::TimeOfDay(time) => time,
+        }
+    }
+
+    fn cmp(self, other: Stamp) -> std::cmp::Ordering {
+        match (self, other) {
+            (Stamp::Full(a), Stamp::Full(b)) => a.cmp(&b),
+            _ => self.time_of_day().cmp(&other.time_of_day()),
+        }
+    }
+}
+
+/// A filter with its bounds parsed, ready to test messages against.
+pub struct Matcher<'a> {
+    role: Option<&'a str>,
+    since: Option<Stamp>,
+    until: Option<Stamp>,
+}
+
+impl HistoryFilter {
+    /// Parse the bounds, naming the first one that can't be read.
+    pub fn matcher(&self) -> Result<Matcher<'_>, String> {
+        let bound = |name: &str, value: &Option<String>| match value {
+            Some(text) => Stamp::parse(text)
+                .map(Some)
+                .ok_or_else(|| format!("Invalid {} timestamp: {}", name, text)),
+            None => Ok(None),
+        };
+        Ok(Matcher {
+            role: self.role.as_deref(),
+            since: bound("since", &self.since)?,
+            until: bound("until", &self.until)?,
+        })
+    }
+}
+
+impl Matcher<'_> {
+    /// Whether a message matches. With a time bound set, messages whose
+    /// timestamp can't be parsed are left out.
+    pub fn matches(&self, role: &str, timestamp: &str) -> bool {
+        if self.role.is_some_and(|wanted| wanted != role) {
+            return false;
+        }
+        if self.since.is_none() && self.until.is_none() {
+            return true;
+        }
+        let Some(stamp) = Stamp::parse(timestamp) else { return false };
+        self.since.is_none_or(|since| stamp.cmp(since).is_ge()) && self.until.is_none_or(|until| stamp.cmp(until).is_le())
+    }
+}
@@ -0,0 +1,127 @@
+//! Idle Dimming
+//!
+//! Fades the overlay after a period with no agent traffic and no focus,
+//! and brings it back on the next message or interaction.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::WebviewWindow;
+
+use crate::animation::Motion;
+use crate::liquid_glass;
+
+/// How often the idle timer is checked.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+const FADE_DURATION: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdleDimming {
+    pub enabled: bool,
+    /// Inactivity before the window dims.
+    pub timeout_ms: u64,
+    /// Window opacity while dimmed, as a fraction of the base opacity (0.1-1.0).
+    pub dimmed_opacity: f64,
+}
+
+impl Default for IdleDimming {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_ms: 120_000,
+            dimmed_opacity: 0.4,
+        }
+    }
+}
+
+pub struct IdleTracker {
+    last_activity: Instant,
+    dimmed: bool,
+    // Opacity last applied to the window, shared with the running fade
+    opacity: Arc<Mutex<f64>>,
+    fade: Option<JoinHandle<()>>,
+}
+
+impl Default for IdleTracker {
+    fn default() -> Self {
+        Self {
+            last_activity: Instant::now(),
+            dimmed: false,
+            opacity: Arc::new(Mutex::new(1.0)),
+            fade: None,
+        }
+    }
+}
+
+impl IdleTracker {
+    /// Record activity. Returns true if the window was dimmed and should
+    /// be restored.
+    pub fn touch(&mut self) -> bool {
+        self.last_activity = Instant::now();
+        std::mem::replace(&mut self.dimmed, false)
+    }
+
+    /// Whether the window should dim now. Marks the tracker dimmed when it does.
+    pub fn should_dim(&mut self, settings: &IdleDimming, focused: bool) -> bool {
+        if !settings.enabled || self.dimmed || focused {
+            return false;
+        }
+        if self.last_activity.elapsed() < Duration::from_millis(settings.timeout_ms) {
+            return false;
+        }
+        self.dimmed = true;
+        true
+    }
+
+    /// Opacity last applied to the window.
+    pub fn opacity(&self) -> f64 {
+        *self.opacity.lock().unwrap()
+    }
+
+    /// Opacity the window should settle at: `base`, scaled down while dimmed.
+    pub fn target(&self, settings: &IdleDimming, base: f64) -> f64 {
+        if self.dimmed {
+            base * settings.dimmed_opacity
+        } else {
+            base
+        }
+    }
+
+    /// Set the opacity without animating. Must be called on the main thread.
+    pub fn set_now(&mut self, window: &WebviewWindow, opacity: f64) {
+        if let Some(fade) = self.fade.take() {
+            fade.abort();
+        }
+        *self.opacity.lock().unwrap() = opacity;
+        liquid_glass::set_opacity(window, opacity);
+    }
+
+    /// Animate the window from its current opacity to `target`, replacing
+    /// any fade still running.
+    pub fn fade_to(&mut self, window: &WebviewWindow, target: f64, motion: Motion) {
+        if let Some(fade) = self.fade.take() {
+            fade.abort();
+        }
+        self.fade = Some(fade(window.clone(), self.opacity.clone(), target, motion));
+    }
+}
+
+fn fade(window: WebviewWindow, opacity: Arc<Mutex<f64>>, target: f64, motion: Motion) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let start = *opacity.lock().unwrap();
+        let steps = motion.steps(FADE_DURATION);
+        let mut tick = tokio::time::interval(FADE_DURATION / steps);
+        for step in 1..=steps {
+            tick.tick().await;
+            let value = start + (target - start) * step as f64 / steps as f64;
+            *opacity.lock().unwrap() = value;
+            // Window opacity is set through AppKit/Win32, which need the main thread
+            let target_window = window.clone();
+            let _ = window.run_on_main_thread(move || liquid_glass::set_opacity(&target_window, value));
+        }
+    })
+}
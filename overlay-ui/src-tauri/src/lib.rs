@@ -1,26 +1,777 @@
+mod animation;
+mod app_snapshot;
+mod attention;
+mod audio;
+mod auth;
+mod binary;
+mod capture;
+mod click_through;
+mod config;
+mod conn_limit;
+mod content_type;
+mod delta;
+mod error;
+mod events;
+mod focus;
+mod glass_presets;
+mod health;
+mod history;
+mod idle;
+mod links;
 mod liquid_glass;
+mod overlay_mode;
+mod overlay_windows;
+mod logging;
+mod msgpack;
+mod pacing;
+mod permissions;
+mod prefs;
+mod rate_limit;
+mod screenshot;
+mod self_test;
+mod stability;
+mod status;
+mod tls;
+mod tool_calls;
+mod transcript;
+mod transcript_log;
+mod turns;
+mod window_layout;
+mod window_level;
 
+use base64::Engine;
+use error::CommandError;
+use events::EventSink;
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tauri::{
-    AppHandle, Emitter, Manager, State,
+    AppHandle, Emitter, Manager, RunEvent, State, Theme, WebviewWindow, WindowEvent,
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    menu::{Menu, MenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem},
 };
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::Mutex;
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio::sync::{watch, Mutex, Notify};
+use tokio_tungstenite::{
+    accept_async_with_config,
+    tungstenite::protocol::{frame::coding::CloseCode, CloseFrame, WebSocketConfig},
+    tungstenite::{Error as WsError, Message},
+};
 
 const WS_PORT: u16 = 19823;
 
-// Shared state for WebSocket writer
-type WsWriter = Arc<Mutex<Option<futures_util::stream::SplitSink<tokio_tungstenite::WebSocketStream<TcpStream>, Message>>>>;
+// Global hotkey that shows/hides the overlay unless the user picked another
+const DEFAULT_TOGGLE_HOTKEY: &str = "CmdOrCtrl+Shift+Space";
+
+// How long a new connection has to send its auth frame
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Range of agent protocol versions this server speaks. Bump PROTOCOL_VERSION
+// when message shapes change; raise MIN_PROTOCOL_VERSION when old shapes
+// are no longer understood.
+const PROTOCOL_VERSION: u32 = 2;
+const MIN_PROTOCOL_VERSION: u32 = 1;
+
+// First protocol version whose agents send a hello frame right after auth
+const HELLO_PROTOCOL_VERSION: u32 = 2;
+
+fn protocol_supported(version: u32) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+// Bump when the agent-status payload shape changes
+const STATUS_EVENT_VERSION: u32 = 1;
+
+// Binary payloads above this size go to a temp file instead of inline base64
+const INLINE_BINARY_LIMIT: usize = 256 * 1024;
+
+// Number of ping round trips averaged for agent-latency
+const LATENCY_WINDOW: usize = 10;
+
+// Quiet period after the last move/resize before the layout is saved
+const LAYOUT_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+// Longest ping_agent waits for a reply
+const MAX_ECHO_TIMEOUT_MS: u64 = 60_000;
+
+// Most messages load_transcript_page returns at once
+const MAX_TRANSCRIPT_PAGE: usize = 500;
+
+// Longest preview_glass accepts
+const MAX_GLASS_PREVIEW_MS: u64 = 60_000;
+
+// Parse failures kept for get_recent_errors
+const RECENT_ERRORS_LIMIT: usize = 20;
+
+// Warnings kept for get_recent_warnings
+const RECENT_WARNINGS_LIMIT: usize = 50;
+
+// Messages held back during do-not-disturb before the oldest are dropped
+const DND_HELD_LIMIT: usize = 500;
+
+// Share of max_in_flight at which QueueNearFull is reported
+const QUEUE_WARN_RATIO: f64 = 0.8;
+
+// Characters of the offending payload kept in a parse error record
+const ERROR_PAYLOAD_PREVIEW: usize = 512;
+
+// An agent reconnecting within this long after a disconnect is told it's resuming
+const RESUME_WINDOW: Duration = Duration::from_secs(60);
+
+// Pause after a transient accept error so fd exhaustion doesn't spin the loop
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+// Backoff between attempts to rebind a listener that failed
+const REBIND_INITIAL_DELAY: Duration = Duration::from_millis(500);
+const REBIND_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// Window that receives agent events unless set_event_target picks another
+const DEFAULT_EVENT_TARGET: &str = "main";
+
+// Id of the tray icon built in setup_tray
+const TRAY_ID: &str = "main";
+
+// Unix socket the server binds when the transport is "unix"
+#[cfg(unix)]
+const SOCKET_FILE: &str = "jarvis-overlay.sock";
+
+// How long to wait for a close frame to flush on shutdown
+const CLOSE_TIMEOUT: Duration = Duration::from_millis(500);
+
+// Transport under the WebSocket: plain TCP or TLS
+trait AgentStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AgentStream for T {}
+
+// Where accept_loop takes TCP connections from; a rebound listener replaces it
+trait AgentListener: From<TcpListener> + Send + 'static {
+    fn accept(&mut self) -> impl std::future::Future<Output = std::io::Result<(TcpStream, SocketAddr)>> + Send;
+    fn local_addr(&self) -> std::io::Result<SocketAddr>;
+}
+
+impl AgentListener for TcpListener {
+    fn accept(&mut self) -> impl std::future::Future<Output = std::io::Result<(TcpStream, SocketAddr)>> + Send {
+        TcpListener::accept(self)
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        TcpListener::local_addr(self)
+    }
+}
+
+type WsSink = futures_util::stream::SplitSink<tokio_tungstenite::WebSocketStream<Box<dyn AgentStream>>, Message>;
+type WsSource = futures_util::stream::SplitStream<tokio_tungstenite::WebSocketStream<Box<dyn AgentStream>>>;
+
+// A connected agent
+struct Connection {
+    writer: WsSink,
+    // Rolling average ping round-trip time
+    latency_ms: Option<u32>,
+    // Limits user_input sent to this agent
+    outbound: rate_limit::TokenBucket,
+    // Consecutive sends that timed out because the agent stopped reading
+    send_timeouts: u32,
+    // Signalled when the connection is dropped from outside its reader task
+    dropped: Arc<DropSignal>,
+    // Last data frame either way, or ping from the agent; for idle_disconnect_ms
+    last_frame: Arc<std::sync::Mutex<Instant>>,
+    meta: ConnectionMeta,
+}
+
+// Wakes a connection's reader task when something else drops it, with the reason
+#[derive(Default)]
+struct DropSignal {
+    notify: Notify,
+    reason: std::sync::Mutex<&'static str>,
+}
+
+impl DropSignal {
+    fn trigger(&self, reason: &'static str) {
+        *self.reason.lock().unwrap() = reason;
+        self.notify.notify_one();
+    }
+
+    fn reason(&self) -> &'static str {
+        *self.reason.lock().unwrap()
+    }
+}
+
+// What an agent told us about itself, for list_connections
+struct ConnectionMeta {
+    remote_addr: Option<String>,
+    connected_at: String,
+    agent_name: Option<String>,
+    agent_version: Option<String>,
+    protocol_version: u32,
+    // From the hello frame; unknown for version 1 agents
+    model: Option<String>,
+    capabilities: Vec<String>,
+    // Agent-chosen id it can resume under after a restart
+    session_id: Option<String>,
+    // Negotiated in the auth frame
+    format: msgpack::WireFormat,
+    // RFC 3339 time of the last frame; shared with the reader so it needn't lock the map
+    last_activity: Arc<std::sync::Mutex<Option<String>>>,
+}
+
+// Payload of list_connections
+#[derive(Debug, Clone, Serialize)]
+struct ConnectionInfo {
+    id: u64,
+    // Receives UI messages
+    active: bool,
+    remote_addr: Option<String>,
+    connected_at: String,
+    agent_name: Option<String>,
+    agent_version: Option<String>,
+    protocol_version: u32,
+    model: Option<String>,
+    capabilities: Vec<String>,
+    session_id: Option<String>,
+    format: msgpack::WireFormat,
+    last_activity: Option<String>,
+    latency_ms: Option<u32>,
+}
+
+// Why a message couldn't be delivered to an agent
+enum SendError {
+    NotConnected,
+    // `timeouts` counts consecutive timeouts on connection `id`
+    TimedOut { id: u64, timeouts: u32 },
+    Failed(WsError),
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::NotConnected => write!(f, "Not connected to agent"),
+            SendError::TimedOut { .. } => write!(f, "Send timed out"),
+            SendError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// Connected agents, keyed by connection id.
+// UI messages go to the most recently connected agent.
+#[derive(Default)]
+struct Connections {
+    entries: HashMap<u64, Connection>,
+    active: Option<u64>,
+    next_id: u64,
+}
+
+impl Connections {
+    fn insert(
+        &mut self,
+        writer: WsSink,
+        outbound: rate_limit::TokenBucket,
+        dropped: Arc<DropSignal>,
+        last_frame: Arc<std::sync::Mutex<Instant>>,
+        meta: ConnectionMeta,
+    ) -> u64 {
+        self.next_id += 1;
+        let connection = Connection {
+            writer,
+            latency_ms: None,
+            outbound,
+            send_timeouts: 0,
+            dropped,
+            last_frame,
+            meta,
+        };
+        self.entries.insert(self.next_id, connection);
+        self.active = Some(self.next_id);
+        self.next_id
+    }
+
+    fn remove(&mut self, id: u64) -> Option<WsSink> {
+        let connection = self.entries.remove(&id);
+        if self.active == Some(id) {
+            self.active = self.entries.keys().max().copied();
+        }
+        connection.map(|c| c.writer)
+    }
+
+    // Send without letting an agent that stopped reading stall the caller.
+    // After too many consecutive timeouts the connection is dropped.
+    async fn send(&mut self, id: u64, msg: Message, config: &config::ServerConfig) -> Result<(), SendError> {
+        let connection = self.entries.get_mut(&id).ok_or(SendError::NotConnected)?;
+        let timeout = Duration::from_millis(config.send_timeout_ms);
+        // Our own keepalive pings don't keep an idle connection open
+        let data = msg.is_text() || msg.is_binary();
+        let msg = match msg {
+            Message::Text(text) if connection.meta.format == msgpack::WireFormat::Msgpack => encode_msgpack(text),
+            msg => msg,
+        };
+        match tokio::time::timeout(timeout, connection.writer.send(msg)).await {
+            Ok(result) => {
+                connection.send_timeouts = 0;
+                if data && result.is_ok() {
+                    *connection.last_frame.lock().unwrap() = Instant::now();
+                }
+                result.map_err(SendError::Failed)
+            }
+            Err(_) => {
+                connection.send_timeouts += 1;
+                let timeouts = connection.send_timeouts;
+                if timeouts >= config.send_timeouts_before_drop {
+                    tracing::warn!(id, "Agent stopped reading, dropping connection");
+                    connection.dropped.trigger("send timed out");
+                    self.remove(id);
+                }
+                Err(SendError::TimedOut { id, timeouts })
+            }
+        }
+    }
+
+    async fn send_active(&mut self, msg: Message, config: &config::ServerConfig) -> Result<(), SendError> {
+        let id = self.active.ok_or(SendError::NotConnected)?;
+        self.send(id, msg, config).await
+    }
+
+    fn active_connection(&mut self) -> Option<&mut Connection> {
+        let id = self.active?;
+        self.entries.get_mut(&id)
+    }
+}
+
+// Re-encode an outgoing JSON frame for an agent that negotiated MessagePack.
+// Every text frame we send is JSON, so the fallback only guards against a bug.
+fn encode_msgpack(text: String) -> Message {
+    let value = serde_json::from_str::<serde_json::Value>(&text).ok();
+    match value.map(|value| msgpack::to_vec(&value)) {
+        Some(Ok(bytes)) => Message::Binary(bytes),
+        _ => {
+            tracing::warn!("Couldn't encode frame as MessagePack, sending JSON");
+            Message::Text(text)
+        }
+    }
+}
+
+// Keepalive ping bookkeeping for one connection
+#[derive(Default)]
+struct Heartbeat {
+    seq: u64,
+    pending: Option<(u64, Instant)>,
+    missed: u32,
+    samples: VecDeque<u32>,
+}
+
+impl Heartbeat {
+    // Start the next ping, counting the previous one as missed if unanswered
+    fn next_ping(&mut self) -> Vec<u8> {
+        if self.pending.is_some() {
+            self.missed += 1;
+        }
+        self.seq += 1;
+        self.pending = Some((self.seq, Instant::now()));
+        self.seq.to_be_bytes().to_vec()
+    }
+
+    // Match a pong to the pending ping, returning (rtt, rolling average) in ms
+    fn on_pong(&mut self, payload: &[u8]) -> Option<(u32, u32)> {
+        let seq = u64::from_be_bytes(payload.try_into().ok()?);
+        let (pending_seq, sent_at) = self.pending?;
+        if seq != pending_seq {
+            return None;
+        }
+        self.pending = None;
+        self.missed = 0;
+
+        let rtt = sent_at.elapsed().as_millis().min(u32::MAX as u128) as u32;
+        if self.samples.len() == LATENCY_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt);
+        let avg = self.samples.iter().sum::<u32>() / self.samples.len() as u32;
+        Some((rtt, avg))
+    }
+}
+
+// Shared state for WebSocket writers
+type WsConnections = Arc<Mutex<Connections>>;
+
+// Set to true to stop the accept loop and close every connection.
+// A restart installs a fresh channel so tasks of the old server still see true.
+struct ShutdownSignal(std::sync::Mutex<watch::Sender<bool>>);
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        Self(std::sync::Mutex::new(watch::channel(false).0))
+    }
+}
+
+impl ShutdownSignal {
+    fn subscribe(&self) -> watch::Receiver<bool> {
+        self.0.lock().unwrap().subscribe()
+    }
+
+    fn trigger(&self) {
+        self.0.lock().unwrap().send_replace(true);
+    }
+
+    fn reset(&self) {
+        *self.0.lock().unwrap() = watch::channel(false).0;
+    }
+}
+
+// Session an agent identified itself with, kept after it disconnects so a
+// restart within session_idle_secs resumes instead of starting fresh
+struct AgentSession {
+    // Current connection, or the last one while the agent is away
+    connection_id: u64,
+    // Set while the agent is away
+    disconnected_at: Option<Instant>,
+}
+
+// Overlay session agents attach to. Lives as long as the auth token.
+struct Session {
+    id: String,
+    // When the last agent connection ended
+    last_disconnect: Option<Instant>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self { id: auth::random_hex(8), last_disconnect: None }
+    }
+}
 
 #[derive(Default)]
 struct AppState {
-    ws_writer: WsWriter,
+    connections: WsConnections,
+    shutdown: ShutdownSignal,
+    // Accept loop of the running server and the port it bound
+    server_task: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    ws_port: std::sync::Mutex<Option<u16>>,
+    // Socket file to delete on shutdown when listening on a Unix socket
+    ws_socket_path: std::sync::Mutex<Option<std::path::PathBuf>>,
+    ws_token: std::sync::Mutex<Option<auth::WsToken>>,
+    // SHA-256 fingerprint of the wss:// certificate when TLS is enabled
+    ws_cert_fingerprint: std::sync::Mutex<Option<String>>,
+    session: std::sync::Mutex<Session>,
+    config: std::sync::Mutex<config::ServerConfig>,
+    glass: std::sync::Mutex<liquid_glass::GlassConfig>,
+    // Labels of windows with the glass effect applied
+    glass_windows: std::sync::Mutex<HashMap<String, liquid_glass::GlassBackend>>,
+    // The tray's "Glass Effect" item, kept checked while main has glass
+    glass_menu_item: std::sync::Mutex<Option<CheckMenuItem<tauri::Wry>>>,
+    // Puts the saved glass back when a preview_glass runs out
+    glass_preview: std::sync::Mutex<GlassPreviewTimer>,
+    // Theme pinned by the user; None follows the OS color scheme
+    theme_override: std::sync::Mutex<Option<Theme>>,
+    // RFC 3339 time of the last frame received from any agent
+    last_message_at: std::sync::Mutex<Option<String>>,
+    prefs: std::sync::Mutex<prefs::Preferences>,
+    // Keeps the click-through hit region interactive while running
+    hit_region_task: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Currently registered show/hide hotkey
+    toggle_hotkey: std::sync::Mutex<Option<Shortcut>>,
+    // Pending debounced save of the window layout
+    layout_save_task: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Running resize_to_content animation
+    resize_task: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Running attention pulse
+    pulse_task: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Most recent completed messages, oldest first; streaming deltas are not kept
+    history: std::sync::Mutex<VecDeque<AgentMessage>>,
+    // On-disk copy of the history while transcript logging is on
+    transcript_log: std::sync::Mutex<Option<transcript_log::TranscriptLog>>,
+    // Timers removing ephemeral messages, by message id
+    expiry_tasks: std::sync::Mutex<HashMap<String, tauri::async_runtime::JoinHandle<()>>>,
+    // Text of the last streamed answer, unless a full assistant message came after it
+    last_streamed: std::sync::Mutex<Option<String>>,
+    // Deltas fed by inject_agent_message, kept across calls so a done delta
+    // completes the text of the earlier ones
+    injected_deltas: std::sync::Mutex<Option<delta::DeltaBuffer>>,
+    idle: std::sync::Mutex<idle::IdleTracker>,
+    // Most recent parse failures, oldest first
+    recent_errors: std::sync::Mutex<VecDeque<ParseErrorRecord>>,
+    // Most recent protective measures taken, oldest first
+    recent_warnings: std::sync::Mutex<VecDeque<WarningRecord>>,
+    // Messages held back while do-not-disturb is on. Locked before prefs
+    // whenever both are needed, so the dnd flag can't change in between.
+    dnd_suppressed: std::sync::Mutex<HeldMessages>,
+    // User inputs held by set_input_paused, in send order; None while not paused
+    paused_inputs: std::sync::Mutex<Option<Vec<PausedInput>>>,
+    // User inputs sent but not yet acknowledged by the agent, by message id
+    in_flight: std::sync::Mutex<HashMap<String, InFlight>>,
+    stats: health::ServerStats,
+    // Connects, reconnects and downtime since the session started
+    stability: std::sync::Mutex<stability::StabilityTracker>,
+    // Window label agent events go to; None uses DEFAULT_EVENT_TARGET
+    event_target: std::sync::Mutex<Option<String>>,
+    // Whether the agent says it is working, from its "state" frames
+    activity: std::sync::Mutex<AgentActivity>,
+    // Resets a busy agent to idle after busy_timeout_secs without traffic
+    busy_watchdog: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Tool calls still running, for routing tool_call_update frames
+    tool_calls: std::sync::Mutex<tool_calls::ToolCalls>,
+    // Agent sessions by the session_id agents sent in their auth frame
+    agent_sessions: std::sync::Mutex<HashMap<String, AgentSession>>,
+    status_filter: std::sync::Mutex<status::StatusFilter<ConnectionEvent>>,
+    // Pending flush of flapping connect/disconnect events
+    status_flush_task: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Difference hash of the last screenshot shown, for dropping repeats
+    last_screenshot_hash: std::sync::Mutex<Option<u64>>,
+    // Slots for concurrent agent connections, sized by max_connections
+    connection_limit: Arc<conn_limit::ConnectionLimit>,
+    // Completed messages waiting for their turn during a paced burst
+    replay_pacer: std::sync::Mutex<pacing::Pacer<AgentMessage>>,
+    replay_task: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Microphone stream being forwarded to the agent, and its sender task
+    audio_stream: std::sync::Mutex<Option<audio::AudioStream>>,
+    audio_ready: Notify,
+    audio_task: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Timing of the current user turn and the last finished one
+    turns: std::sync::Mutex<turns::TurnTracker>,
+    // open_url calls waiting for confirm_open, by id
+    open_confirms: std::sync::Mutex<HashMap<String, tokio::sync::oneshot::Sender<bool>>>,
+    // Main window scale factor, for reporting what a change changed from
+    window_scale: std::sync::Mutex<Option<f64>>,
+    overlay_mode: std::sync::Mutex<overlay_mode::ModeState>,
+    // ping_agent calls waiting for their echo_reply, by nonce
+    echoes: std::sync::Mutex<HashMap<String, tokio::sync::oneshot::Sender<()>>>,
+    // Windows made by create_overlay_window, label to page
+    overlay_windows: std::sync::Mutex<HashMap<String, String>>,
+    // JSON of the app state snapshot last written, to skip unchanged saves
+    saved_snapshot: std::sync::Mutex<Option<String>>,
+}
+
+// A user input waiting for the agent's ack
+struct InFlight {
+    // Connection it was sent on, or the one an away agent will resume
+    connection_id: u64,
+    // The frame as sent, for resending to a resumed agent
+    frame: String,
+    // Dropping this without sending fails the delivery
+    acked: tokio::sync::oneshot::Sender<()>,
+}
+
+// User input queued while input is paused; sent once it resumes
+struct PausedInput {
+    id: String,
+    content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DeliveryStatus {
+    // Sent, ack not received yet
+    Pending,
+    Delivered,
+    // No ack within ack_timeout_ms, or the agent disconnected first
+    Failed,
+}
+
+// Payload of agent-input-acked events, and the result of send_to_agent
+#[derive(Debug, Clone, Serialize)]
+struct DeliveryEvent {
+    id: String,
+    status: DeliveryStatus,
+}
+
+// Payload of agent-status events; the UI switches on `kind`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ConnectionEvent {
+    // `port` is 0 and `socket_path` set when listening on a Unix socket
+    Listening {
+        port: u16,
+        url: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        socket_path: Option<String>,
+    },
+    // restart_ws_server is closing connections before rebinding
+    Restarting { port: u16 },
+    // The port is still taken; bind attempt `attempt` of `max_attempts`
+    // follows in `retry_in_ms`
+    Binding { port: u16, attempt: u32, max_attempts: u32, retry_in_ms: u64 },
+    Connected { id: u64 },
+    // An agent reconnected under a recent session_id; unacknowledged
+    // inputs were resent to it
+    Resumed { id: u64, session_id: String, resent: usize },
+    // `code` and `close_reason` come from the close frame, whichever side
+    // sent it; both are missing when the connection just dropped
+    Disconnected {
+        id: u64,
+        reason: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<u16>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code_label: Option<&'static str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        close_reason: Option<String>,
+    },
+    // Pings are going unanswered; the connection closes if it continues
+    Degraded { id: u64, missed_pings: u32 },
+    // Listening on a non-loopback interface: agents on other machines can
+    // connect with the token
+    RemoteExposed { url: String, tls: bool },
+    // Connects and disconnects were flapping; `transitions` were collapsed
+    // into this event and the state they settled on, which follows it
+    Unstable { transitions: u32 },
+    Error { message: String },
+}
+
+// Human label for a WebSocket close code (RFC 6455 section 7.4)
+fn close_code_label(code: u16) -> &'static str {
+    match code {
+        1000 => "normal closure",
+        1001 => "going away",
+        1002 => "protocol error",
+        1003 => "unsupported data",
+        1005 => "no status code",
+        1006 => "abnormal closure",
+        1007 => "invalid payload",
+        1008 => "policy violation",
+        1009 => "message too big",
+        1010 => "missing extension",
+        1011 => "internal error",
+        1012 => "service restart",
+        1013 => "try again later",
+        1015 => "TLS handshake failed",
+        3000..=3999 => "library-defined",
+        4000..=4999 => "application-defined",
+        _ => "unknown",
+    }
+}
+
+// Payload of agent-latency events
+#[derive(Debug, Clone, Serialize)]
+struct LatencyEvent {
+    id: u64,
+    rtt_ms: u32,
+    avg_ms: u32,
+}
+
+// Payload of agent-error events; `parse_error` is set for frames that failed to parse
+#[derive(Debug, Clone, Serialize)]
+struct AgentErrorEvent {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parse_error: Option<ParseErrorRecord>,
+}
+
+// A text frame the server couldn't parse, for debugging protocol drift
+#[derive(Debug, Clone, Serialize)]
+struct ParseErrorRecord {
+    connection_id: u64,
+    error: String,
+    line: usize,
+    column: usize,
+    // Truncated to ERROR_PAYLOAD_PREVIEW characters
+    payload: String,
+    timestamp: String,
+}
+
+// Protective measure the server took against a misbehaving or slow agent;
+// the UI switches on `kind`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum AgentWarning {
+    // `direction` is "inbound" (agent frames delayed) or "outbound" (user input refused)
+    RateLimited { direction: &'static str },
+    // The agent stopped reading and a send to it timed out
+    SlowConsumer { id: u64, timeouts: u32 },
+    // A frame over max_message_size was refused and the connection closed
+    OversizeFrame { id: u64, limit: usize },
+    // Unacknowledged user inputs are approaching max_in_flight
+    QueueNearFull { depth: usize, capacity: usize },
+    // A connection was refused because max_connections were already open
+    ConnectionLimit { max: usize },
+}
+
+// Payload of agent-warning events, and what get_recent_warnings returns
+#[derive(Debug, Clone, Serialize)]
+struct WarningRecord {
+    #[serde(flatten)]
+    warning: AgentWarning,
+    timestamp: String,
+}
+
+// Payload of input-paused-changed events; `flushed` counts inputs sent on resume
+#[derive(Debug, Clone, Serialize)]
+struct InputPausedEvent {
+    paused: bool,
+    flushed: usize,
+}
+
+// Payload of glass-preview-start; `replaced` is set when it cut another preview short
+#[derive(Debug, Clone, Serialize)]
+struct GlassPreviewEvent {
+    duration_ms: u64,
+    replaced: bool,
+}
+
+// Payload of dnd-changed events; `suppressed` counts messages held back,
+// or on turning it off those replayed or discarded
+#[derive(Debug, Clone, Serialize)]
+struct DndEvent {
+    enabled: bool,
+    suppressed: usize,
+}
+
+// Payload of agent-message-expire events
+#[derive(Debug, Clone, Serialize)]
+struct MessageExpireEvent {
+    message_id: String,
+}
+
+// Payload of token-rotated events; the token itself stays in the file
+#[derive(Debug, Clone, Serialize)]
+struct TokenRotatedEvent {
+    path: String,
+    overlap_secs: u64,
+}
+
+// Payload of permission-needed events, sent when a capture fails for want
+// of a permission the user can grant
+#[derive(Debug, Clone, Serialize)]
+struct PermissionNeededEvent {
+    permission: &'static str,
+    status: permissions::PermissionStatus,
+}
+
+// Payload of window-level-changed events
+#[derive(Debug, Clone, Serialize)]
+struct WindowLevelEvent {
+    // The level applied, which above_fullscreen can raise over the saved one
+    level: window_level::WindowLevel,
+    join_all_spaces: bool,
+    above_fullscreen: bool,
+}
+
+// Payload of glass-status events
+#[derive(Debug, Clone, Serialize)]
+struct GlassStatusEvent {
+    label: String,
+    status: liquid_glass::GlassStatus,
+    backend: liquid_glass::GlassBackend,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct StatusEvent {
+    version: u32,
+    #[serde(flatten)]
+    event: ConnectionEvent,
+}
+
+// Connection snapshot so a freshly loaded UI can render the right state
+#[derive(Debug, Clone, Serialize)]
+struct AgentStatus {
+    connected: bool,
+    input_paused: bool,
+    // The server is bound and accepting agents
+    listening: bool,
+    connection_count: usize,
+    port: u16,
+    last_message_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,218 +782,4132 @@ struct AgentMessage {
     #[serde(rename = "toolCalls")]
     tool_calls: Option<Vec<String>>,
     attachments: Option<Vec<String>>,
+    // A later message or a done delta with the same id replaces this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    // Ephemeral notice: removed from the transcript after this long and
+    // never kept in the history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttl_ms: Option<u64>,
+    // How the webview renders `content`; plain text unless the agent says otherwise
+    #[serde(default)]
+    content_type: content_type::ContentType,
+}
+
+// Pending message for queue display
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingMessage {
+    id: String,
+    content: String,
+    timestamp: String,
+}
+
+// Pending queue update message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingQueueMessage {
+    #[serde(rename = "type")]
+    msg_type: String,
+    messages: Vec<PendingMessage>,
+}
+
+// Whether the agent is working on something; payload of agent-activity events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AgentActivity {
+    #[default]
+    Idle,
+    Busy,
+}
+
+// Agent reporting its activity: {"type": "state", "value": "busy"}
+#[derive(Debug, Clone, Deserialize)]
+struct StateMessage {
+    #[serde(rename = "type")]
+    msg_type: String,
+    value: AgentActivity,
+}
+
+// Agent acknowledging a user input it has taken in
+#[derive(Debug, Clone, Deserialize)]
+struct AckMessage {
+    #[serde(rename = "type")]
+    msg_type: String,
+    id: String,
+}
+
+// Agent asking for the user's attention, e.g. when it needs input
+#[derive(Debug, Clone, Deserialize)]
+struct AttentionMessage {
+    #[serde(rename = "type")]
+    msg_type: String,
+    #[serde(default = "default_pulses")]
+    times: u32,
+}
+
+// Application-level round trip: the overlay sends "echo", the agent answers
+// "echo_reply" with the same nonce
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EchoMessage {
+    #[serde(rename = "type")]
+    msg_type: String,
+    nonce: String,
+}
+
+#[derive(Clone, Serialize)]
+struct EchoResultEvent {
+    nonce: String,
+    // None when no reply came in time
+    rtt_ms: Option<u32>,
+}
+
+// Agent asking to open a URL; always confirmed by the user
+#[derive(Debug, Clone, Deserialize)]
+struct OpenUrlMessage {
+    #[serde(rename = "type")]
+    msg_type: String,
+    url: String,
+}
+
+fn default_pulses() -> u32 {
+    2
+}
+
+// Binary payload from the agent (see binary.rs for the framing).
+// Small payloads are inlined as base64, large ones written to a temp file.
+#[derive(Debug, Clone, Serialize)]
+struct BinaryEvent {
+    kind: String,
+    metadata: serde_json::Value,
+    size: usize,
+    data: Option<String>,
+    path: Option<String>,
+}
+
+// First frame an agent must send: {"type": "auth", "token": "...", "protocol_version": 2}
+#[derive(Debug, Clone, Deserialize)]
+struct AuthMessage {
+    #[serde(rename = "type")]
+    msg_type: String,
+    token: String,
+    // Agents from before versioning don't send it; they speak version 1
+    #[serde(default = "legacy_protocol_version")]
+    protocol_version: u32,
+    // Optional self-description shown by list_connections
+    #[serde(default)]
+    agent_name: Option<String>,
+    #[serde(default)]
+    agent_version: Option<String>,
+    // Stable across agent restarts; a known recent one resumes the session
+    #[serde(default)]
+    session_id: Option<String>,
+    // "json" (the default) or "msgpack"; applies from the session frame on
+    #[serde(default)]
+    format: Option<String>,
+}
+
+fn legacy_protocol_version() -> u32 {
+    1
+}
+
+// Frame a version 2+ agent sends right after auth:
+// {"type": "hello", "agent_name": "...", "agent_version": "...",
+//  "protocol_version": 2, "model": "...", "capabilities": [...]}
+#[derive(Debug, Deserialize)]
+struct HelloMessage {
+    #[serde(rename = "type")]
+    msg_type: String,
+    agent_name: String,
+    agent_version: String,
+    protocol_version: u32,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    capabilities: Vec<String>,
+}
+
+// Payload of agent-hello events
+#[derive(Debug, Clone, Serialize)]
+struct AgentHello {
+    id: u64,
+    agent_name: String,
+    agent_version: String,
+    protocol_version: u32,
+    model: Option<String>,
+    capabilities: Vec<String>,
+}
+
+// Why an agent was refused at the auth frame
+enum AuthError {
+    Invalid,
+    UnsupportedVersion(u32),
+}
+
+// First frame sent to an authenticated agent so a reconnecting agent can
+// tell a fresh overlay session from a resumed one
+#[derive(Debug, Clone, Serialize)]
+struct SessionMessage {
+    #[serde(rename = "type")]
+    msg_type: &'static str,
+    session_id: String,
+    protocol_version: u32,
+    server_time: String,
+    is_resume: bool,
+    // What the agent asked for, or JSON when it asked for something unknown
+    format: msgpack::WireFormat,
+}
+
+// Message from UI to Agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UiMessage {
+    #[serde(rename = "type")]
+    msg_type: String,  // "user_input"
+    content: String,
+    // Echoed back by the agent in its ack
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    // What a "context" frame describes; one of CONTEXT_KINDS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+}
+
+// Ambient context the UI can pass along with send_context_to_agent
+const CONTEXT_KINDS: [&str; 3] = ["selection", "active_window", "clipboard"];
+
+// Tauri command to send message to agent. Returns once the frame is sent
+// (status "pending"), or with `await_ack` once the agent acknowledges it
+// or ack_timeout_ms passes. Either way agent-input-acked reports the outcome.
+#[tauri::command]
+async fn send_to_agent(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    content: String,
+    await_ack: Option<bool>,
+) -> Result<DeliveryEvent, CommandError> {
+    note_activity(&app);
+    let config = state.config.lock().unwrap().clone();
+    let content = sanitize_input(&content, config.max_input_chars)?;
+    let id = auth::random_hex(16);
+    if let Some(paused) = state.paused_inputs.lock().unwrap().as_mut() {
+        tracing::debug!(%id, "Input is paused, queueing user input");
+        paused.push(PausedInput { id: id.clone(), content });
+        return Ok(DeliveryEvent { id, status: DeliveryStatus::Pending });
+    }
+    deliver_input(&app, &state, &config, id, content, await_ack.unwrap_or(false), true).await
+}
+
+// Send a user input under `id` and track its ack. `throttle` applies the
+// outbound rate limit; inputs flushed after a pause were already admitted.
+async fn deliver_input(
+    app: &AppHandle,
+    state: &AppState,
+    config: &config::ServerConfig,
+    id: String,
+    content: String,
+    await_ack: bool,
+    throttle: bool,
+) -> Result<DeliveryEvent, CommandError> {
+    let mut connections = state.connections.lock().await;
+
+    // While a resumable agent is away, hold the input for it instead of failing
+    let active = connections.active;
+    let connection_id = match active {
+        Some(id) => id,
+        None => awaiting_resume(state, config).ok_or(CommandError::NotConnected)?,
+    };
+    if let Some(connection) = connections.active_connection().filter(|_| throttle) {
+        if let Err(throttled) = connection.outbound.try_take() {
+            if throttled.first {
+                emit_agent_warning(app, AgentWarning::RateLimited { direction: "outbound" });
+            }
+            return Err(CommandError::RateLimited);
+        }
+    }
+    let depth = state.in_flight.lock().unwrap().len();
+    if depth >= config.max_in_flight {
+        emit_agent_warning(app, AgentWarning::QueueNearFull { depth, capacity: config.max_in_flight });
+        return Err(CommandError::RateLimited);
+    }
+    if depth + 1 == (config.max_in_flight as f64 * QUEUE_WARN_RATIO).ceil() as usize {
+        emit_agent_warning(app, AgentWarning::QueueNearFull { depth: depth + 1, capacity: config.max_in_flight });
+    }
+
+    let msg = UiMessage {
+        msg_type: "user_input".to_string(),
+        content,
+        id: Some(id.clone()),
+        kind: None,
+    };
+    let json = serde_json::to_string(&msg)?;
+
+    let (acked, ack) = tokio::sync::oneshot::channel();
+    state.in_flight.lock().unwrap().insert(id.clone(), InFlight { connection_id, frame: json.clone(), acked });
+    // Reported before sending so it can't arrive after a fast ack
+    emit_agent_event(app, "agent-input-acked", DeliveryEvent { id: id.clone(), status: DeliveryStatus::Pending });
+    if active.is_some() {
+        let bytes = json.len();
+        if let Err(e) = check_send(app, connections.send_active(Message::Text(json), config).await) {
+            state.in_flight.lock().unwrap().remove(&id);
+            emit_agent_event(app, "agent-input-acked", DeliveryEvent { id, status: DeliveryStatus::Failed });
+            return Err(e.into());
+        }
+        state.stats.record_out(bytes);
+    } else {
+        tracing::debug!(%id, "Agent is away, holding input until it resumes");
+    }
+    drop(connections);
+    track_turn(app, |turns, now| turns.start(id.clone(), now));
+
+    let wait = wait_for_ack(app.clone(), id.clone(), ack, Duration::from_millis(config.ack_timeout_ms));
+    if await_ack {
+        return Ok(DeliveryEvent { id, status: wait.await });
+    }
+    tauri::async_runtime::spawn(wait);
+    Ok(DeliveryEvent { id, status: DeliveryStatus::Pending })
 }
 
-// Pending message for queue display
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct PendingMessage {
-    id: String,
-    content: String,
-    timestamp: String,
-}
+// Tauri command to hold user input back from the agent while the connection
+// stays up and agent output keeps arriving. Inputs sent while paused are
+// queued and go out in order on resume. Returns how many were flushed.
+#[tauri::command]
+async fn set_input_paused(app: AppHandle, state: State<'_, AppState>, paused: bool) -> Result<usize, CommandError> {
+    let queued = {
+        let mut inputs = state.paused_inputs.lock().unwrap();
+        if paused {
+            inputs.get_or_insert_with(Vec::new);
+            Vec::new()
+        } else {
+            inputs.take().unwrap_or_default()
+        }
+    };
+    let flushed = queued.len();
+    tracing::info!(paused, flushed, "Input pause changed");
+    let config = state.config.lock().unwrap().clone();
+    for input in queued {
+        let id = input.id.clone();
+        if let Err(e) = deliver_input(&app, &state, &config, input.id, input.content, false, false).await {
+            tracing::warn!(%id, error = %e, "Failed to send user input queued while paused");
+            // May repeat a failure deliver_input already reported; the id is what matters
+            emit_agent_event(&app, "agent-input-acked", DeliveryEvent { id, status: DeliveryStatus::Failed });
+        }
+    }
+    let _ = app.emit("input-paused-changed", InputPausedEvent { paused, flushed });
+    Ok(flushed)
+}
+
+// Last connection of the most recently disconnected agent that can still
+// resume, dropping sessions idle past session_idle_secs
+fn awaiting_resume(state: &AppState, config: &config::ServerConfig) -> Option<u64> {
+    let idle = Duration::from_secs(config.session_idle_secs);
+    let mut sessions = state.agent_sessions.lock().unwrap();
+    sessions.retain(|_, session| session.disconnected_at.is_none_or(|at| at.elapsed() < idle));
+    sessions
+        .values()
+        .filter_map(|session| Some((session.disconnected_at?, session.connection_id)))
+        .max()
+        .map(|(_, connection_id)| connection_id)
+}
+
+// Attach connection `id` to the agent's session. If the agent is resuming
+// one that is still waiting, moves the inputs it never acknowledged to the
+// new connection and returns their frames for resending.
+fn resume_agent_session(app: &impl EventSink, session_id: &str, id: u64, config: &config::ServerConfig) -> Option<Vec<String>> {
+    let state = app.app_state();
+    awaiting_resume(state, config);
+    let mut sessions = state.agent_sessions.lock().unwrap();
+    let previous = match sessions.get_mut(session_id) {
+        // An agent still connected under this id is a different process; start fresh
+        Some(session) if config.session_idle_secs > 0 && session.disconnected_at.is_some() => {
+            Some(std::mem::replace(&mut session.connection_id, id))
+        }
+        _ => None,
+    };
+    sessions.insert(session_id.to_string(), AgentSession { connection_id: id, disconnected_at: None });
+    let previous = previous?;
+
+    let mut in_flight = state.in_flight.lock().unwrap();
+    Some(
+        in_flight
+            .values_mut()
+            .filter(|in_flight| in_flight.connection_id == previous)
+            .map(|in_flight| {
+                in_flight.connection_id = id;
+                in_flight.frame.clone()
+            })
+            .collect(),
+    )
+}
+
+// Reject input over `max_chars` and strip control characters other than
+// newlines and tabs, which agents and their log formats don't expect
+fn sanitize_input(content: &str, max_chars: usize) -> Result<String, CommandError> {
+    let length = content.chars().count();
+    if length > max_chars {
+        return Err(CommandError::InvalidArg(format!(
+            "Input is {} characters, the limit is {}",
+            length, max_chars
+        )));
+    }
+    Ok(content
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+        .collect())
+}
+
+// Wait for the ack of one user input; reports failure itself on timeout or disconnect
+async fn wait_for_ack(
+    app: AppHandle,
+    id: String,
+    ack: tokio::sync::oneshot::Receiver<()>,
+    timeout: Duration,
+) -> DeliveryStatus {
+    if let Ok(Ok(())) = tokio::time::timeout(timeout, ack).await {
+        return DeliveryStatus::Delivered;
+    }
+    app.state::<AppState>().in_flight.lock().unwrap().remove(&id);
+    tracing::warn!(%id, "User input was not acknowledged");
+    emit_agent_event(&app, "agent-input-acked", DeliveryEvent { id, status: DeliveryStatus::Failed });
+    DeliveryStatus::Failed
+}
+
+// Resolve an in-flight user input acknowledged by the agent
+fn handle_ack(app: &impl EventSink, id: String) {
+    let Some(in_flight) = app.app_state().in_flight.lock().unwrap().remove(&id) else {
+        tracing::debug!(%id, "Ack for unknown or expired message");
+        return;
+    };
+    let _ = in_flight.acked.send(());
+    emit_agent_event(app, "agent-input-acked", DeliveryEvent { id, status: DeliveryStatus::Delivered });
+}
+
+// Tauri command to pass environmental context (selected text, the active
+// window, the clipboard) to the agent as a "context" frame. Agents treat it
+// as background information, not as a turn to reply to.
+#[tauri::command]
+async fn send_context_to_agent(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    kind: String,
+    content: String,
+) -> Result<bool, CommandError> {
+    if !CONTEXT_KINDS.contains(&kind.as_str()) {
+        return Err(CommandError::InvalidArg(format!(
+            "Unknown context kind: {} (expected one of {})",
+            kind,
+            CONTEXT_KINDS.join(", ")
+        )));
+    }
+    let config = state.config.lock().unwrap().clone();
+    let msg = UiMessage {
+        msg_type: "context".to_string(),
+        content: sanitize_input(&content, config.max_input_chars)?,
+        id: None,
+        kind: Some(kind),
+    };
+    let json = serde_json::to_string(&msg)?;
+
+    let mut connections = state.connections.lock().await;
+    let bytes = json.len();
+    check_send(&app, connections.send_active(Message::Text(json), &config).await)?;
+    state.stats.record_out(bytes);
+    Ok(true)
+}
+
+// Tauri command to close agent connections with no frames either way for
+// `timeout_ms` (0 disables), until restart. Applies to open connections too
+#[tauri::command]
+fn set_idle_disconnect(state: State<'_, AppState>, timeout_ms: u64) {
+    state.config.lock().unwrap().idle_disconnect_ms = timeout_ms;
+    tracing::info!(timeout_ms, "Idle disconnect changed");
+}
+
+// Tauri command to change how many agent connections are served at once
+// (0 for unlimited), until restart. Open connections are never closed
+#[tauri::command]
+fn set_max_connections(state: State<'_, AppState>, max: usize) -> Result<(), CommandError> {
+    state.config.lock().unwrap().max_connections = max;
+    state.connection_limit.set_max(max);
+    tracing::info!(max, "Connection limit changed");
+    Ok(())
+}
+
+// Tauri command to test the link end to end: sends an "echo" frame that the
+// agent has to parse and answer, unlike a WebSocket ping. Returns the round
+// trip in ms, or None on timeout, and emits it as "echo-result".
+#[tauri::command]
+async fn ping_agent(app: AppHandle, state: State<'_, AppState>, timeout_ms: u64) -> Result<Option<u32>, CommandError> {
+    if !(1..=MAX_ECHO_TIMEOUT_MS).contains(&timeout_ms) {
+        return Err(CommandError::InvalidArg(format!(
+            "Timeout must be 1-{} ms, got {}",
+            MAX_ECHO_TIMEOUT_MS, timeout_ms
+        )));
+    }
+    let config = state.config.lock().unwrap().clone();
+    let nonce = auth::random_hex(8);
+    let json = serde_json::to_string(&EchoMessage { msg_type: "echo".to_string(), nonce: nonce.clone() })?;
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    state.echoes.lock().unwrap().insert(nonce.clone(), tx);
+    let bytes = json.len();
+    let sent = {
+        let mut connections = state.connections.lock().await;
+        check_send(&app, connections.send_active(Message::Text(json), &config).await)
+    };
+    if let Err(e) = sent {
+        state.echoes.lock().unwrap().remove(&nonce);
+        return Err(e.into());
+    }
+    state.stats.record_out(bytes);
+    // Timed from after the send so a slow lock doesn't count against the agent
+    let started = Instant::now();
+
+    let rtt_ms = match tokio::time::timeout(Duration::from_millis(timeout_ms), rx).await {
+        Ok(Ok(())) => Some(started.elapsed().as_millis().min(u32::MAX as u128) as u32),
+        _ => {
+            state.echoes.lock().unwrap().remove(&nonce);
+            None
+        }
+    };
+    tracing::info!(?rtt_ms, "Agent echo");
+    let _ = app.emit("echo-result", EchoResultEvent { nonce, rtt_ms });
+    Ok(rtt_ms)
+}
+
+// Tauri command to forward an arbitrary JSON frame to the agent verbatim,
+// for trying out new message types. Needs dev_commands in the config
+#[tauri::command]
+async fn send_raw_to_agent(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    json: String,
+    require_type: Option<bool>,
+) -> Result<bool, CommandError> {
+    let config = state.config.lock().unwrap().clone();
+    if !config.dev_commands {
+        return Err(CommandError::InvalidArg(
+            "send_raw_to_agent is disabled; set dev_commands in the server config".to_string(),
+        ));
+    }
+    let value: serde_json::Value =
+        serde_json::from_str(&json).map_err(|e| CommandError::InvalidArg(format!("Invalid JSON: {}", e)))?;
+    if require_type.unwrap_or(true) && !value.get("type").is_some_and(|t| t.is_string()) {
+        return Err(CommandError::InvalidArg("Frame has no string \"type\" field".to_string()));
+    }
+
+    let mut connections = state.connections.lock().await;
+    let bytes = json.len();
+    check_send(&app, connections.send_active(Message::Text(json), &config).await)?;
+    state.stats.record_out(bytes);
+    Ok(true)
+}
+
+// Streaming fragment for inject_agent_message
+#[derive(Debug, Clone, Deserialize)]
+struct InjectedDelta {
+    #[serde(default)]
+    delta: String,
+    #[serde(default)]
+    done: bool,
+}
+
+// Tauri command to feed the UI a made-up agent message, for working on the
+// transcript without an agent. It goes through the same handling as a real
+// frame: a delta of message `id` when `delta` is given, otherwise a complete
+// message of `role` and `content`. Needs dev_commands in the config
+#[tauri::command]
+fn inject_agent_message(
+    app: AppHandle,
+    role: Option<String>,
+    content: Option<String>,
+    tool_calls: Option<Vec<String>>,
+    id: Option<String>,
+    delta: Option<InjectedDelta>,
+) -> Result<(), CommandError> {
+    inject_frame(&app, role, content, tool_calls, id, delta)
+}
+
+fn inject_frame(
+    app: &impl EventSink,
+    role: Option<String>,
+    content: Option<String>,
+    tool_calls: Option<Vec<String>>,
+    id: Option<String>,
+    delta: Option<InjectedDelta>,
+) -> Result<(), CommandError> {
+    let state = app.app_state();
+    let config = state.config.lock().unwrap().clone();
+    if !config.dev_commands {
+        return Err(CommandError::InvalidArg(
+            "inject_agent_message is disabled; set dev_commands in the server config".to_string(),
+        ));
+    }
+
+    let frame = if let Some(InjectedDelta { delta, done }) = delta {
+        if role.is_some() || content.is_some() || tool_calls.is_some() {
+            return Err(CommandError::InvalidArg(
+                "A delta takes no role, content or tool_calls; pass its text as delta".to_string(),
+            ));
+        }
+        let message_id = id.ok_or_else(|| CommandError::InvalidArg("A delta needs an id".to_string()))?;
+        serde_json::json!({ "type": "delta", "message_id": message_id, "delta": delta, "done": done }).to_string()
+    } else {
+        let (Some(role), Some(content)) = (role, content) else {
+            return Err(CommandError::InvalidArg("A message needs a role and content".to_string()));
+        };
+        serde_json::to_string(&AgentMessage {
+            role,
+            content,
+            timestamp: chrono::Local::now().format("%H:%M:%S").to_string(),
+            tool_calls,
+            attachments: None,
+            id,
+            ttl_ms: None,
+            content_type: content_type::ContentType::Text,
+        })?
+    };
+
+    let mut deltas = state.injected_deltas.lock().unwrap();
+    let deltas = deltas.get_or_insert_with(|| delta::DeltaBuffer::new(Duration::from_millis(config.delta_flush_ms)));
+    // Connection 0 is never assigned, so this can't be mistaken for an agent
+    handle_text_frame(app, 0, &frame, deltas, &config);
+    // Nothing flushes this buffer on a timer, so pending text goes out now
+    for event in deltas.take_all() {
+        emit_delta(app, event);
+    }
+    Ok(())
+}
+
+// Tauri command to start forwarding microphone audio to the agent. Any
+// stream already running is stopped first. Returns the new stream id
+#[tauri::command]
+async fn start_audio_stream(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    sample_rate: u32,
+    channels: u16,
+    codec: audio::Codec,
+) -> Result<String, CommandError> {
+    if sample_rate == 0 || channels == 0 {
+        return Err(CommandError::InvalidArg(format!("Invalid audio format: {} Hz, {} channels", sample_rate, channels)));
+    }
+    stop_audio_stream(app.clone(), state.clone()).await?;
+
+    let format = audio::AudioFormat { sample_rate, channels, codec };
+    let stream_id = auth::random_hex(8);
+    *state.audio_stream.lock().unwrap() = Some(audio::AudioStream::new(stream_id.clone(), format));
+    *state.audio_task.lock().unwrap() = Some(tauri::async_runtime::spawn(forward_audio(app.clone())));
+    tracing::info!(%stream_id, sample_rate, channels, ?codec, "Audio stream started");
+    let _ = app.emit("audio-stream", audio::AudioStreamEvent::Started { stream_id: stream_id.clone(), format });
+    Ok(stream_id)
+}
+
+// Tauri command to queue one chunk of the running audio stream
+#[tauri::command]
+fn push_audio_chunk(app: AppHandle, state: State<'_, AppState>, bytes: Vec<u8>) -> Result<(), CommandError> {
+    let mut stream = state.audio_stream.lock().unwrap();
+    let stream = stream
+        .as_mut()
+        .ok_or_else(|| CommandError::InvalidArg("No audio stream running".to_string()))?;
+    if stream.push(bytes) && stream.dropped.is_power_of_two() {
+        // Reported at 1, 2, 4, 8... drops so a long stall doesn't flood the UI
+        tracing::warn!(dropped = stream.dropped, "Agent is behind, dropping audio");
+        let event = audio::AudioStreamEvent::Dropped { stream_id: stream.id.clone(), dropped: stream.dropped };
+        let _ = app.emit("audio-stream", event);
+    }
+    state.audio_ready.notify_one();
+    Ok(())
+}
+
+// Tauri command to end the running audio stream; the agent gets an empty
+// chunk with "end": true. Does nothing when no stream is running
+#[tauri::command]
+async fn stop_audio_stream(app: AppHandle, state: State<'_, AppState>) -> Result<(), CommandError> {
+    if let Some(task) = state.audio_task.lock().unwrap().take() {
+        task.abort();
+    }
+    let Some(stream) = state.audio_stream.lock().unwrap().take() else {
+        return Ok(());
+    };
+    let frame = binary::encode(binary::BinaryKind::Audio, &stream.end_metadata(), &[]);
+    let config = state.config.lock().unwrap().clone();
+    if let Err(e) = state.connections.lock().await.send_active(Message::Binary(frame), &config).await {
+        tracing::debug!(error = %CommandError::from(e), "Could not send end of audio stream");
+    }
+    tracing::info!(stream_id = %stream.id, dropped = stream.dropped, "Audio stream stopped");
+    let _ = app.emit("audio-stream", audio::AudioStreamEvent::Stopped { stream_id: stream.id, dropped: stream.dropped });
+    Ok(())
+}
+
+// Send queued audio chunks to the agent as they arrive. While a send is
+// slow, push_audio_chunk keeps dropping the oldest queued chunks
+async fn forward_audio(app: AppHandle) {
+    let state = app.state::<AppState>();
+    loop {
+        let next = state.audio_stream.lock().unwrap().as_mut().and_then(|stream| stream.pop());
+        let Some((metadata, chunk)) = next else {
+            state.audio_ready.notified().await;
+            continue;
+        };
+        let frame = binary::encode(binary::BinaryKind::Audio, &metadata, &chunk);
+        let bytes = frame.len();
+        let config = state.config.lock().unwrap().clone();
+        let result = state.connections.lock().await.send_active(Message::Binary(frame), &config).await;
+        match check_send(&app, result) {
+            Ok(()) => state.stats.record_out(bytes),
+            Err(e) => tracing::debug!(error = %CommandError::from(e), "Dropped audio chunk"),
+        }
+    }
+}
+
+// Tauri command to send an image or audio payload to agent as a binary frame
+#[tauri::command]
+async fn send_binary_to_agent(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    kind: String,
+    bytes: Vec<u8>,
+    metadata: Option<serde_json::Value>,
+) -> Result<bool, CommandError> {
+    let kind = binary::BinaryKind::parse(&kind).ok_or_else(|| CommandError::InvalidArg(format!("Unknown binary kind: {}", kind)))?;
+    let metadata = metadata.unwrap_or_else(|| serde_json::json!({}));
+    let frame = binary::encode(kind, &metadata, &bytes);
+    note_activity(&app);
+
+    let config = state.config.lock().unwrap().clone();
+    let mut connections = state.connections.lock().await;
+    let bytes = frame.len();
+    check_send(&app, connections.send_active(Message::Binary(frame), &config).await)?;
+    state.stats.record_out(bytes);
+    Ok(true)
+}
+
+// Tauri command to stop the agent
+#[tauri::command]
+async fn stop_agent(app: AppHandle, state: State<'_, AppState>) -> Result<bool, CommandError> {
+    note_activity(&app);
+    let msg = UiMessage {
+        msg_type: "stop_agent".to_string(),
+        content: String::new(),
+        id: None,
+        kind: None,
+    };
+    let json = serde_json::to_string(&msg)?;
+
+    let config = state.config.lock().unwrap().clone();
+    let mut connections = state.connections.lock().await;
+    let bytes = json.len();
+    check_send(&app, connections.send_active(Message::Text(json), &config).await)?;
+    state.stats.record_out(bytes);
+    track_turn(&app, |turns, now| turns.finish(turns::TurnOutcome::Cancelled, now));
+    Ok(true)
+}
+
+// Tauri command to get the timing of the last finished user turn
+#[tauri::command]
+fn get_last_turn_metrics(state: State<'_, AppState>) -> Option<turns::TurnMetrics> {
+    state.turns.lock().unwrap().last().cloned()
+}
+
+// Update the turn tracker and emit "turn-metrics" for a turn that ended
+fn track_turn(app: &impl EventSink, update: impl FnOnce(&mut turns::TurnTracker, Instant) -> Option<turns::TurnMetrics>) {
+    let finished = update(&mut app.app_state().turns.lock().unwrap(), Instant::now());
+    if let Some(metrics) = finished {
+        tracing::debug!(?metrics, "Turn finished");
+        emit_agent_event(app, "turn-metrics", metrics);
+    }
+}
+
+// Tauri command to update pending messages queue
+#[tauri::command]
+async fn update_pending_queue(app: AppHandle, messages: Vec<PendingMessage>) -> Result<(), CommandError> {
+    emit_agent_event(&app, "pending-messages", messages);
+    Ok(())
+}
+
+// Tauri command to query the current connection state
+#[tauri::command]
+async fn get_agent_status(state: State<'_, AppState>) -> Result<AgentStatus, CommandError> {
+    let connections = state.connections.lock().await;
+    Ok(AgentStatus {
+        connected: connections.active.is_some(),
+        input_paused: state.paused_inputs.lock().unwrap().is_some(),
+        listening: server_listening(&state),
+        connection_count: connections.entries.len(),
+        port: state.ws_port.lock().unwrap().unwrap_or(WS_PORT),
+        last_message_at: state.last_message_at.lock().unwrap().clone(),
+    })
+}
+
+// Tauri command to close every connection and rebind the server, on `port`
+// or the current one. Returns the port actually bound.
+#[tauri::command]
+async fn restart_ws_server(app: AppHandle, state: State<'_, AppState>, port: Option<u16>) -> Result<u16, CommandError> {
+    let port = port.unwrap_or_else(|| state.ws_port.lock().unwrap().unwrap_or(WS_PORT));
+    emit_status(&app, ConnectionEvent::Restarting { port });
+
+    shutdown_ws_server(&state).await;
+    let task = state.server_task.lock().unwrap().take();
+    if let Some(task) = task {
+        let _ = task.await;
+    }
+
+    state.shutdown.reset();
+    start_ws_server(app.clone(), port).await.map_err(CommandError::Io)
+}
+
+// Tauri command to choose whether the server starts at launch. Off, agents
+// can't connect until start_server is called, e.g. on first interaction.
+#[tauri::command]
+fn set_server_autostart(app: AppHandle, state: State<'_, AppState>, enabled: bool) {
+    state.prefs.lock().unwrap().server_autostart = enabled;
+    save_prefs(&app);
+}
+
+// Tauri command to start the server if it isn't listening. Returns the port,
+// or 0 on a Unix socket.
+#[tauri::command]
+async fn start_server(app: AppHandle, state: State<'_, AppState>) -> Result<u16, CommandError> {
+    if server_listening(&state) {
+        return Ok(state.ws_port.lock().unwrap().unwrap_or(0));
+    }
+    state.server_task.lock().unwrap().take();
+    state.shutdown.reset();
+    let port = state.ws_port.lock().unwrap().unwrap_or(WS_PORT);
+    start_ws_server(app.clone(), port).await.map_err(CommandError::Io)
+}
+
+// Tauri command to close every connection and stop listening
+#[tauri::command]
+async fn stop_server(app: AppHandle, state: State<'_, AppState>) -> Result<(), CommandError> {
+    shutdown_ws_server(&state).await;
+    let task = state.server_task.lock().unwrap().take();
+    if let Some(task) = task {
+        let _ = task.await;
+    }
+    tracing::info!("WebSocket server stopped");
+    let _ = app.emit("server-stopped", ServerStoppedEvent { reason: "stopped" });
+    Ok(())
+}
+
+// The accept loop runs from a successful bind until shutdown
+fn server_listening(state: &AppState) -> bool {
+    state.server_task.lock().unwrap().as_ref().is_some_and(|task| !task.inner().is_finished())
+}
+
+// The TCP port being listened on. `ws_port` outlives stop_server so
+// start_server can rebind it, so it alone doesn't mean the server is up.
+fn listening_port(state: &AppState) -> Option<u16> {
+    server_listening(state).then(|| *state.ws_port.lock().unwrap()).flatten()
+}
+
+// Payload of server-stopped events
+#[derive(Clone, Serialize)]
+struct ServerStoppedEvent {
+    // "autostart_off" at launch, "stopped" after stop_server
+    reason: &'static str,
+}
+
+// Tauri command to change log verbosity at runtime, e.g. "debug" or "jarvis_overlay_lib=trace"
+#[tauri::command]
+fn set_log_level(level: String) -> Result<(), CommandError> {
+    logging::set_level(&level).map_err(CommandError::InvalidArg)?;
+    tracing::info!(%level, "Log level changed");
+    Ok(())
+}
+
+// Tauri command to get whether the agent is "busy" or "idle"
+#[tauri::command]
+fn get_agent_activity(state: State<'_, AppState>) -> String {
+    let activity = *state.activity.lock().unwrap();
+    match activity {
+        AgentActivity::Idle => "idle".to_string(),
+        AgentActivity::Busy => "busy".to_string(),
+    }
+}
+
+// Record the agent's activity and emit "agent-activity" when it changes.
+// While busy, a watchdog falls back to idle if the agent goes quiet.
+fn set_agent_activity(app: &impl EventSink, activity: AgentActivity) {
+    let state = app.app_state();
+    let previous = std::mem::replace(&mut *state.activity.lock().unwrap(), activity);
+
+    let mut watchdog = state.busy_watchdog.lock().unwrap();
+    if let Some(task) = watchdog.take() {
+        task.abort();
+    }
+    let timeout = state.config.lock().unwrap().busy_timeout_secs;
+    if activity == AgentActivity::Busy && timeout > 0 {
+        *watchdog = Some(tauri::async_runtime::spawn(watch_busy(app.clone(), Duration::from_secs(timeout))));
+    }
+    drop(watchdog);
+
+    if previous != activity {
+        emit_agent_event(app, "agent-activity", activity);
+    }
+}
+
+// Reset to idle once no frame has arrived for `timeout`
+async fn watch_busy(app: impl EventSink, timeout: Duration) {
+    let mut wait = timeout;
+    loop {
+        tokio::time::sleep(wait).await;
+        let last_message_at = app.app_state().last_message_at.lock().unwrap().clone();
+        let quiet_for = last_message_at
+            .and_then(|at| chrono::DateTime::parse_from_rfc3339(&at).ok())
+            .and_then(|at| (chrono::Utc::now() - at.with_timezone(&chrono::Utc)).to_std().ok())
+            .unwrap_or(timeout);
+        if quiet_for >= timeout {
+            break;
+        }
+        wait = timeout - quiet_for;
+    }
+    tracing::warn!(timeout_secs = timeout.as_secs(), "Busy agent went quiet, assuming idle");
+    let state = app.app_state();
+    // Taken first so set_agent_activity doesn't abort this task mid-emit
+    drop(state.busy_watchdog.lock().unwrap().take());
+    set_agent_activity(&app, AgentActivity::Idle);
+}
+
+// Result of run_self_test; `checks` lists each check in the order it ran
+#[derive(Debug, Clone, Serialize)]
+struct SelfTestReport {
+    ws_bound: bool,
+    ws_port: Option<u16>,
+    glass_backend: Option<liquid_glass::GlassBackend>,
+    glass_ok: bool,
+    os: &'static str,
+    os_version: String,
+    monitor_count: usize,
+    // None where the OS has no screen recording permission
+    screen_recording: Option<bool>,
+    checks: Vec<self_test::SelfTestCheck>,
+}
+
+// Tauri command to check the server, glass and platform, emitting
+// "self-test-check" as each check finishes
+#[tauri::command]
+fn run_self_test(app: AppHandle, state: State<'_, AppState>) -> SelfTestReport {
+    let mut checks = Vec::new();
+    let mut check = |name, ok, detail: String| {
+        let result = self_test::SelfTestCheck { name, ok, detail };
+        let _ = app.emit("self-test-check", result.clone());
+        checks.push(result);
+    };
+
+    let ws_bound = server_listening(&state);
+    let ws_port = listening_port(&state);
+    let socket_path = state.ws_socket_path.lock().unwrap().clone().filter(|_| ws_bound);
+    let detail = match (ws_port, &socket_path) {
+        (_, Some(path)) => format!("Listening on {}", path.display()),
+        (Some(port), None) => format!("Listening on port {}", port),
+        (None, None) => "Server is not listening".to_string(),
+    };
+    check("server", ws_bound, detail);
+
+    let glass_backend = state.glass_windows.lock().unwrap().get("main").copied();
+    let glass_ok = glass_backend.is_some_and(|backend| backend != liquid_glass::GlassBackend::Fallback);
+    let detail = match glass_backend {
+        Some(backend) => format!("Backend: {}", backend.as_str()),
+        None => "Glass is not applied to the main window".to_string(),
+    };
+    check("glass", glass_ok, detail);
+
+    let os = std::env::consts::OS;
+    let os_version = self_test::os_version();
+    check("os", true, format!("{} {}", os, os_version));
+
+    let monitor_count = app.available_monitors().map(|monitors| monitors.len()).unwrap_or(0);
+    check("monitors", monitor_count > 0, format!("{} connected", monitor_count));
+
+    let screen_recording = self_test::screen_recording_granted();
+    if let Some(granted) = screen_recording {
+        let detail = if granted { "Granted" } else { "Not granted; only needed by agents that capture the screen" };
+        check("screen_recording", granted, detail.to_string());
+    }
+
+    SelfTestReport {
+        ws_bound,
+        ws_port,
+        glass_backend,
+        glass_ok,
+        os,
+        os_version,
+        monitor_count,
+        screen_recording,
+        checks,
+    }
+}
+
+// Tauri command to list connected agents, oldest first
+#[tauri::command]
+async fn list_connections(state: State<'_, AppState>) -> Result<Vec<ConnectionInfo>, CommandError> {
+    let connections = state.connections.lock().await;
+    let mut list: Vec<ConnectionInfo> = connections
+        .entries
+        .iter()
+        .map(|(&id, connection)| {
+            let meta = &connection.meta;
+            ConnectionInfo {
+                id,
+                active: connections.active == Some(id),
+                remote_addr: meta.remote_addr.clone(),
+                connected_at: meta.connected_at.clone(),
+                agent_name: meta.agent_name.clone(),
+                agent_version: meta.agent_version.clone(),
+                protocol_version: meta.protocol_version,
+                model: meta.model.clone(),
+                capabilities: meta.capabilities.clone(),
+                session_id: meta.session_id.clone(),
+                format: meta.format,
+                last_activity: meta.last_activity.lock().unwrap().clone(),
+                latency_ms: connection.latency_ms,
+            }
+        })
+        .collect();
+    list.sort_by_key(|info| info.id);
+    Ok(list)
+}
+
+// Tauri command to close one agent's connection
+#[tauri::command]
+async fn disconnect_connection(state: State<'_, AppState>, id: u64) -> Result<(), CommandError> {
+    let mut connections = state.connections.lock().await;
+    let dropped = connections
+        .entries
+        .get(&id)
+        .map(|connection| connection.dropped.clone())
+        .ok_or_else(|| CommandError::NotFound(format!("No connection with id {}", id)))?;
+    let writer = connections.remove(id);
+    drop(connections);
+
+    dropped.trigger("disconnected by user");
+    if let Some(writer) = writer {
+        close_writer(writer, CloseCode::Normal, "disconnected by user").await;
+    }
+    Ok(())
+}
+
+// Tauri command to get traffic counters and the state of the server
+#[tauri::command]
+fn get_server_health(state: State<'_, AppState>) -> health::ServerHealth {
+    server_health(&state)
+}
+
+fn server_health(state: &AppState) -> health::ServerHealth {
+    let queue_depth = state.in_flight.lock().unwrap().len();
+    state.stats.snapshot(queue_depth, server_listening(state), listening_port(state))
+}
+
+// Emit server-health every health_interval_secs
+async fn watch_health(app: AppHandle) {
+    let interval_secs = app.state::<AppState>().config.lock().unwrap().health_interval_secs;
+    if interval_secs == 0 {
+        return;
+    }
+    let mut tick = tokio::time::interval(Duration::from_secs(interval_secs));
+    loop {
+        tick.tick().await;
+        let _ = app.emit("server-health", server_health(&app.state::<AppState>()));
+        let stats = app.state::<AppState>().stability.lock().unwrap().snapshot(Instant::now());
+        let _ = app.emit("connection-stats", stats);
+    }
+}
+
+// Tauri command to get how often agents connected, reconnected and were
+// away since the session started; also emitted as "connection-stats"
+#[tauri::command]
+fn get_connection_stats(state: State<'_, AppState>) -> stability::ConnectionStats {
+    state.stability.lock().unwrap().snapshot(Instant::now())
+}
+
+// Tauri command to get the agent protocol version this overlay speaks
+#[tauri::command]
+fn get_protocol_version() -> u32 {
+    PROTOCOL_VERSION
+}
+
+// Tauri command to get the active agent's rolling average ping latency in ms
+#[tauri::command]
+async fn get_agent_latency(state: State<'_, AppState>) -> Result<Option<u32>, CommandError> {
+    let connections = state.connections.lock().await;
+    Ok(connections
+        .active
+        .and_then(|id| connections.entries.get(&id))
+        .and_then(|c| c.latency_ms))
+}
+
+// Tauri command to get the wss:// certificate fingerprint agents should pin
+#[tauri::command]
+fn get_ws_cert_fingerprint(state: State<'_, AppState>) -> Result<String, CommandError> {
+    state
+        .ws_cert_fingerprint
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| CommandError::NotFound("TLS is not enabled".to_string()))
+}
+
+// Tauri command to toggle do-not-disturb. The agent stays connected; its
+// messages still reach the history but not the webview. Turning it off
+// replays the held-back messages unless `replay` is false.
+#[tauri::command]
+fn set_dnd(app: AppHandle, enabled: bool, replay: Option<bool>) -> Result<(), CommandError> {
+    apply_dnd(&app, enabled, replay.unwrap_or(true));
+    save_prefs(&app);
+    Ok(())
+}
+
+// Messages held back during do-not-disturb, oldest first
+#[derive(Default)]
+struct HeldMessages {
+    messages: VecDeque<AgentMessage>,
+    // Dropped from the front once DND_HELD_LIMIT was reached
+    dropped: usize,
+}
+
+impl HeldMessages {
+    fn push(&mut self, message: AgentMessage) {
+        if self.messages.len() >= DND_HELD_LIMIT {
+            self.messages.pop_front();
+            self.dropped += 1;
+        }
+        self.messages.push_back(message);
+    }
+
+    // Everything held back, including the messages dropped for space
+    fn count(&self) -> usize {
+        self.messages.len() + self.dropped
+    }
+}
+
+// Turn do-not-disturb on or off; turning it off replays the held-back
+// messages, or discards them when `replay` is false
+fn apply_dnd(app: &impl EventSink, enabled: bool, replay: bool) {
+    let state = app.app_state();
+    let mut held = state.dnd_suppressed.lock().unwrap();
+    state.prefs.lock().unwrap().dnd = enabled;
+    let count = held.count();
+    if let Some(handle) = app.app_handle() {
+        let _ = handle.emit("dnd-changed", DndEvent { enabled, suppressed: count });
+    }
+    if enabled {
+        return;
+    }
+    // Replayed under the lock so newer messages can't overtake them
+    let messages = std::mem::take(&mut *held).messages;
+    if replay {
+        for message in messages {
+            emit_message_events(app, message);
+        }
+    }
+}
+
+// Forward a completed message to the webview, or hold it back during do-not-disturb
+fn emit_agent_message(app: &impl EventSink, message: AgentMessage) {
+    let state = app.app_state();
+    // Held from the dnd check to the push, so set_dnd can't drain in between
+    let mut held = state.dnd_suppressed.lock().unwrap();
+    let (dnd, auto_expand) = {
+        let prefs = state.prefs.lock().unwrap();
+        (prefs.dnd, prefs.auto_expand)
+    };
+    if !dnd {
+        drop(held);
+        // The user's own messages echoed back don't need the panel
+        if let Some(handle) = app.app_handle().filter(|_| auto_expand && message.role != "user") {
+            if let Err(e) = switch_overlay_mode(handle, overlay_mode::OverlayMode::Expanded, true) {
+                tracing::error!(error = %e, "Failed to expand overlay");
+            }
+        }
+        emit_message_events(app, message);
+        return;
+    }
+    held.push(message);
+    if let Some(handle) = app.app_handle() {
+        let _ = handle.emit("dnd-changed", DndEvent { enabled: true, suppressed: held.count() });
+    }
+}
+
+// Emit "agent-tool-call" after any text still buffered, so the webview sees
+// a message's text and its tool calls in the order the agent sent them
+fn emit_tool_call(app: &impl EventSink, deltas: &mut delta::DeltaBuffer, update: tool_calls::ToolCallUpdate) {
+    for event in deltas.take_all() {
+        emit_delta(app, event);
+    }
+    emit_agent_event(app, "agent-tool-call", update);
+}
+
+// Emit a complete message, then its tool call summaries as "agent-tool-call"
+fn emit_message_events(app: &impl EventSink, message: AgentMessage) {
+    let calls = match (&message.id, &message.tool_calls) {
+        (Some(id), Some(summaries)) => tool_calls::from_summaries(id, summaries),
+        _ => Vec::new(),
+    };
+    emit_agent_event(app, "agent-message", message);
+    for call in calls {
+        emit_agent_event(app, "agent-tool-call", call);
+    }
+}
+
+// Forward a streaming delta unless do-not-disturb is on
+fn emit_delta(app: &impl EventSink, event: delta::DeltaEvent) {
+    if !app.app_state().prefs.lock().unwrap().dnd {
+        emit_agent_event(app, "agent-message-delta", event);
+    }
+}
+
+// Tauri command to get the most recent agent warnings, oldest first
+#[tauri::command]
+fn get_recent_warnings(state: State<'_, AppState>) -> Result<Vec<WarningRecord>, CommandError> {
+    Ok(state.recent_warnings.lock().unwrap().iter().cloned().collect())
+}
+
+// Record a warning and emit it as "agent-warning"
+fn emit_agent_warning(app: &impl EventSink, warning: AgentWarning) {
+    tracing::warn!(?warning, "Agent warning");
+    let record = WarningRecord { warning, timestamp: chrono::Utc::now().to_rfc3339() };
+    {
+        let state = app.app_state();
+        let mut recent = state.recent_warnings.lock().unwrap();
+        recent.push_back(record.clone());
+        while recent.len() > RECENT_WARNINGS_LIMIT {
+            recent.pop_front();
+        }
+    }
+    emit_agent_event(app, "agent-warning", record);
+}
+
+// Pass a send result through, reporting timeouts as SlowConsumer warnings.
+// SendError is as large as the tungstenite error it wraps.
+#[allow(clippy::result_large_err)]
+fn check_send(app: &impl EventSink, result: Result<(), SendError>) -> Result<(), SendError> {
+    if let Err(SendError::TimedOut { id, timeouts }) = result {
+        emit_agent_warning(app, AgentWarning::SlowConsumer { id, timeouts });
+    }
+    result
+}
+
+// Tauri command to get the last few frames that failed to parse, oldest first
+#[tauri::command]
+fn get_recent_errors(state: State<'_, AppState>) -> Result<Vec<ParseErrorRecord>, CommandError> {
+    Ok(state.recent_errors.lock().unwrap().iter().cloned().collect())
+}
+
+// Tauri command to choose which window receives agent events
+#[tauri::command]
+fn set_event_target(app: AppHandle, state: State<'_, AppState>, label: String) -> Result<(), CommandError> {
+    if app.get_webview_window(&label).is_none() {
+        tracing::warn!(%label, "Event target window does not exist yet, broadcasting until it does");
+    }
+    *state.event_target.lock().unwrap() = Some(label);
+    Ok(())
+}
+
+// Emit an agent event through the app's event sink
+fn emit_agent_event<S: Serialize + Clone>(app: &impl EventSink, event: &str, payload: S) {
+    app.emit_event(event, payload);
+}
+
+fn emit_agent_error(app: &impl EventSink, message: String) {
+    emit_agent_event(app, "agent-error", AgentErrorEvent { message, parse_error: None });
+}
+
+// Log an unparseable frame and report it to the UI with the raw payload
+fn report_parse_error(app: &impl EventSink, connection_id: u64, text: &str, e: &serde_json::Error) {
+    tracing::warn!(id = connection_id, error = %e, "Failed to parse message");
+    let record = ParseErrorRecord {
+        connection_id,
+        error: e.to_string(),
+        line: e.line(),
+        column: e.column(),
+        payload: text.chars().take(ERROR_PAYLOAD_PREVIEW).collect(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let state = app.app_state();
+    let mut recent = state.recent_errors.lock().unwrap();
+    recent.push_back(record.clone());
+    while recent.len() > RECENT_ERRORS_LIMIT {
+        recent.pop_front();
+    }
+
+    emit_agent_event(
+        app,
+        "agent-error",
+        AgentErrorEvent { message: format!("Parse error: {}", e), parse_error: Some(record) },
+    );
+}
+
+// Tauri command to get the most recent completed messages matching an
+// optional filter, oldest first
+#[tauri::command]
+fn get_message_history(
+    state: State<'_, AppState>,
+    filter: Option<history::HistoryFilter>,
+) -> Result<Vec<AgentMessage>, CommandError> {
+    let filter = filter.unwrap_or_default();
+    let matcher = filter.matcher().map_err(CommandError::InvalidArg)?;
+    let history = state.history.lock().unwrap();
+    let matching: Vec<&AgentMessage> = history
+        .iter()
+        .filter(|message| matcher.matches(&message.role, &message.timestamp))
+        .collect();
+    let skip = filter.limit.map_or(0, |limit| matching.len().saturating_sub(limit));
+    Ok(matching.into_iter().skip(skip).cloned().collect())
+}
+
+// Tauri command to save the message history as "markdown" or "json", to
+// `path` or the app data dir. Returns the path written.
+#[tauri::command]
+async fn export_transcript(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    format: String,
+    path: Option<String>,
+) -> Result<String, CommandError> {
+    let format = transcript::Format::parse(&format).map_err(CommandError::InvalidArg)?;
+    let history: Vec<AgentMessage> = state.history.lock().unwrap().iter().cloned().collect();
+    if history.is_empty() {
+        return Err(CommandError::NotFound("There are no messages to export".to_string()));
+    }
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => app.path().app_data_dir()?.join(transcript::TRANSCRIPT_DIR).join(format!(
+            "transcript-{}.{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            format.extension()
+        )),
+    };
+    let text = transcript::render(format, &history)?;
+
+    tokio::task::spawn_blocking(move || {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, text)?;
+        tracing::info!(path = %path.display(), "Exported transcript");
+        Ok(path.to_string_lossy().into_owned())
+    })
+    .await
+    .map_err(|e| CommandError::Io(e.to_string()))?
+}
+
+// Tauri command to re-emit the history as agent-message events, e.g. after a webview reload
+#[tauri::command]
+fn replay_history(app: AppHandle, state: State<'_, AppState>) -> Result<usize, CommandError> {
+    let history = state.history.lock().unwrap().clone();
+    let count = history.len();
+    for message in history {
+        deliver_paced(&app, message);
+    }
+    Ok(count)
+}
+
+// Tauri command to pace bursts of completed messages at `msgs_per_sec`,
+// 0 for no pacing. Anything queued is shown at once when pacing stops
+#[tauri::command]
+fn set_history_replay_rate(app: AppHandle, state: State<'_, AppState>, msgs_per_sec: u32) -> Result<(), CommandError> {
+    state.config.lock().unwrap().replay_rate_per_sec = msgs_per_sec;
+    let flushed = state.replay_pacer.lock().unwrap().set_rate(msgs_per_sec);
+    for message in flushed {
+        emit_agent_event(&app, "agent-message", message);
+    }
+    Ok(())
+}
+
+// Deliver a replayed message now, or queue it for the replay task when a
+// burst exceeds the replay rate. Live agent messages bypass the pacer so
+// they stay in order with the deltas and tool calls around them
+fn deliver_paced(app: &AppHandle, message: AgentMessage) {
+    let state = app.state::<AppState>();
+    let admitted = state.replay_pacer.lock().unwrap().admit(message);
+    match admitted {
+        Some(message) => emit_agent_event(app, "agent-message", message),
+        None => {
+            let mut task = state.replay_task.lock().unwrap();
+            if task.is_none() {
+                *task = Some(tauri::async_runtime::spawn(drain_paced(app.clone())));
+            }
+        }
+    }
+}
+
+// Deliver queued messages one interval apart until the queue is empty
+async fn drain_paced(app: AppHandle) {
+    loop {
+        let interval = app.state::<AppState>().replay_pacer.lock().unwrap().interval();
+        tokio::time::sleep(interval).await;
+        let next = {
+            // Clear the task under its lock so a message queued meanwhile starts a new one
+            let state = app.state::<AppState>();
+            let mut task = state.replay_task.lock().unwrap();
+            let next = state.replay_pacer.lock().unwrap().pop();
+            if next.is_none() {
+                *task = None;
+            }
+            next
+        };
+        match next {
+            Some(message) => emit_agent_event(&app, "agent-message", message),
+            None => break,
+        }
+    }
+}
+
+// Tauri command to get the text of the latest assistant answer, streamed or
+// not. Tool-only turns without text are skipped; None when there is no answer
+#[tauri::command]
+fn get_last_assistant_message(state: State<'_, AppState>) -> Option<String> {
+    if let Some(text) = state.last_streamed.lock().unwrap().clone() {
+        return Some(text);
+    }
+    state
+        .history
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .find(|message| message.role == "assistant" && !message.content.trim().is_empty())
+        .map(|message| message.content.clone())
+}
+
+// Tauri command to put the latest assistant answer on the clipboard.
+// Returns false when there is nothing to copy
+#[tauri::command]
+fn copy_last_response(app: AppHandle, state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let Some(text) = get_last_assistant_message(state) else {
+        return Ok(false);
+    };
+    app.clipboard().write_text(text).map_err(|e| CommandError::Io(e.to_string()))?;
+    Ok(true)
+}
+
+// Append a completed message to the history, dropping the oldest past `capacity`
+fn record_history(app: &impl EventSink, message: &AgentMessage, capacity: usize) {
+    let state = app.app_state();
+    if message.role == "assistant" && !message.content.trim().is_empty() {
+        *state.last_streamed.lock().unwrap() = None;
+    }
+    if let Some(log) = state.transcript_log.lock().unwrap().as_mut() {
+        if let Err(e) = log.append(message) {
+            tracing::warn!(error = %e, "Failed to write transcript log");
+        }
+    }
+    let mut history = state.history.lock().unwrap();
+    history.push_back(message.clone());
+    while history.len() > capacity {
+        history.pop_front();
+    }
+}
+
+// Tauri command to keep every completed message in a log under the app
+// data dir, archived and gzipped each time it reaches `max_size_mb`
+#[tauri::command]
+fn set_transcript_logging(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+    max_size_mb: u64,
+) -> Result<(), CommandError> {
+    check_transcript_size(max_size_mb)?;
+    let logging = transcript_log::TranscriptLogging { enabled, max_size_mb };
+    apply_transcript_logging(&app, &state, logging)?;
+    state.prefs.lock().unwrap().transcript_logging = logging;
+    save_prefs(&app);
+    Ok(())
+}
+
+fn check_transcript_size(max_size_mb: u64) -> Result<(), CommandError> {
+    if !(1..=transcript_log::MAX_SIZE_MB).contains(&max_size_mb) {
+        return Err(CommandError::InvalidArg(format!(
+            "Transcript log size must be 1-{} MB, got {}",
+            transcript_log::MAX_SIZE_MB,
+            max_size_mb
+        )));
+    }
+    Ok(())
+}
+
+// Open, resize or close the transcript log to match `logging`
+fn apply_transcript_logging(
+    app: &AppHandle,
+    state: &AppState,
+    logging: transcript_log::TranscriptLogging,
+) -> Result<(), CommandError> {
+    let mut log = state.transcript_log.lock().unwrap();
+    match (logging.enabled, log.as_mut()) {
+        (true, Some(log)) => log.set_max_size(logging.max_size_mb),
+        (true, None) => {
+            let dir = app.path().app_data_dir()?.join(transcript_log::LOG_DIR);
+            *log = Some(transcript_log::TranscriptLog::open(&dir, logging.max_size_mb)?);
+        }
+        (false, _) => *log = None,
+    }
+    Ok(())
+}
+
+// Tauri command to read logged messages back from disk, oldest first,
+// skipping the `offset` most recent; for scrolling past the in-memory history
+#[tauri::command]
+async fn load_transcript_page(app: AppHandle, offset: usize, limit: usize) -> Result<Vec<AgentMessage>, CommandError> {
+    if !(1..=MAX_TRANSCRIPT_PAGE).contains(&limit) {
+        return Err(CommandError::InvalidArg(format!(
+            "Page size must be 1-{}, got {}",
+            MAX_TRANSCRIPT_PAGE, limit
+        )));
+    }
+    let dir = app.path().app_data_dir()?.join(transcript_log::LOG_DIR);
+    tokio::task::spawn_blocking(move || Ok(transcript_log::load_page(&dir, offset, limit)?))
+        .await
+        .map_err(|e| CommandError::Io(e.to_string()))?
+}
+
+// Tauri command to replace the auth token and rewrite the token file. The
+// old token keeps working for auth::ROTATION_OVERLAP; emits "token-rotated"
+#[tauri::command]
+fn rotate_ws_token(app: AppHandle, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let mut token = state.ws_token.lock().unwrap();
+    let token = token
+        .as_mut()
+        .ok_or_else(|| CommandError::NotFound("Auth token not initialized".to_string()))?;
+    token.rotate(Instant::now())?;
+    tracing::info!(path = %token.path.display(), "WebSocket token rotated");
+    let event = TokenRotatedEvent {
+        path: token.path.to_string_lossy().into_owned(),
+        overlap_secs: auth::ROTATION_OVERLAP.as_secs(),
+    };
+    let _ = app.emit("token-rotated", event);
+    Ok(())
+}
+
+// Tauri command to get the path of the token file agents authenticate with
+#[tauri::command]
+fn get_ws_token_path(state: State<'_, AppState>) -> Result<String, CommandError> {
+    let token = state.ws_token.lock().unwrap();
+    let token = token
+        .as_ref()
+        .ok_or_else(|| CommandError::NotFound("Auth token not initialized".to_string()))?;
+    Ok(token.path.to_string_lossy().into_owned())
+}
+
+// Tauri command to set the glass tint and re-apply it live.
+// Alpha is 0-220 (liquid_glass::MAX_TINT_ALPHA); larger values are clamped.
+#[tauri::command]
+fn set_glass_tint(app: AppHandle, state: State<'_, AppState>, r: u8, g: u8, b: u8, a: u8) -> Result<(), CommandError> {
+    state.glass.lock().unwrap().set_tint(r, g, b, a);
+    reapply_glass(&app);
+    Ok(())
+}
+
+// Revert timer of the running preview_glass. An aborted timer may already
+// be past its sleep, so it checks its generation before reverting.
+#[derive(Default)]
+struct GlassPreviewTimer {
+    generation: u64,
+    task: Option<tauri::async_runtime::JoinHandle<()>>,
+}
+
+impl GlassPreviewTimer {
+    // Cancel the running preview's timer; returns the new preview's
+    // generation and whether one was cut short
+    fn begin(&mut self) -> (u64, bool) {
+        let replaced = self.task.take().inspect(|task| task.abort()).is_some();
+        self.generation += 1;
+        (self.generation, replaced)
+    }
+
+    // Whether the preview of `generation` is still the newest; if so its timer is done
+    fn finish(&mut self, generation: u64) -> bool {
+        if generation != self.generation {
+            return false;
+        }
+        self.task = None;
+        true
+    }
+}
+
+// Tauri command to try glass settings on every glass window for
+// `duration_ms`, then put the current ones back. A new preview replaces a
+// running one. Emits "glass-preview-start" and "glass-preview-end".
+#[tauri::command]
+fn preview_glass(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    params: liquid_glass::GlassPreview,
+    duration_ms: u64,
+) -> Result<(), CommandError> {
+    if !(1..=MAX_GLASS_PREVIEW_MS).contains(&duration_ms) {
+        return Err(CommandError::InvalidArg(format!(
+            "Preview duration must be 1-{} ms, got {}",
+            MAX_GLASS_PREVIEW_MS, duration_ms
+        )));
+    }
+    let mut timer = state.glass_preview.lock().unwrap();
+    let (generation, replaced) = timer.begin();
+    let preview = params.over(&state.glass.lock().unwrap());
+    reapply_glass_with(&app, &preview);
+    let _ = app.emit("glass-preview-start", GlassPreviewEvent { duration_ms, replaced });
+
+    let app = app.clone();
+    timer.task = Some(tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(duration_ms)).await;
+        let main = app.clone();
+        let _ = app.run_on_main_thread(move || {
+            let state = main.state::<AppState>();
+            // Held while reverting so a newer preview can't apply in between
+            let mut timer = state.glass_preview.lock().unwrap();
+            if !timer.finish(generation) {
+                return;
+            }
+            // Back to whatever the setters hold now, which may have changed meanwhile
+            reapply_glass(&main);
+            let _ = main.emit("glass-preview-end", ());
+        });
+    }));
+    Ok(())
+}
+
+// Tauri command to set glass intensity (0-100), re-applied live; emits
+// "glass-intensity-changed" with the clamped level and returns it
+#[tauri::command]
+fn set_glass_intensity(app: AppHandle, state: State<'_, AppState>, level: u8) -> u8 {
+    let level = state.glass.lock().unwrap().set_intensity(level);
+    reapply_glass(&app);
+    let _ = app.emit("glass-intensity-changed", level);
+    level
+}
+
+// Tauri command to set the window corner radius in logical pixels.
+// Windows with glass are re-applied and each emits "corner-radius-changed"
+// with the radius shown and whether it is native or left to CSS.
+#[tauri::command]
+fn set_corner_radius(app: AppHandle, state: State<'_, AppState>, radius: f64) -> Result<f64, CommandError> {
+    if !radius.is_finite() {
+        return Err(CommandError::InvalidArg(format!("Invalid corner radius: {}", radius)));
+    }
+    let radius = radius.clamp(0.0, liquid_glass::MAX_CORNER_RADIUS);
+    state.glass.lock().unwrap().corner_radius = radius;
+    reapply_glass(&app);
+    Ok(radius)
+}
+
+// Tauri command to confine the glass to a card `px` logical pixels in from
+// the window edges, with the margin invisible and passing clicks through.
+// Each glass window emits "glass-geometry-changed" with the card.
+#[tauri::command]
+fn set_glass_inset(app: AppHandle, state: State<'_, AppState>, px: f64) -> Result<f64, CommandError> {
+    if !px.is_finite() || px < 0.0 {
+        return Err(CommandError::InvalidArg(format!("Invalid glass inset: {}", px)));
+    }
+    let inset = px.min(liquid_glass::MAX_GLASS_INSET);
+    state.glass.lock().unwrap().inset = inset;
+    reapply_glass(&app);
+    if let Some(window) = app.get_webview_window("main") {
+        let click_through = state.prefs.lock().unwrap().click_through;
+        apply_click_through(&app, &window, click_through)?;
+    }
+    Ok(inset)
+}
+
+// Tauri command to turn the drop shadow behind glass windows on or off.
+// Each emits "window-appearance-changed".
+#[tauri::command]
+fn set_window_shadow(app: AppHandle, state: State<'_, AppState>, enabled: bool) {
+    state.glass.lock().unwrap().shadow = enabled;
+    redecorate_glass(&app);
+}
+
+// Tauri command to draw a border around glass windows; `color` is RGBA and
+// a width of 0 removes it. Each window emits "window-appearance-changed".
+#[tauri::command]
+fn set_window_border(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    color: (u8, u8, u8, u8),
+    width: f64,
+) -> Result<(), CommandError> {
+    if !(0.0..=liquid_glass::MAX_BORDER_WIDTH).contains(&width) {
+        return Err(CommandError::InvalidArg(format!(
+            "Border width must be 0-{}, got {}",
+            liquid_glass::MAX_BORDER_WIDTH,
+            width
+        )));
+    }
+    let border = (width > 0.0).then_some(liquid_glass::WindowBorder { color, width });
+    state.glass.lock().unwrap().border = border;
+    redecorate_glass(&app);
+    Ok(())
+}
+
+// Re-apply shadow and border to every window with glass, without touching the effect
+fn redecorate_glass(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let glass = *state.glass.lock().unwrap();
+    for label in state.glass_windows.lock().unwrap().keys() {
+        if let Some(window) = app.get_webview_window(label) {
+            let _ = app.emit("window-appearance-changed", liquid_glass::decorate(&window, &glass));
+        }
+    }
+}
+
+// Tauri command to report what the glass effect supports on this OS
+#[tauri::command]
+fn glass_capabilities() -> liquid_glass::GlassCapabilities {
+    liquid_glass::capabilities()
+}
+
+// Tauri command to list vibrancy materials and which the current OS supports
+#[tauri::command]
+fn list_glass_materials() -> Vec<liquid_glass::GlassMaterialInfo> {
+    liquid_glass::materials()
+}
+
+// Tauri command to turn the glass effect on or off for any window by label
+#[tauri::command]
+fn set_window_glass(app: AppHandle, state: State<'_, AppState>, label: String, enabled: bool) -> Result<(), CommandError> {
+    let window = app
+        .get_webview_window(&label)
+        .ok_or_else(|| CommandError::window_not_found(&label))?;
+    toggle_glass(&app, &state, &window, enabled);
+    Ok(())
+}
+
+fn toggle_glass(app: &AppHandle, state: &AppState, window: &WebviewWindow, enabled: bool) {
+    let label = window.label().to_string();
+    let glass = *state.glass.lock().unwrap();
+    {
+        let mut glass_windows = state.glass_windows.lock().unwrap();
+        if enabled {
+            let previous = glass_windows.get(&label).copied();
+            let backend = apply_glass(app, window, &glass, previous);
+            glass_windows.insert(label.clone(), backend);
+        } else {
+            liquid_glass::remove(window);
+            if glass_windows.remove(&label) == Some(liquid_glass::GlassBackend::Fallback) {
+                liquid_glass::clear_fallback(window);
+            }
+        }
+    }
+    if label == "main" {
+        if let Some(item) = state.glass_menu_item.lock().unwrap().as_ref() {
+            let _ = item.set_checked(enabled);
+        }
+    }
+}
+
+// Re-fit the main window's size-dependent corner rounding and glass inset
+// after a resize
+fn refit_glass(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let Some(backend) = state.glass_windows.lock().unwrap().get("main").copied() else {
+        return;
+    };
+    let Some(window) = app.get_webview_window("main") else { return };
+    let glass = *state.glass.lock().unwrap();
+    liquid_glass::refit_to_size(&window, &glass);
+    if glass.inset > 0.0 {
+        // The card's hit region moved with the window size
+        let click_through = state.prefs.lock().unwrap().click_through;
+        if let Err(e) = apply_click_through(app, &window, click_through) {
+            tracing::error!(error = %e, "Failed to update click-through for the glass inset");
+        }
+        let _ = app.emit("glass-geometry-changed", liquid_glass::geometry(&window, &glass, backend));
+    }
+}
+
+// Re-apply the current glass config to every window that has it enabled
+fn reapply_glass(app: &AppHandle) {
+    let glass = *app.state::<AppState>().glass.lock().unwrap();
+    reapply_glass_with(app, &glass);
+}
+
+fn reapply_glass_with(app: &AppHandle, glass: &liquid_glass::GlassConfig) {
+    let state = app.state::<AppState>();
+    for (label, backend) in state.glass_windows.lock().unwrap().iter_mut() {
+        if let Some(window) = app.get_webview_window(label) {
+            *backend = apply_glass(app, &window, glass, Some(*backend));
+        }
+    }
+}
+
+// Apply glass to one window and emit "glass-status" with the outcome
+fn apply_glass(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    glass: &liquid_glass::GlassConfig,
+    previous: Option<liquid_glass::GlassBackend>,
+) -> liquid_glass::GlassBackend {
+    // Keep the main window's base or dimmed opacity across the re-apply
+    let mut glass = *glass;
+    if window.label() == "main" {
+        glass.opacity = Some(app.state::<AppState>().idle.lock().unwrap().opacity());
+    }
+    let backend = liquid_glass::apply(window, &glass);
+    let corners = liquid_glass::round_corners(window, &glass, backend);
+    let _ = app.emit("corner-radius-changed", corners);
+    let _ = app.emit("window-appearance-changed", liquid_glass::decorate(window, &glass));
+    let _ = app.emit("glass-geometry-changed", liquid_glass::geometry(window, &glass, backend));
+    let status = backend.status();
+    if status == liquid_glass::GlassStatus::Vibrancy && previous == Some(liquid_glass::GlassBackend::Fallback) {
+        liquid_glass::clear_fallback(window);
+    }
+    // The macOS effect resets the window to the floating level; put back the chosen one
+    if window.label() == "main" {
+        if let Err(e) = apply_window_level(app) {
+            tracing::error!(error = %e, "Failed to apply window level");
+        }
+    }
+    let _ = app.emit(
+        "glass-status",
+        GlassStatusEvent { label: window.label().to_string(), status, backend },
+    );
+    backend
+}
+
+// Tauri command to get the effect behind the main window: "vibrancy",
+// "acrylic", "mica", "compositor" or "fallback"
+#[tauri::command]
+fn get_glass_backend(state: State<'_, AppState>) -> Result<String, CommandError> {
+    state
+        .glass_windows
+        .lock()
+        .unwrap()
+        .get("main")
+        .map(|backend| backend.as_str().to_string())
+        .ok_or_else(|| CommandError::NotFound("Glass is not applied to the main window".to_string()))
+}
+
+// Tauri command to pin the theme ("light" or "dark"), or follow the OS again with None
+#[tauri::command]
+fn set_theme_override(app: AppHandle, theme: Option<String>) -> Result<(), CommandError> {
+    let pinned = match theme.as_deref() {
+        None => None,
+        Some("light") => Some(Theme::Light),
+        Some("dark") => Some(Theme::Dark),
+        Some(other) => return Err(CommandError::InvalidArg(format!("Unknown theme: {}", other))),
+    };
+    pin_theme(&app, pinned)
+}
+
+// Pin the theme, or follow the OS with None, and re-apply the glass for it
+fn pin_theme(app: &AppHandle, pinned: Option<Theme>) -> Result<(), CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    *app.state::<AppState>().theme_override.lock().unwrap() = pinned;
+
+    // Pin the native appearance too so vibrancy materials follow it
+    let _ = window.set_theme(pinned);
+    let theme = pinned.unwrap_or_else(|| liquid_glass::system_theme(&window));
+    apply_theme(app, theme);
+    Ok(())
+}
+
+// Tauri command to save the current tint, theme and window level as a named preset
+#[tauri::command]
+fn save_glass_preset(app: AppHandle, state: State<'_, AppState>, name: String) -> Result<(), CommandError> {
+    if !glass_presets::valid_name(&name) {
+        return Err(CommandError::InvalidArg(format!("Invalid preset name: {}", name)));
+    }
+    let preset = {
+        let prefs = state.prefs.lock().unwrap();
+        glass_presets::GlassPreset {
+            tint: state.glass.lock().unwrap().tint,
+            theme: *state.theme_override.lock().unwrap(),
+            window_level: prefs.window_level,
+            current_space_only: prefs.current_space_only,
+        }
+    };
+    let dir = app.path().app_config_dir()?.join(glass_presets::PRESET_DIR);
+    glass_presets::save(&dir, &name, &preset)?;
+    Ok(())
+}
+
+// Tauri command to apply a saved or built-in preset; emits "glass-status"
+#[tauri::command]
+fn load_glass_preset(app: AppHandle, state: State<'_, AppState>, name: String) -> Result<(), CommandError> {
+    if !glass_presets::valid_name(&name) {
+        return Err(CommandError::InvalidArg(format!("Invalid preset name: {}", name)));
+    }
+    let dir = app.path().app_config_dir()?.join(glass_presets::PRESET_DIR);
+    let preset = glass_presets::load(&dir, &name)?
+        .ok_or_else(|| CommandError::NotFound(format!("Unknown glass preset: {}", name)))?;
+
+    match preset.tint {
+        Some((r, g, b, a)) => state.glass.lock().unwrap().set_tint(r, g, b, a),
+        None => state.glass.lock().unwrap().tint = None,
+    }
+    {
+        let mut prefs = state.prefs.lock().unwrap();
+        prefs.window_level = preset.window_level;
+        prefs.current_space_only = preset.current_space_only;
+    }
+    save_prefs(&app);
+    // Re-applies the glass, and with it the window level
+    pin_theme(&app, preset.theme)
+}
+
+// Tauri command to list built-in and saved glass presets
+#[tauri::command]
+fn list_glass_presets(app: AppHandle) -> Result<Vec<String>, CommandError> {
+    let dir = app.path().app_config_dir()?.join(glass_presets::PRESET_DIR);
+    Ok(glass_presets::list(&dir))
+}
+
+// Tauri command to let clicks fall through the overlay to the app behind it.
+// With a hit region (logical px, window-relative) that area stays interactive.
+#[tauri::command]
+fn set_click_through(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+    hit_region: Option<click_through::HitRegion>,
+) -> Result<(), CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    let click_through = click_through::ClickThrough { enabled, hit_region };
+    apply_click_through(&app, &window, click_through)?;
+
+    state.prefs.lock().unwrap().click_through = click_through;
+    save_prefs(&app);
+    let _ = app.emit("click-through-changed", click_through);
+    Ok(())
+}
+
+// With a glass inset and click-through otherwise off, only the glass card
+// stays interactive so the invisible margin passes clicks
+fn apply_click_through(app: &AppHandle, window: &tauri::WebviewWindow, click_through: click_through::ClickThrough) -> tauri::Result<()> {
+    let state = app.state::<AppState>();
+    let glass = *state.glass.lock().unwrap();
+    let click_through = match click_through {
+        click_through::ClickThrough { enabled: false, .. } if glass.inset > 0.0 && window.label() == "main" => {
+            let card = liquid_glass::geometry(window, &glass, liquid_glass::GlassBackend::Vibrancy);
+            click_through::ClickThrough {
+                enabled: true,
+                hit_region: Some(click_through::HitRegion { x: card.x, y: card.y, width: card.width, height: card.height }),
+            }
+        }
+        click_through => click_through,
+    };
+    let mut task = state.hit_region_task.lock().unwrap();
+    if let Some(task) = task.take() {
+        task.abort();
+    }
+    *task = click_through::apply(window, click_through)?;
+    Ok(())
+}
+
+// Tauri command to fade the overlay after `timeout_ms` without messages or focus
+#[tauri::command]
+fn set_idle_dimming(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+    timeout_ms: u64,
+    dimmed_opacity: f64,
+) -> Result<(), CommandError> {
+    let settings = idle::IdleDimming {
+        enabled,
+        timeout_ms,
+        dimmed_opacity: dimmed_opacity.clamp(0.1, 1.0),
+    };
+    state.prefs.lock().unwrap().idle_dimming = settings;
+    save_prefs(&app);
+    // Start the timer over and restore the window if it was dimmed
+    note_activity(&app);
+    Ok(())
+}
+
+// Reset the idle timer, restoring the overlay if it was dimmed
+fn note_activity(app: &impl EventSink) {
+    let state = app.app_state();
+    let mut tracker = state.idle.lock().unwrap();
+    if let Some(handle) = app.app_handle().filter(|_| tracker.touch()) {
+        if let Some(window) = handle.get_webview_window("main") {
+            let base = state.prefs.lock().unwrap().base_opacity;
+            tracker.fade_to(&window, base, motion(handle));
+        }
+        let _ = handle.emit("idle-dimming-changed", false);
+    }
+}
+
+// Dim the overlay once it has been idle and unfocused for the configured time
+async fn watch_idle(app: AppHandle) {
+    let mut tick = tokio::time::interval(idle::CHECK_INTERVAL);
+    loop {
+        tick.tick().await;
+        let Some(window) = app.get_webview_window("main") else { continue };
+        let focused = window.is_focused().unwrap_or(false);
+        let state = app.state::<AppState>();
+        let (settings, base) = {
+            let prefs = state.prefs.lock().unwrap();
+            (prefs.idle_dimming, prefs.base_opacity)
+        };
+        let mut tracker = state.idle.lock().unwrap();
+        if tracker.should_dim(&settings, focused) {
+            let target = tracker.target(&settings, base);
+            tracker.fade_to(&window, target, motion(&app));
+            let _ = app.emit("idle-dimming-changed", true);
+        }
+    }
+}
+
+// Tauri command to set the window's opacity when not dimmed, clamped to
+// liquid_glass::MIN_BASE_OPACITY-1.0. Emits "opacity-changed" with the
+// opacity the window settles at, which is lower while dimmed.
+#[tauri::command]
+fn set_base_opacity(app: AppHandle, state: State<'_, AppState>, opacity: f64) -> Result<f64, CommandError> {
+    if !opacity.is_finite() {
+        return Err(CommandError::InvalidArg(format!("Invalid opacity: {}", opacity)));
+    }
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    let base = opacity.clamp(liquid_glass::MIN_BASE_OPACITY, 1.0);
+    let settings = {
+        let mut prefs = state.prefs.lock().unwrap();
+        prefs.base_opacity = base;
+        prefs.idle_dimming
+    };
+    save_prefs(&app);
+
+    let mut tracker = state.idle.lock().unwrap();
+    let effective = tracker.target(&settings, base);
+    tracker.fade_to(&window, effective, motion(&app));
+    drop(tracker);
+    let _ = app.emit("opacity-changed", effective);
+    Ok(effective)
+}
+
+// Tauri command to turn the text contrast scrim on or off, at `min_opacity`
+// (0.0-1.0). Emits "scrim-changed" with the resulting opacity and color
+#[tauri::command]
+fn set_text_contrast_scrim(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+    min_opacity: f64,
+) -> Result<liquid_glass::ScrimState, CommandError> {
+    if !(0.0..=1.0).contains(&min_opacity) {
+        return Err(CommandError::InvalidArg(format!("Invalid scrim opacity: {}", min_opacity)));
+    }
+    state.prefs.lock().unwrap().text_scrim = liquid_glass::TextScrim { enabled, min_opacity };
+    save_prefs(&app);
+    Ok(emit_scrim(&app))
+}
+
+// Tauri command to get the scrim the webview should draw, e.g. on load
+#[tauri::command]
+fn get_text_contrast_scrim(state: State<'_, AppState>) -> liquid_glass::ScrimState {
+    let scrim = state.prefs.lock().unwrap().text_scrim;
+    scrim.state(state.glass.lock().unwrap().dark)
+}
+
+// Emit "scrim-changed" for the current scrim and theme
+fn emit_scrim(app: &AppHandle) -> liquid_glass::ScrimState {
+    let scrim = get_text_contrast_scrim(app.state());
+    let _ = app.emit("scrim-changed", scrim.clone());
+    scrim
+}
+
+// Tauri command to blink the overlay `times` times (at most
+// attention::MAX_PULSES) and ask the OS for the user's attention
+#[tauri::command]
+fn pulse_attention(app: AppHandle, times: u32) -> Result<(), CommandError> {
+    if app.get_webview_window("main").is_none() {
+        return Err(CommandError::window_not_found("main"));
+    }
+    pulse_main_window(&app, times);
+    Ok(())
+}
+
+fn pulse_main_window(app: &AppHandle, times: u32) {
+    let Some(window) = app.get_webview_window("main") else { return };
+    let state = app.state::<AppState>();
+    let base = state.idle.lock().unwrap().opacity();
+    let mut task = state.pulse_task.lock().unwrap();
+    if let Some(task) = task.take() {
+        task.abort();
+    }
+    *task = attention::pulse(window, times, base, motion(app));
+}
+
+// Tauri command to set the frame rate animations step at (1-animation::MAX_FPS)
+#[tauri::command]
+fn set_animation_fps(app: AppHandle, state: State<'_, AppState>, fps: u32) -> Result<(), CommandError> {
+    if fps == 0 || fps > animation::MAX_FPS {
+        return Err(CommandError::InvalidArg(format!(
+            "Frame rate must be 1-{}, got {}",
+            animation::MAX_FPS,
+            fps
+        )));
+    }
+    state.prefs.lock().unwrap().animation.fps = fps;
+    save_prefs(&app);
+    Ok(())
+}
+
+// Tauri command to replace animated transitions with instant changes
+#[tauri::command]
+fn set_reduced_motion(app: AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<(), CommandError> {
+    state.prefs.lock().unwrap().animation.reduced_motion = enabled;
+    save_prefs(&app);
+    Ok(())
+}
+
+// How the next animation should run, given the preferences and power state
+fn motion(app: &AppHandle) -> animation::Motion {
+    let settings = app.state::<AppState>().prefs.lock().unwrap().animation;
+    animation::Motion::resolve(&settings, animation::low_power())
+}
+
+// Tauri command to set the main window's stacking level:
+// "Normal", "Floating", "ModalPanel", "Status" or "ScreenSaver"
+#[tauri::command]
+fn set_window_level(app: AppHandle, state: State<'_, AppState>, level: String) -> Result<(), CommandError> {
+    let level = window_level::WindowLevel::parse(&level).ok_or_else(|| CommandError::InvalidArg(format!("Unknown window level: {}", level)))?;
+    state.prefs.lock().unwrap().window_level = level;
+    save_prefs(&app);
+    apply_window_level(&app)
+}
+
+// Tauri command to choose when the main window takes keyboard focus:
+// "NeverSteal", "FocusOnShow" or "FocusOnMessage". Emits "focus-policy-changed"
+#[tauri::command]
+fn set_focus_policy(app: AppHandle, state: State<'_, AppState>, policy: String) -> Result<(), CommandError> {
+    let policy =
+        focus::FocusPolicy::parse(&policy).ok_or_else(|| CommandError::InvalidArg(format!("Unknown focus policy: {}", policy)))?;
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    focus::apply(&window, policy)?;
+    state.prefs.lock().unwrap().focus_policy = policy;
+    save_prefs(&app);
+    let _ = app.emit("focus-policy-changed", policy);
+    Ok(())
+}
+
+// Tauri command to show the main window on every space/workspace, or only its own
+#[tauri::command]
+fn set_join_all_spaces(app: AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<(), CommandError> {
+    state.prefs.lock().unwrap().current_space_only = !enabled;
+    save_prefs(&app);
+    apply_window_level(&app)
+}
+
+// Tauri command to keep the main window above fullscreen apps. On macOS
+// this raises the level to at least "Status"; on Windows it forces topmost
+#[tauri::command]
+fn set_above_fullscreen(app: AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<(), CommandError> {
+    state.prefs.lock().unwrap().above_fullscreen = enabled;
+    save_prefs(&app);
+    apply_window_level(&app)
+}
+
+// Apply the saved level and space behavior to the main window
+fn apply_window_level(app: &AppHandle) -> Result<(), CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    let prefs = app.state::<AppState>().prefs.lock().unwrap().clone();
+    let (join_all_spaces, above_fullscreen) = (!prefs.current_space_only, prefs.above_fullscreen);
+    let level = window_level::apply(&window, prefs.window_level, join_all_spaces, above_fullscreen)?;
+    let _ = app.emit("window-level-changed", WindowLevelEvent { level, join_all_spaces, above_fullscreen });
+    Ok(())
+}
+
+// Tauri command to change the global show/hide hotkey, e.g. "CmdOrCtrl+Shift+Space"
+#[tauri::command]
+fn set_toggle_hotkey(app: AppHandle, state: State<'_, AppState>, accelerator: String) -> Result<(), CommandError> {
+    register_toggle_hotkey(&app, &accelerator)?;
+    state.prefs.lock().unwrap().toggle_hotkey = Some(accelerator);
+    save_prefs(&app);
+    Ok(())
+}
+
+// Register a new toggle hotkey, unregistering the previous one once it succeeds
+fn register_toggle_hotkey(app: &AppHandle, accelerator: &str) -> Result<(), CommandError> {
+    let shortcut: Shortcut = accelerator
+        .parse()
+        .map_err(|e| CommandError::InvalidArg(format!("Invalid hotkey {}: {}", accelerator, e)))?;
+    let shortcuts = app.global_shortcut();
+    if shortcuts.is_registered(shortcut) {
+        return Err(CommandError::InvalidArg(format!("Hotkey {} is already registered", accelerator)));
+    }
+    // Fails when another application already holds the combination
+    shortcuts
+        .register(shortcut)
+        .map_err(|e| CommandError::Io(format!("Failed to register hotkey {}: {}", accelerator, e)))?;
+
+    let state = app.state::<AppState>();
+    if let Some(previous) = state.toggle_hotkey.lock().unwrap().replace(shortcut) {
+        let _ = shortcuts.unregister(previous);
+    }
+    Ok(())
+}
+
+// Show and focus the main window, or hide it and hand focus back
+fn toggle_main_window(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else { return };
+
+    if window.is_visible().unwrap_or(false) && window.is_focused().unwrap_or(false) {
+        hide_main_window(app);
+        // Hiding the app returns focus to the previously active one
+        #[cfg(target_os = "macos")]
+        let _ = app.hide();
+    } else {
+        show_main_window(app);
+    }
+}
+
+// Tauri command to hide the overlay until it is brought back from the tray
+#[tauri::command]
+fn minimize_to_tray(app: AppHandle) -> Result<(), CommandError> {
+    if app.get_webview_window("main").is_none() {
+        return Err(CommandError::window_not_found("main"));
+    }
+    hide_main_window(&app);
+    Ok(())
+}
+
+// Hide the main window, saving its placement; emits "window-visibility-changed"
+fn hide_main_window(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else { return };
+    save_window_layout(app);
+    let _ = window.hide();
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_tooltip(Some("Jarvis (hidden)"));
+    }
+    let _ = app.emit("window-visibility-changed", false);
+}
+
+// Show the main window where it was saved, at the chosen window level
+fn show_main_window(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else { return };
+    if !window.is_visible().unwrap_or(false) {
+        if let Ok(dir) = app.path().app_config_dir() {
+            if let Some(saved) = window_layout::load(&dir.join(window_layout::LAYOUT_FILE)) {
+                if let Err(e) = window_layout::restore(&window, Some(saved)) {
+                    tracing::error!(error = %e, "Failed to restore window layout");
+                }
+            }
+        }
+    }
+    let policy = app.state::<AppState>().prefs.lock().unwrap().focus_policy;
+    if let Err(e) = focus::show(&window, policy) {
+        tracing::error!(error = %e, "Failed to show main window");
+    }
+    // macOS can drop the floating level while the window is hidden
+    if let Err(e) = apply_window_level(app) {
+        tracing::error!(error = %e, "Failed to apply window level");
+    }
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_tooltip(Some("Jarvis"));
+    }
+    let _ = app.emit("window-visibility-changed", true);
+}
+
+// Tauri command to move the main window with the mouse, for the custom titlebar
+#[tauri::command]
+fn start_window_drag(app: AppHandle) -> Result<(), CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    window.start_dragging()?;
+    // On macOS the drag runs until the mouse is released, and dropping the
+    // window on another space can reset the floating level; put it back
+    #[cfg(target_os = "macos")]
+    apply_window_level(&app)?;
+    Ok(())
+}
+
+// Tauri command to resize the main window from an edge or corner:
+// "North", "NorthEast", "East", ... "NorthWest"
+#[tauri::command]
+fn start_window_resize(app: AppHandle, edge: String) -> Result<(), CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    let direction = window_layout::resize_direction(&edge)
+        .ok_or_else(|| CommandError::InvalidArg(format!("Unknown edge: {}", edge)))?;
+    // Only exposed on the underlying Window, not WebviewWindow
+    window.as_ref().window().start_resize_dragging(direction)?;
+    Ok(())
+}
+
+// Tauri command to size the main window to its content (logical px), called
+// by the frontend after layout. Clamped to the work area and animated.
+#[tauri::command]
+fn resize_to_content(app: AppHandle, state: State<'_, AppState>, width: f64, height: f64) -> Result<(), CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    // The collapsed pill has a fixed size whatever the content
+    if state.overlay_mode.lock().unwrap().mode == overlay_mode::OverlayMode::Collapsed {
+        return Ok(());
+    }
+    let target = window_layout::fit_to_content(&window, width, height).map_err(CommandError::Io)?;
+
+    let mut task = state.resize_task.lock().unwrap();
+    if let Some(task) = task.take() {
+        task.abort();
+    }
+    // The frontend calls this again after every layout, including the one the resize causes
+    if window_layout::capture(&window)? != target {
+        *task = Some(window_layout::animate_to(window, target, motion(&app)));
+    }
+    Ok(())
+}
+
+// Tauri command to switch the overlay between the collapsed pill and the
+// expanded panel: "Collapsed" or "Expanded". `auto_expand` sets whether an
+// agent message expands the collapsed overlay.
+#[tauri::command]
+fn set_overlay_mode(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mode: String,
+    auto_expand: Option<bool>,
+) -> Result<(), CommandError> {
+    let mode = overlay_mode::OverlayMode::parse(&mode).ok_or_else(|| CommandError::InvalidArg(format!("Unknown overlay mode: {}", mode)))?;
+    if let Some(auto_expand) = auto_expand {
+        state.prefs.lock().unwrap().auto_expand = auto_expand;
+        save_prefs(&app);
+    }
+    switch_overlay_mode(&app, mode, false)
+}
+
+// Animate the main window to the size of `mode`, keeping its docked corner
+fn switch_overlay_mode(app: &AppHandle, mode: overlay_mode::OverlayMode, automatic: bool) -> Result<(), CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    let state = app.state::<AppState>();
+    let current = window.inner_size()?.to_logical::<f64>(window.scale_factor()?);
+    let Some((width, height)) = state.overlay_mode.lock().unwrap().switch(mode, (current.width, current.height)) else {
+        return Ok(());
+    };
+    let target = window_layout::anchored(&window, width, height).map_err(CommandError::Io)?;
+
+    let mut task = state.resize_task.lock().unwrap();
+    if let Some(task) = task.take() {
+        task.abort();
+    }
+    *task = Some(window_layout::animate_to(window, target, motion(app)));
+    drop(task);
+    tracing::info!(?mode, automatic, "Overlay mode changed");
+    app.emit("overlay-mode-changed", overlay_mode::OverlayModeEvent { mode, automatic })?;
+    Ok(())
+}
+
+// Tauri command to save a PNG of the main window as it looks right now,
+// to `path` or the app data dir. Returns the path written.
+#[tauri::command]
+async fn capture_overlay(app: AppHandle, path: Option<String>) -> Result<String, CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => app
+            .path()
+            .app_data_dir()?
+            .join(capture::CAPTURE_DIR)
+            .join(format!("overlay-{}.png", chrono::Local::now().format("%Y%m%d-%H%M%S"))),
+    };
+
+    // Window pixels can only be read on the main thread
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.run_on_main_thread(move || {
+        let _ = tx.send(capture::grab(&window));
+    })?;
+    let image = rx.await.map_err(|e| CommandError::Io(e.to_string()))?;
+    let image = image.inspect_err(|_| {
+        // A missing grant shows up as a failed grab; let the UI explain it
+        let status = check_screen_permission(app.state());
+        if matches!(status, permissions::PermissionStatus::Denied | permissions::PermissionStatus::Undetermined) {
+            let _ = app.emit("permission-needed", PermissionNeededEvent { permission: "screen_recording", status });
+        }
+    });
+    let image = image.map_err(CommandError::Io)?;
+
+    tokio::task::spawn_blocking(move || {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        image
+            .save_with_format(&path, image::ImageFormat::Png)
+            .map_err(|e| CommandError::Io(e.to_string()))?;
+        Ok(path.to_string_lossy().into_owned())
+    })
+    .await
+    .map_err(|e| CommandError::Io(e.to_string()))?
+}
+
+// Tauri command to open a URL with the system default handler. Only the
+// schemes in open_url_schemes are allowed. With `confirm`, the open waits for
+// confirm_open after an `open-confirm` event. Returns whether it was opened.
+#[tauri::command]
+async fn open_url(app: AppHandle, url: String, confirm: bool) -> Result<bool, CommandError> {
+    open_url_checked(&app, &url, confirm).await
+}
+
+async fn open_url_checked(app: &AppHandle, url: &str, confirm: bool) -> Result<bool, CommandError> {
+    let state = app.state::<AppState>();
+    let config = state.config.lock().unwrap().clone();
+    let url = links::check(url, &config.open_url_schemes).map_err(CommandError::InvalidArg)?;
+
+    if confirm {
+        let id = auth::random_hex(8);
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        state.open_confirms.lock().unwrap().insert(id.clone(), tx);
+        app.emit("open-confirm", links::OpenConfirmEvent { id: id.clone(), url: url.to_string() })?;
+
+        let timeout = Duration::from_millis(config.open_confirm_timeout_ms);
+        let allowed = match tokio::time::timeout(timeout, rx).await {
+            Ok(answer) => answer.unwrap_or(false),
+            Err(_) => {
+                state.open_confirms.lock().unwrap().remove(&id);
+                let _ = app.emit("open-confirm-expired", &id);
+                false
+            }
+        };
+        if !allowed {
+            tracing::info!(%url, "Open was not confirmed");
+            return Ok(false);
+        }
+    }
+
+    links::open(app, &url).map_err(CommandError::Io)?;
+    tracing::info!(%url, "Opened URL");
+    Ok(true)
+}
+
+// Tauri command to answer an `open-confirm` event
+#[tauri::command]
+fn confirm_open(state: State<'_, AppState>, id: String, allow: bool) -> Result<(), CommandError> {
+    let tx = state
+        .open_confirms
+        .lock()
+        .unwrap()
+        .remove(&id)
+        .ok_or_else(|| CommandError::NotFound(format!("No open waiting for confirmation with id {}", id)))?;
+    let _ = tx.send(allow);
+    Ok(())
+}
+
+// Tauri command to get the screen recording permission status
+#[tauri::command]
+fn check_screen_permission(state: State<'_, AppState>) -> permissions::PermissionStatus {
+    permissions::screen_status(state.prefs.lock().unwrap().screen_permission_requested)
+}
+
+// Tauri command to show the system screen recording prompt (macOS only)
+#[tauri::command]
+fn request_screen_permission(app: AppHandle, state: State<'_, AppState>) -> permissions::PermissionStatus {
+    let status = permissions::request_screen();
+    if status != permissions::PermissionStatus::NotApplicable {
+        state.prefs.lock().unwrap().screen_permission_requested = true;
+        save_prefs(&app);
+    }
+    status
+}
+
+// Tauri command to recenter the main window, discarding the saved placement
+#[tauri::command]
+fn reset_window_layout(app: AppHandle) -> Result<(), CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    window.center()?;
+    save_window_layout(&app);
+    Ok(())
+}
+
+// Tauri command to get the main window's current scale factor
+#[tauri::command]
+fn get_window_scale(app: AppHandle) -> Result<f64, CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    Ok(window.scale_factor()?)
+}
+
+#[derive(Clone, Serialize)]
+struct ScaleChangedEvent {
+    label: String,
+    scale_factor: f64,
+    previous: f64,
+}
+
+// The main window landed on a monitor of another scale. The platform keeps
+// its logical size; keep it on screen and tell the UI.
+fn handle_scale_change(app: &AppHandle, scale_factor: f64, new_inner_size: tauri::PhysicalSize<u32>) {
+    let Some(window) = app.get_webview_window("main") else { return };
+    let previous = app.state::<AppState>().window_scale.lock().unwrap().replace(scale_factor).unwrap_or(scale_factor);
+    if let Err(e) = window_layout::keep_on_monitor(&window, new_inner_size) {
+        tracing::error!(error = %e, "Failed to keep window on its monitor");
+    }
+    tracing::info!(previous, scale_factor, "Window scale factor changed");
+    let _ = app.emit("scale-changed", ScaleChangedEvent { label: window.label().to_string(), scale_factor, previous });
+}
+
+// Tauri command to dock the main window, e.g. snap_window("TopRight", None).
+// Without a monitor index the monitor under the cursor is used.
+#[tauri::command]
+fn snap_window(app: AppHandle, position: String, monitor: Option<usize>) -> Result<(), CommandError> {
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+    let position = window_layout::SnapPosition::parse(&position).ok_or_else(|| CommandError::InvalidArg(format!("Unknown position: {}", position)))?;
+    window_layout::snap(&window, position, monitor).map_err(CommandError::InvalidArg)?;
+    save_window_layout(&app);
+    Ok(())
+}
+
+// Save the main window's position and size to the app config dir
+fn save_window_layout(app: &AppHandle) {
+    save_layout_of(app, "main");
+}
+
+fn save_layout_of(app: &AppHandle, label: &str) {
+    let Some(window) = app.get_webview_window(label) else { return };
+    // A minimized window reports a bogus position on some platforms
+    if window.is_minimized().unwrap_or(false) {
+        return;
+    }
+    let (Ok(dir), Ok(layout), Ok(scale)) = (app.path().app_config_dir(), window_layout::capture(&window), window.scale_factor()) else {
+        return;
+    };
+    if let Err(e) = window_layout::save(&dir.join(window_layout::layout_file(label)), &layout, scale) {
+        tracing::error!(%label, error = %e, "Failed to save window layout");
+    }
+}
+
+// Tauri command to open another overlay window showing `url`, a page of the
+// app such as "index.html?panel=tools". The label must start with "overlay-",
+// e.g. "overlay-tools". It gets the glass and its own saved
+// layout, and can be made the agent event target with set_event_target.
+// Async because creating a window from a sync command deadlocks on Windows.
+#[tauri::command]
+async fn create_overlay_window(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    label: String,
+    url: String,
+    width: f64,
+    height: f64,
+) -> Result<(), CommandError> {
+    if !overlay_windows::valid_label(&label) {
+        return Err(CommandError::InvalidArg(format!(
+            "Invalid window label {:?}: expected {}[A-Za-z0-9_-]+",
+            label,
+            overlay_windows::LABEL_PREFIX
+        )));
+    }
+    if app.get_webview_window(&label).is_some() || state.overlay_windows.lock().unwrap().contains_key(&label) {
+        return Err(CommandError::InvalidArg(format!("A window labelled {} already exists", label)));
+    }
+    let range = overlay_windows::MIN_SIZE..=overlay_windows::MAX_SIZE;
+    if !range.contains(&width) || !range.contains(&height) {
+        return Err(CommandError::InvalidArg(format!(
+            "Window size must be {}-{} in each dimension, got {}x{}",
+            overlay_windows::MIN_SIZE,
+            overlay_windows::MAX_SIZE,
+            width,
+            height
+        )));
+    }
+    let page = overlay_windows::page_url(&url).map_err(CommandError::InvalidArg)?;
+
+    // Same look as the main window in tauri.conf.json
+    let window = tauri::WebviewWindowBuilder::new(&app, &label, page)
+        .title(&label)
+        .inner_size(width, height)
+        .resizable(false)
+        .decorations(false)
+        .transparent(true)
+        .always_on_top(true)
+        .shadow(false)
+        .visible(false)
+        .build()?;
+    state.overlay_windows.lock().unwrap().insert(label.clone(), url);
+
+    let saved = window_layout::load(&app.path().app_config_dir()?.join(window_layout::layout_file(&label)));
+    // The native effects have to be applied on the main thread
+    let handle = app.clone();
+    app.run_on_main_thread(move || {
+        if let Err(e) = window_layout::restore(&window, saved) {
+            tracing::error!(label = window.label(), error = %e, "Failed to restore window layout");
+        }
+        toggle_glass(&handle, &handle.state::<AppState>(), &window, true);
+        if let Err(e) = window.show() {
+            tracing::error!(label = window.label(), error = %e, "Failed to show overlay window");
+        }
+    })?;
+    tracing::info!(%label, "Created overlay window");
+    let _ = app.emit("overlay-windows-changed", overlay_windows::OverlayWindowEvent { label, open: true });
+    Ok(())
+}
+
+// Tauri command to close a window made by create_overlay_window, saving its layout
+#[tauri::command]
+fn close_overlay_window(app: AppHandle, state: State<'_, AppState>, label: String) -> Result<(), CommandError> {
+    if !state.overlay_windows.lock().unwrap().contains_key(&label) {
+        return Err(CommandError::NotFound(format!("No overlay window labelled {}", label)));
+    }
+    let window = app.get_webview_window(&label).ok_or_else(|| CommandError::window_not_found(&label))?;
+    save_layout_of(&app, &label);
+    window.close()?;
+    Ok(())
+}
+
+// Events of windows other than main; only created overlay windows are handled
+fn handle_overlay_window_event(app: &AppHandle, label: &str, event: &WindowEvent) {
+    let state = app.state::<AppState>();
+    if !state.overlay_windows.lock().unwrap().contains_key(label) {
+        return;
+    }
+    match event {
+        WindowEvent::CloseRequested { .. } => save_layout_of(app, label),
+        WindowEvent::Destroyed => {
+            state.overlay_windows.lock().unwrap().remove(label);
+            state.glass_windows.lock().unwrap().remove(label);
+            tracing::info!(%label, "Closed overlay window");
+            let _ = app.emit("overlay-windows-changed", overlay_windows::OverlayWindowEvent { label: label.to_string(), open: false });
+        }
+        _ => {}
+    }
+}
+
+// Save the layout once moves/resizes settle instead of on every event
+fn schedule_layout_save(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let mut task = state.layout_save_task.lock().unwrap();
+    if let Some(task) = task.take() {
+        task.abort();
+    }
+    let app = app.clone();
+    *task = Some(tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(LAYOUT_SAVE_DELAY).await;
+        save_window_layout(&app);
+    }));
+}
+
+// Write the current preferences to the app config dir
+fn save_prefs(app: &AppHandle) {
+    let Ok(dir) = app.path().app_config_dir() else { return };
+    let prefs = app.state::<AppState>().prefs.lock().unwrap().clone();
+    if let Err(e) = prefs::save(&dir.join(prefs::PREFS_FILE), &prefs) {
+        tracing::error!(error = %e, "Failed to save preferences");
+    }
+    save_app_snapshot(app);
+}
+
+// Gather the UI state that survives a restart into one snapshot
+fn app_snapshot(app: &AppHandle) -> app_snapshot::AppStateSnapshot {
+    let state = app.app_state();
+    let window_layout = app.get_webview_window("main").and_then(|window| {
+        // A minimized window reports a bogus position on some platforms
+        if window.is_minimized().unwrap_or(false) {
+            return None;
+        }
+        let layout = window_layout::capture(&window).ok()?;
+        Some(window_layout::SavedLayout { layout, scale: window.scale_factor().ok() })
+    });
+    app_snapshot::AppStateSnapshot {
+        version: app_snapshot::SNAPSHOT_VERSION,
+        preferences: state.prefs.lock().unwrap().clone(),
+        glass: app_snapshot::GlassSnapshot::from(&*state.glass.lock().unwrap()),
+        theme: *state.theme_override.lock().unwrap(),
+        window_layout,
+        overlay_mode: state.overlay_mode.lock().unwrap().mode,
+        event_target: state.event_target.lock().unwrap().clone(),
+    }
+}
+
+// Write the snapshot to the app config dir if it changed since the last write
+fn save_app_snapshot(app: &AppHandle) {
+    let Ok(dir) = app.path().app_config_dir() else { return };
+    let json = match serde_json::to_string_pretty(&app_snapshot(app)) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to serialize app state snapshot");
+            return;
+        }
+    };
+    let state = app.state::<AppState>();
+    let mut saved = state.saved_snapshot.lock().unwrap();
+    if saved.as_deref() == Some(json.as_str()) {
+        return;
+    }
+    match app_snapshot::save(&dir.join(app_snapshot::SNAPSHOT_FILE), &json) {
+        Ok(()) => *saved = Some(json),
+        Err(e) => tracing::error!(error = %e, "Failed to save app state snapshot"),
+    }
+}
+
+// Save the snapshot now and then to catch changes that don't go through save_prefs
+async fn autosave_app_snapshot(app: AppHandle) {
+    let mut tick = tokio::time::interval(app_snapshot::SAVE_INTERVAL);
+    tick.tick().await;
+    loop {
+        tick.tick().await;
+        save_app_snapshot(&app);
+    }
+}
+
+// Tauri command to get the UI state that isn't tied to a connection:
+// preferences, glass, theme, window layout, overlay mode and event target.
+// The auth token is not included.
+#[tauri::command]
+fn export_app_state(app: AppHandle) -> app_snapshot::AppStateSnapshot {
+    app_snapshot(&app)
+}
+
+// Tauri command to apply a snapshot from export_app_state and save it
+#[tauri::command]
+fn import_app_state(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    snapshot: app_snapshot::AppStateSnapshot,
+) -> Result<(), CommandError> {
+    // Everything that can be checked up front is, so a bad snapshot changes nothing
+    if snapshot.version > app_snapshot::SNAPSHOT_VERSION {
+        return Err(CommandError::InvalidArg(format!(
+            "Snapshot version {} is newer than this app supports ({})",
+            snapshot.version,
+            app_snapshot::SNAPSHOT_VERSION
+        )));
+    }
+    let mut prefs = snapshot.preferences;
+    check_transcript_size(prefs.transcript_logging.max_size_mb)?;
+    if prefs.animation.fps == 0 || prefs.animation.fps > animation::MAX_FPS {
+        return Err(CommandError::InvalidArg(format!("Invalid animation frame rate: {}", prefs.animation.fps)));
+    }
+    if !(0.0..=1.0).contains(&prefs.text_scrim.min_opacity) {
+        return Err(CommandError::InvalidArg(format!("Invalid scrim opacity: {}", prefs.text_scrim.min_opacity)));
+    }
+    if !prefs.base_opacity.is_finite() {
+        return Err(CommandError::InvalidArg(format!("Invalid opacity: {}", prefs.base_opacity)));
+    }
+    let accelerator = prefs.toggle_hotkey.clone().unwrap_or_else(|| DEFAULT_TOGGLE_HOTKEY.to_string());
+    let shortcut: Shortcut = accelerator
+        .parse()
+        .map_err(|e| CommandError::InvalidArg(format!("Invalid hotkey {}: {}", accelerator, e)))?;
+    let window = app.get_webview_window("main").ok_or_else(|| CommandError::window_not_found("main"))?;
+
+    snapshot.glass.apply_to(&mut state.glass.lock().unwrap());
+    *state.event_target.lock().unwrap() = snapshot.event_target;
+    // The layout carries the window size, so the mode switches without animating
+    {
+        let mut overlay = state.overlay_mode.lock().unwrap();
+        overlay.mode = snapshot.overlay_mode;
+        overlay.expanded_size = None;
+    }
+    let _ = app.emit(
+        "overlay-mode-changed",
+        overlay_mode::OverlayModeEvent { mode: snapshot.overlay_mode, automatic: false },
+    );
+
+    // A setting that fails to apply keeps its current value, so the saved
+    // preferences still describe what is in effect
+    let previous = state.prefs.lock().unwrap().clone();
+    let mut failed = Vec::new();
+    if *state.toggle_hotkey.lock().unwrap() != Some(shortcut) {
+        if let Err(e) = register_toggle_hotkey(&app, &accelerator) {
+            failed.push(format!("hotkey: {}", e));
+            prefs.toggle_hotkey = previous.toggle_hotkey.clone();
+        }
+    }
+    match focus::apply(&window, prefs.focus_policy) {
+        Ok(()) => {
+            let _ = app.emit("focus-policy-changed", prefs.focus_policy);
+        }
+        Err(e) => {
+            failed.push(format!("focus policy: {}", e));
+            prefs.focus_policy = previous.focus_policy;
+        }
+    }
+    if let Err(e) = apply_click_through(&app, &window, prefs.click_through) {
+        failed.push(format!("click-through: {}", e));
+        prefs.click_through = previous.click_through;
+    }
+    if let Err(e) = apply_transcript_logging(&app, &state, prefs.transcript_logging) {
+        failed.push(format!("transcript logging: {}", e));
+        prefs.transcript_logging = previous.transcript_logging;
+    }
+    prefs.idle_dimming.dimmed_opacity = prefs.idle_dimming.dimmed_opacity.clamp(0.1, 1.0);
+    prefs.base_opacity = prefs.base_opacity.clamp(liquid_glass::MIN_BASE_OPACITY, 1.0);
+    // Left for apply_dnd to flip, so held-back messages are replayed
+    let dnd = std::mem::replace(&mut prefs.dnd, previous.dnd);
+    let base_opacity = prefs.base_opacity;
+    *state.prefs.lock().unwrap() = prefs;
+
+    if dnd != previous.dnd {
+        apply_dnd(&app, dnd, true);
+    }
+    state.idle.lock().unwrap().set_now(&window, base_opacity);
+    note_activity(&app);
+    if let Some(saved) = snapshot.window_layout {
+        if let Err(e) = window_layout::restore(&window, Some(saved)) {
+            failed.push(format!("window layout: {}", e));
+        }
+    }
+    // Re-applies the glass, and with it the window level
+    if let Err(e) = pin_theme(&app, snapshot.theme) {
+        failed.push(format!("theme: {}", e));
+    }
+    emit_scrim(&app);
+
+    save_prefs(&app);
+    save_window_layout(&app);
+    if !failed.is_empty() {
+        tracing::warn!(?failed, "Imported app state snapshot in part");
+        return Err(CommandError::Io(format!("Imported app state except {}", failed.join("; "))));
+    }
+    tracing::info!("Imported app state snapshot");
+    Ok(())
+}
+
+// Re-apply the glass for a theme and let the webview swap its CSS
+fn apply_theme(app: &AppHandle, theme: Theme) {
+    app.state::<AppState>().glass.lock().unwrap().dark = theme == Theme::Dark;
+    reapply_glass(app);
+    let _ = app.emit("theme-changed", theme.to_string());
+    emit_scrim(app);
+}
+
+// Follow an OS theme change unless the user pinned a theme
+fn follow_system_theme(app: &AppHandle, theme: Theme) {
+    if app.state::<AppState>().theme_override.lock().unwrap().is_some() {
+        return;
+    }
+    apply_theme(app, theme);
+}
+
+// Emit a typed agent-status event, dropping repeats and collapsing flapping
+// connects/disconnects (see status.rs)
+fn emit_status(app: &impl EventSink, event: ConnectionEvent) {
+    let state = app.app_state();
+    let transition = matches!(
+        event,
+        ConnectionEvent::Connected { .. } | ConnectionEvent::Resumed { .. } | ConnectionEvent::Disconnected { .. }
+    );
+    let verdict = state.status_filter.lock().unwrap().filter(&event, transition, Instant::now());
+    match verdict {
+        status::Verdict::Emit => send_status(app, event),
+        status::Verdict::Drop => tracing::trace!(?event, "Dropped repeated status"),
+        status::Verdict::Hold => {
+            let mut task = state.status_flush_task.lock().unwrap();
+            if let Some(task) = task.take() {
+                task.abort();
+            }
+            let app = app.clone();
+            *task = Some(tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(status::FLAP_WINDOW).await;
+                let flushed = app.app_state().status_filter.lock().unwrap().flush(Instant::now());
+                if let Some((event, transitions)) = flushed {
+                    tracing::warn!(transitions, "Agent connection is unstable");
+                    send_status(&app, ConnectionEvent::Unstable { transitions });
+                    send_status(&app, event);
+                }
+            }));
+        }
+    }
+}
+
+fn send_status(app: &impl EventSink, event: ConnectionEvent) {
+    emit_agent_event(app, "agent-status", StatusEvent { version: STATUS_EVENT_VERSION, event });
+}
+
+// Resolve once the shutdown signal is raised
+async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stop| *stop).await;
+}
+
+// Cut a close reason to the 123 bytes a close frame can carry
+fn close_reason_prefix(reason: &str) -> &str {
+    let mut end = reason.len().min(123);
+    while !reason.is_char_boundary(end) {
+        end -= 1;
+    }
+    &reason[..end]
+}
+
+// Send a close frame and drop the writer, waiting at most CLOSE_TIMEOUT
+async fn close_writer(mut writer: WsSink, code: CloseCode, reason: &str) {
+    let frame = CloseFrame { code, reason: reason.to_string().into() };
+    let _ = tokio::time::timeout(CLOSE_TIMEOUT, writer.send(Message::Close(Some(frame)))).await;
+}
+
+// Stop the accept loop and close every agent connection with "session-ended"
+async fn shutdown_ws_server(state: &AppState) {
+    state.shutdown.trigger();
+    state.stability.lock().unwrap().reset(Instant::now());
+    if let Some(path) = state.ws_socket_path.lock().unwrap().take() {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let mut connections = state.connections.lock().await;
+    let ids: Vec<u64> = connections.entries.keys().copied().collect();
+    for id in ids {
+        if let Some(writer) = connections.remove(id) {
+            close_writer(writer, CloseCode::Away, "session-ended").await;
+        }
+    }
+}
+
+// Decode a binary frame from the agent and forward it to the webview
+async fn handle_binary_frame(app: &impl EventSink, data: &[u8]) {
+    let frame = match binary::decode(data) {
+        Ok(frame) => frame,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to decode binary frame");
+            emit_agent_error(app, format!("Invalid binary frame: {}", e));
+            return;
+        }
+    };
+
+    // Payloads are stored under the app's directories
+    let Some(handle) = app.app_handle() else { return };
+    if frame.kind == binary::BinaryKind::Screenshot {
+        handle_screenshot(handle, frame.payload).await;
+        return;
+    }
+
+    let mut event = BinaryEvent {
+        kind: frame.kind.as_str().to_string(),
+        metadata: frame.metadata,
+        size: frame.payload.len(),
+        data: None,
+        path: None,
+    };
+
+    if frame.payload.len() <= INLINE_BINARY_LIMIT {
+        event.data = Some(base64::engine::general_purpose::STANDARD.encode(&frame.payload));
+    } else {
+        match write_temp_blob(handle, frame.kind, &frame.payload).await {
+            Ok(path) => event.path = Some(path),
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to write binary payload");
+                emit_agent_error(app, format!("Failed to store binary payload: {}", e));
+                return;
+            }
+        }
+    }
+
+    emit_agent_event(app, "agent-binary", event);
+}
+
+// Store a screenshot and emit agent-screenshot with a thumbnail and its path
+async fn handle_screenshot(app: &AppHandle, payload: Vec<u8>) {
+    let Ok(dir) = app.path().app_data_dir().map(|dir| dir.join(screenshot::SCREENSHOT_DIR)) else {
+        return;
+    };
+    let state = app.state::<AppState>();
+    let config = state.config.lock().unwrap().clone();
+    let previous = *state.last_screenshot_hash.lock().unwrap();
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        screenshot::cleanup(&dir, Duration::from_secs(config.screenshot_ttl_secs));
+        screenshot::process(&dir, &payload, config.thumbnail_max_px, previous, config.screenshot_dedup_distance)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(screenshot::Processed::Shown(event, hash))) => {
+            *state.last_screenshot_hash.lock().unwrap() = Some(hash);
+            emit_agent_event(app, "agent-screenshot", event);
+        }
+        Ok(Ok(screenshot::Processed::Duplicate)) => {
+            tracing::trace!("Suppressed near-duplicate screenshot");
+            state.stats.record_suppressed_frame();
+        }
+        Ok(Err(e)) => {
+            tracing::warn!(error = %e, "Failed to process screenshot");
+            emit_agent_error(app, format!("Invalid screenshot: {}", e));
+        }
+        Err(e) => tracing::error!(error = %e, "Screenshot task failed"),
+    }
+}
+
+// Write a large binary payload to the temp dir and return its path
+async fn write_temp_blob(app: &AppHandle, kind: binary::BinaryKind, payload: &[u8]) -> std::io::Result<String> {
+    let dir = app
+        .path()
+        .temp_dir()
+        .map_err(std::io::Error::other)?
+        .join("jarvis-overlay");
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let name = format!("{}-{}-{:08x}.bin", kind.as_str(), chrono::Utc::now().timestamp_millis(), rand::random::<u32>());
+    let path = dir.join(name);
+    tokio::fs::write(&path, payload).await?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+// Cancel the expiry timer of message `id`, if any, and start a new one
+// when the message replacing it is ephemeral too
+fn supersede_message(app: &impl EventSink, id: &str, ttl_ms: Option<u64>) {
+    let state = app.app_state();
+    let mut tasks = state.expiry_tasks.lock().unwrap();
+    if let Some(task) = tasks.remove(id) {
+        task.abort();
+    }
+    let Some(ttl_ms) = ttl_ms else { return };
+    let (app, message_id) = (app.clone(), id.to_string());
+    let task = tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(ttl_ms)).await;
+        app.app_state().expiry_tasks.lock().unwrap().remove(&message_id);
+        emit_agent_event(&app, "agent-message-expire", MessageExpireEvent { message_id });
+    });
+    tasks.insert(id.to_string(), task);
+}
+
+// Route a text frame from the agent by its shape
+fn handle_text_frame(
+    app: &impl EventSink,
+    id: u64,
+    text: &str,
+    deltas: &mut delta::DeltaBuffer,
+    config: &config::ServerConfig,
+) {
+    // Try to parse as pending queue update first
+    match serde_json::from_str::<PendingQueueMessage>(text) {
+        Ok(queue_msg) if queue_msg.msg_type == "pending_queue" => {
+            emit_agent_event(app, "pending-messages", queue_msg.messages);
+            return;
+        }
+        _ => {}
+    }
+
+    match serde_json::from_str::<AckMessage>(text) {
+        Ok(ack) if ack.msg_type == "ack" => {
+            handle_ack(app, ack.id);
+            return;
+        }
+        _ => {}
+    }
+
+    match serde_json::from_str::<EchoMessage>(text) {
+        Ok(reply) if reply.msg_type == "echo_reply" => {
+            match app.app_state().echoes.lock().unwrap().remove(&reply.nonce) {
+                Some(tx) => {
+                    let _ = tx.send(());
+                }
+                None => tracing::debug!(id, nonce = %reply.nonce, "Echo reply for no pending ping"),
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    match serde_json::from_str::<StateMessage>(text) {
+        Ok(state_msg) if state_msg.msg_type == "state" => {
+            if state_msg.value == AgentActivity::Idle {
+                track_turn(app, |turns, now| turns.finish(turns::TurnOutcome::Completed, now));
+            }
+            set_agent_activity(app, state_msg.value);
+            return;
+        }
+        _ => {}
+    }
+
+    match serde_json::from_str::<AttentionMessage>(text) {
+        Ok(attention) if attention.msg_type == "attention" => {
+            let Some(handle) = app.app_handle() else { return };
+            if let Some(window) = handle.get_webview_window("main") {
+                let policy = app.app_state().prefs.lock().unwrap().focus_policy;
+                if let Err(e) = focus::on_attention(&window, policy) {
+                    tracing::error!(error = %e, "Failed to focus main window");
+                }
+            }
+            pulse_main_window(handle, attention.times);
+            return;
+        }
+        _ => {}
+    }
+
+    match serde_json::from_str::<OpenUrlMessage>(text) {
+        Ok(open) if open.msg_type == "open_url" => {
+            // Always confirmed by the user when the agent asks
+            let Some(app) = app.app_handle().cloned() else { return };
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = open_url_checked(&app, &open.url, true).await {
+                    tracing::warn!(id, url = %open.url, "Agent open_url failed: {}", e);
+                }
+            });
+            return;
+        }
+        _ => {}
+    }
 
-// Pending queue update message
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct PendingQueueMessage {
-    #[serde(rename = "type")]
-    msg_type: String,
-    messages: Vec<PendingMessage>,
-}
+    match serde_json::from_str::<tool_calls::ToolCallMessage>(text) {
+        Ok(call) if call.msg_type == "tool_call" => {
+            let update = app.app_state().tool_calls.lock().unwrap().start(id, call);
+            emit_tool_call(app, deltas, update);
+            return;
+        }
+        _ => {}
+    }
 
-// Message from UI to Agent
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct UiMessage {
-    #[serde(rename = "type")]
-    msg_type: String,  // "user_input"
-    content: String,
-}
+    match serde_json::from_str::<tool_calls::ToolCallUpdateMessage>(text) {
+        Ok(update) if update.msg_type == "tool_call_update" => {
+            let result = app.app_state().tool_calls.lock().unwrap().update(update);
+            match result {
+                Ok(update) => emit_tool_call(app, deltas, update),
+                Err(e) => tracing::warn!(id, "Ignoring tool call update: {}", e),
+            }
+            return;
+        }
+        _ => {}
+    }
 
-// Tauri command to send message to agent
-#[tauri::command]
-async fn send_to_agent(state: State<'_, AppState>, content: String) -> Result<bool, String> {
-    let mut writer_guard = state.ws_writer.lock().await;
+    // Streaming deltas are coalesced before reaching the webview
+    match serde_json::from_str::<delta::DeltaMessage>(text) {
+        Ok(delta_msg) if delta_msg.msg_type == "delta" => {
+            let (message_id, done) = (delta_msg.message_id.clone(), delta_msg.done);
+            track_turn(app, |turns, now| {
+                turns.output(now);
+                done.then(|| turns.finish(turns::TurnOutcome::Completed, now)).flatten()
+            });
+            if let Some(event) = deltas.push(delta_msg) {
+                emit_delta(app, event);
+            }
+            if done {
+                supersede_message(app, &message_id, None);
+            }
+            if let Some(text) = deltas.take_completed().filter(|text| !text.trim().is_empty()) {
+                *app.app_state().last_streamed.lock().unwrap() = Some(text);
+            }
+            return;
+        }
+        _ => {}
+    }
 
-    if let Some(writer) = writer_guard.as_mut() {
-        let msg = UiMessage {
-            msg_type: "user_input".to_string(),
-            content,
-        };
-        let json = serde_json::to_string(&msg).map_err(|e| e.to_string())?;
+    // Try to parse as agent message
+    match serde_json::from_str::<AgentMessage>(text) {
+        Ok(mut agent_msg) => {
+            match agent_msg.role.as_str() {
+                "assistant" => track_turn(app, |turns, now| {
+                    turns.output(now);
+                    None
+                }),
+                "error" => track_turn(app, |turns, now| turns.finish(turns::TurnOutcome::Failed, now)),
+                _ => {}
+            }
+            if agent_msg.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty()) {
+                // Tool call events point back at the message by id
+                agent_msg.id.get_or_insert_with(|| auth::random_hex(8));
+            }
+            if agent_msg.ttl_ms.is_some() {
+                // The webview needs an id to know which message expired
+                let id = agent_msg.id.get_or_insert_with(|| auth::random_hex(8)).clone();
+                supersede_message(app, &id, agent_msg.ttl_ms);
+            } else {
+                if let Some(id) = &agent_msg.id {
+                    supersede_message(app, id, None);
+                }
+                record_history(app, &agent_msg, config.history_size);
+            }
+            emit_agent_message(app, agent_msg);
+        }
+        Err(e) => report_parse_error(app, id, text, &e),
+    }
+}
 
-        writer.send(Message::Text(json)).await.map_err(|e| e.to_string())?;
-        Ok(true)
-    } else {
-        Err("Not connected to agent".to_string())
+// Send the next keepalive ping; returns false once too many went unanswered
+async fn send_ping(
+    app: &impl EventSink,
+    connections: &WsConnections,
+    id: u64,
+    heartbeat: &mut Heartbeat,
+    config: &config::ServerConfig,
+) -> bool {
+    let payload = heartbeat.next_ping();
+    if heartbeat.missed >= config.ping_timeout_missed {
+        return false;
+    }
+    if heartbeat.missed == config.degraded_after_missed {
+        emit_status(app, ConnectionEvent::Degraded { id, missed_pings: heartbeat.missed });
     }
+
+    let _ = check_send(app, connections.lock().await.send(id, Message::Ping(payload), config).await);
+    true
 }
 
-// Tauri command to stop the agent
-#[tauri::command]
-async fn stop_agent(state: State<'_, AppState>) -> Result<bool, String> {
-    let mut writer_guard = state.ws_writer.lock().await;
+// Wait for the auth frame and check it against the current token and the
+// supported protocol range
+async fn authenticate(app: &impl EventSink, read: &mut WsSource) -> Result<AuthMessage, AuthError> {
+    let text = match tokio::time::timeout(AUTH_TIMEOUT, read.next()).await {
+        Ok(Some(Ok(Message::Text(text)))) => text,
+        _ => return Err(AuthError::Invalid),
+    };
+    let auth_msg = serde_json::from_str::<AuthMessage>(&text).map_err(|_| AuthError::Invalid)?;
 
-    if let Some(writer) = writer_guard.as_mut() {
-        let msg = UiMessage {
-            msg_type: "stop_agent".to_string(),
-            content: String::new(),
-        };
-        let json = serde_json::to_string(&msg).map_err(|e| e.to_string())?;
+    let state = app.app_state();
+    let token = state.ws_token.lock().unwrap();
+    let token_ok = auth_msg.msg_type == "auth"
+        && token.as_ref().is_some_and(|t| t.accepts(&auth_msg.token, Instant::now()));
+    if !token_ok {
+        return Err(AuthError::Invalid);
+    }
 
-        writer.send(Message::Text(json)).await.map_err(|e| e.to_string())?;
-        Ok(true)
-    } else {
-        Err("Not connected to agent".to_string())
+    if !protocol_supported(auth_msg.protocol_version) {
+        return Err(AuthError::UnsupportedVersion(auth_msg.protocol_version));
     }
+    Ok(auth_msg)
 }
 
-// Tauri command to update pending messages queue
-#[tauri::command]
-async fn update_pending_queue(app: AppHandle, messages: Vec<PendingMessage>) -> Result<(), String> {
-    let _ = app.emit("pending-messages", messages);
-    Ok(())
+// Wait for the hello frame and check it agrees with the auth frame
+async fn read_hello(read: &mut WsSource, protocol_version: u32) -> Result<HelloMessage, String> {
+    let text = match tokio::time::timeout(AUTH_TIMEOUT, read.next()).await {
+        Ok(Some(Ok(Message::Text(text)))) => text,
+        Ok(_) => return Err("expected a hello frame".to_string()),
+        Err(_) => return Err("no hello frame in time".to_string()),
+    };
+    let hello = serde_json::from_str::<HelloMessage>(&text).map_err(|e| format!("malformed hello: {}", e))?;
+    if hello.msg_type != "hello" {
+        return Err(format!("expected a hello frame, got {}", hello.msg_type));
+    }
+    if hello.agent_name.trim().is_empty() || hello.agent_version.trim().is_empty() {
+        return Err("hello needs agent_name and agent_version".to_string());
+    }
+    if hello.protocol_version != protocol_version {
+        return Err(format!("hello protocol_version {} differs from auth", hello.protocol_version));
+    }
+    Ok(hello)
 }
 
-async fn handle_connection(stream: TcpStream, app: AppHandle, ws_writer: WsWriter) {
-    let ws_stream = match accept_async(stream).await {
+#[tracing::instrument(name = "connection", skip_all, fields(id))]
+async fn handle_connection(
+    stream: Box<dyn AgentStream>,
+    remote_addr: Option<String>,
+    app: impl EventSink,
+    connections: WsConnections,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let config = app.app_state().config.lock().unwrap().clone();
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(config.max_message_size),
+        max_frame_size: Some(config.max_message_size),
+        ..Default::default()
+    };
+
+    let ws_stream = match accept_async_with_config(stream, Some(ws_config)).await {
         Ok(ws) => ws,
         Err(e) => {
-            eprintln!("WebSocket handshake failed: {}", e);
+            tracing::warn!(error = %e, "WebSocket handshake failed");
             return;
         }
     };
 
     let (write, mut read) = ws_stream.split();
 
+    // Held until this function returns, freeing the slot for the next agent
+    let limit = app.app_state().connection_limit.clone();
+    let _slot = match limit.try_acquire() {
+        Some(slot) => slot,
+        None if config.connection_limit_mode == config::LimitMode::Queue => {
+            tracing::info!(max = limit.max(), "Connection limit reached, queueing connection");
+            tokio::select! {
+                slot = limit.acquire() => slot,
+                _ = stopped(&mut shutdown) => return,
+            }
+        }
+        None => {
+            emit_agent_warning(&app, AgentWarning::ConnectionLimit { max: limit.max() });
+            close_writer(write, CloseCode::Again, "too many connections").await;
+            return;
+        }
+    };
+
+    // Reject before installing the writer unless the first frame authenticates
+    let auth_msg = match authenticate(&app, &mut read).await {
+        Ok(auth_msg) => auth_msg,
+        Err(AuthError::Invalid) => {
+            tracing::warn!("WebSocket auth failed, closing connection");
+            close_writer(write, CloseCode::Policy, "authentication failed").await;
+            return;
+        }
+        Err(AuthError::UnsupportedVersion(version)) => {
+            let message = format!(
+                "Agent protocol version {} is not supported (overlay supports {}-{})",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            );
+            tracing::warn!(version, "{}", message);
+            emit_agent_error(&app, message);
+            close_writer(write, CloseCode::Protocol, "unsupported protocol version").await;
+            return;
+        }
+    };
+
+    let hello = if auth_msg.protocol_version >= HELLO_PROTOCOL_VERSION {
+        match read_hello(&mut read, auth_msg.protocol_version).await {
+            Ok(hello) => Some(hello),
+            Err(reason) => {
+                tracing::warn!(%reason, "Agent handshake failed, closing connection");
+                emit_agent_error(&app, format!("Agent handshake failed: {}", reason));
+                close_writer(write, CloseCode::Protocol, close_reason_prefix(&reason)).await;
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
     // Store the writer for sending messages back to agent
-    {
-        let mut writer_guard = ws_writer.lock().await;
-        *writer_guard = Some(write);
+    let outbound = rate_limit::TokenBucket::new(config.rate_limit_per_sec, config.rate_limit_burst);
+    let dropped = Arc::new(DropSignal::default());
+    let last_activity = Arc::new(std::sync::Mutex::new(None));
+    let last_frame = Arc::new(std::sync::Mutex::new(Instant::now()));
+    let format = match auth_msg.format.as_deref() {
+        None => msgpack::WireFormat::Json,
+        Some(name) => msgpack::WireFormat::parse(name).unwrap_or_else(|| {
+            tracing::warn!(format = name, "Unknown wire format requested, using JSON");
+            msgpack::WireFormat::Json
+        }),
+    };
+    let mut meta = ConnectionMeta {
+        remote_addr,
+        connected_at: chrono::Utc::now().to_rfc3339(),
+        agent_name: auth_msg.agent_name,
+        agent_version: auth_msg.agent_version,
+        protocol_version: auth_msg.protocol_version,
+        model: None,
+        capabilities: Vec::new(),
+        session_id: auth_msg.session_id.clone(),
+        format,
+        last_activity: last_activity.clone(),
+    };
+    if let Some(hello) = &hello {
+        meta.agent_name = Some(hello.agent_name.clone());
+        meta.agent_version = Some(hello.agent_version.clone());
+        meta.model = hello.model.clone();
+        meta.capabilities = hello.capabilities.clone();
+    }
+    let id = connections.lock().await.insert(write, outbound, dropped.clone(), last_frame.clone(), meta);
+    app.app_state().stats.connected();
+    app.app_state().stability.lock().unwrap().connected(RESUME_WINDOW, Instant::now());
+    tracing::Span::current().record("id", id);
+    tracing::info!("Agent connected");
+    if let Some(hello) = hello {
+        emit_agent_event(
+            &app,
+            "agent-hello",
+            AgentHello {
+                id,
+                agent_name: hello.agent_name,
+                agent_version: hello.agent_version,
+                protocol_version: hello.protocol_version,
+                model: hello.model,
+                capabilities: hello.capabilities,
+            },
+        );
     }
 
-    // Notify UI that agent connected
-    let _ = app.emit("agent-status", "Agent connected");
+    let agent_session_id = auth_msg.session_id.clone();
+    let resumed = agent_session_id
+        .as_deref()
+        .and_then(|session_id| resume_agent_session(&app, session_id, id, &config));
 
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(msg) => {
-                if msg.is_text() {
-                    let text = msg.to_text().unwrap_or("");
-                    
-                    // Try to parse as pending queue update first
-                    match serde_json::from_str::<PendingQueueMessage>(text) {
-                        Ok(queue_msg) if queue_msg.msg_type == "pending_queue" => {
-                            let _ = app.emit("pending-messages", queue_msg.messages);
-                            continue;
-                        }
-                        _ => {}
+    let session = {
+        let state = app.app_state();
+        let session = state.session.lock().unwrap();
+        SessionMessage {
+            msg_type: "session",
+            session_id: session.id.clone(),
+            protocol_version: PROTOCOL_VERSION,
+            server_time: chrono::Utc::now().to_rfc3339(),
+            is_resume: resumed.is_some() || session.last_disconnect.is_some_and(|at| at.elapsed() < RESUME_WINDOW),
+            format,
+        }
+    };
+    if let Ok(json) = serde_json::to_string(&session) {
+        let _ = check_send(&app, connections.lock().await.send(id, Message::Text(json), &config).await);
+    }
+
+    // Notify UI that agent connected, or picked its session back up
+    match (resumed, auth_msg.session_id) {
+        (Some(frames), Some(session_id)) => {
+            let resent = frames.len();
+            let mut connections = connections.lock().await;
+            for frame in frames {
+                let bytes = frame.len();
+                if check_send(&app, connections.send(id, Message::Text(frame), &config).await).is_ok() {
+                    app.app_state().stats.record_out(bytes);
+                }
+            }
+            drop(connections);
+            tracing::info!(%session_id, resent, "Agent resumed session");
+            emit_status(&app, ConnectionEvent::Resumed { id, session_id, resent });
+        }
+        _ => emit_status(&app, ConnectionEvent::Connected { id }),
+    }
+    let mut reason = String::from("connection closed");
+    // Close code and reason of the close frame either side sent
+    let mut close: Option<(u16, String)> = None;
+
+    let mut inbound = rate_limit::TokenBucket::new(config.rate_limit_per_sec, config.rate_limit_burst);
+    // A frame over the rate limit, held until a token frees up
+    let mut held: Option<(Message, tokio::time::Instant)> = None;
+    let mut deltas = delta::DeltaBuffer::new(Duration::from_millis(config.delta_flush_ms));
+    let mut heartbeat = Heartbeat::default();
+    let ping_interval = Duration::from_millis(config.ping_interval_ms.max(1));
+    let mut ping_tick = tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+    ping_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        // Capped so the deadline can't overflow
+        let idle_timeout = app.app_state().config.lock().unwrap().idle_disconnect_ms.min(u32::MAX as u64);
+        let idle_at = tokio::time::Instant::from_std(*last_frame.lock().unwrap() + Duration::from_millis(idle_timeout));
+        let msg = tokio::select! {
+            msg = read.next(), if held.is_none() => msg,
+            _ = tokio::time::sleep_until(held.as_ref().map_or_else(tokio::time::Instant::now, |(_, at)| *at)),
+                if held.is_some() => {
+                let Some((msg, _)) = held.take() else { continue };
+                Some(Ok(msg))
+            }
+            _ = tokio::time::sleep_until(idle_at), if idle_timeout > 0 => {
+                // A send may have landed while we slept
+                if last_frame.lock().unwrap().elapsed() < Duration::from_millis(idle_timeout) {
+                    continue;
+                }
+                tracing::info!(id, idle_timeout, "Closing idle agent connection");
+                if let Some(writer) = connections.lock().await.remove(id) {
+                    close_writer(writer, CloseCode::Normal, "idle").await;
+                }
+                reason = "idle".to_string();
+                close = Some((CloseCode::Normal.into(), reason.clone()));
+                break;
+            }
+            _ = ping_tick.tick() => {
+                if !send_ping(&app, &connections, id, &mut heartbeat, &config).await {
+                    if let Some(writer) = connections.lock().await.remove(id) {
+                        close_writer(writer, CloseCode::Away, "ping timeout").await;
                     }
-                    
-                    // Try to parse as agent message
-                    match serde_json::from_str::<AgentMessage>(text) {
-                        Ok(agent_msg) => {
-                            let _ = app.emit("agent-message", agent_msg);
-                        }
+                    reason = "ping timeout".to_string();
+                    close = Some((CloseCode::Away.into(), reason.clone()));
+                    break;
+                }
+                continue;
+            }
+            _ = tokio::time::sleep_until(deltas.deadline().unwrap_or_else(tokio::time::Instant::now)),
+                if deltas.deadline().is_some() => {
+                for event in deltas.take_all() {
+                    emit_delta(&app, event);
+                }
+                continue;
+            }
+            _ = dropped.notify.notified() => {
+                reason = dropped.reason().to_string();
+                break;
+            }
+            _ = stopped(&mut shutdown) => {
+                if let Some(writer) = connections.lock().await.remove(id) {
+                    close_writer(writer, CloseCode::Away, "session-ended").await;
+                }
+                reason = "session-ended".to_string();
+                close = Some((CloseCode::Away.into(), reason.clone()));
+                break;
+            }
+        };
+        let Some(msg) = msg else { break };
+        if matches!(&msg, Ok(m) if m.is_text() || m.is_binary() || m.is_ping()) {
+            *last_frame.lock().unwrap() = Instant::now();
+        }
+
+        // Hold back agent frames above the rate limit instead of flooding the
+        // webview. Delaying rather than dropping keeps streamed deltas intact;
+        // the wait happens in the select above so shutdown and pings still run.
+        let msg = match msg {
+            Ok(m) if m.is_text() || m.is_binary() => match inbound.try_take() {
+                Ok(()) => Ok(m),
+                Err(throttled) => {
+                    if throttled.first {
+                        emit_agent_warning(&app, AgentWarning::RateLimited { direction: "inbound" });
+                    }
+                    let wait = throttled.retry_in.min(Duration::from_secs(1));
+                    held = Some((m, tokio::time::Instant::now() + wait));
+                    continue;
+                }
+            },
+            other => other,
+        };
+
+        match msg {
+            Ok(Message::Text(text)) => {
+                app.app_state().stats.record_in(text.len());
+                let now = Some(chrono::Utc::now().to_rfc3339());
+                *last_activity.lock().unwrap() = now.clone();
+                *app.app_state().last_message_at.lock().unwrap() = now;
+                note_activity(&app);
+                handle_text_frame(&app, id, &text, &mut deltas, &config);
+            }
+            Ok(Message::Binary(data)) => {
+                app.app_state().stats.record_in(data.len());
+                let now = Some(chrono::Utc::now().to_rfc3339());
+                *last_activity.lock().unwrap() = now.clone();
+                *app.app_state().last_message_at.lock().unwrap() = now;
+                note_activity(&app);
+                if format == msgpack::WireFormat::Msgpack && msgpack::is_message(&data) {
+                    match msgpack::from_slice::<serde_json::Value>(&data) {
+                        Ok(value) => handle_text_frame(&app, id, &value.to_string(), &mut deltas, &config),
                         Err(e) => {
-                            eprintln!("Failed to parse message: {}", e);
-                            let _ = app.emit("agent-error", format!("Parse error: {}", e));
+                            tracing::warn!(id, error = %e, "Invalid MessagePack frame");
+                            emit_agent_error(&app, format!("Invalid MessagePack frame: {}", e));
                         }
                     }
-                } else if msg.is_close() {
-                    let _ = app.emit("agent-status", "Agent disconnected");
-                    break;
+                } else {
+                    handle_binary_frame(&app, &data).await;
+                }
+            }
+            Ok(Message::Ping(_)) => {
+                // tungstenite queues the Pong reply itself and flushes it on the
+                // next read or write; sending one here would answer twice
+                tracing::trace!(id, "Ping from agent");
+            }
+            Ok(Message::Pong(payload)) => {
+                if let Some((rtt_ms, avg_ms)) = heartbeat.on_pong(&payload) {
+                    if let Some(connection) = connections.lock().await.entries.get_mut(&id) {
+                        connection.latency_ms = Some(avg_ms);
+                    }
+                    emit_agent_event(&app, "agent-latency", LatencyEvent { id, rtt_ms, avg_ms });
+                }
+            }
+            Ok(Message::Close(frame)) => {
+                tracing::debug!(id, ?frame, "Close frame from agent");
+                reason = "closed by agent".to_string();
+                close = frame.map(|frame| (frame.code.into(), frame.reason.into_owned()));
+                break;
+            }
+            Ok(Message::Frame(_)) => {
+                // Raw frames are only produced when writing; never expected on read
+                tracing::warn!(id, "Ignoring unexpected raw frame");
+            }
+            Err(WsError::Utf8) => {
+                // The stream ends after any read error, so the frame can't be
+                // skipped; fail the connection as RFC 6455 asks. Reported apart
+                // from JSON parse failures, which need valid text.
+                tracing::warn!(id, "Closing connection on text frame with invalid UTF-8");
+                emit_agent_error(&app, "Invalid UTF-8 in text frame; connection closed".to_string());
+                if let Some(writer) = connections.lock().await.remove(id) {
+                    close_writer(writer, CloseCode::Invalid, "invalid UTF-8").await;
+                }
+                reason = "invalid UTF-8".to_string();
+                close = Some((CloseCode::Invalid.into(), reason.clone()));
+                break;
+            }
+            Err(WsError::Capacity(e)) => {
+                // Oversized frame: refuse it and close instead of buffering it
+                tracing::warn!(id, error = %e, "Rejected oversized message");
+                emit_agent_error(&app, format!("Message rejected: {}", e));
+                emit_agent_warning(&app, AgentWarning::OversizeFrame { id, limit: config.max_message_size });
+                if let Some(writer) = connections.lock().await.remove(id) {
+                    close_writer(writer, CloseCode::Size, "message too large").await;
                 }
+                reason = "message too large".to_string();
+                close = Some((CloseCode::Size.into(), reason.clone()));
+                break;
             }
             Err(e) => {
-                eprintln!("WebSocket error: {}", e);
-                let _ = app.emit("agent-error", format!("Connection error: {}", e));
+                tracing::warn!(id, error = %e, "WebSocket error");
+                reason = format!("connection error: {}", e);
                 break;
             }
         }
     }
 
+    // Deliver any deltas still buffered
+    for event in deltas.take_all() {
+        emit_delta(&app, event);
+    }
+
     // Clear the writer when disconnected
-    {
-        let mut writer_guard = ws_writer.lock().await;
-        *writer_guard = None;
+    connections.lock().await.remove(id);
+    app.app_state().stats.disconnected();
+    app.app_state().stability.lock().unwrap().disconnected(Instant::now());
+    // An agent with a session may come back for its unacknowledged inputs;
+    // otherwise dropping the senders fails them
+    let resumable = agent_session_id.is_some_and(|session_id| {
+        let state = app.app_state();
+        let mut sessions = state.agent_sessions.lock().unwrap();
+        match sessions.get_mut(&session_id) {
+            Some(session) if session.connection_id == id && config.session_idle_secs > 0 => {
+                session.disconnected_at = Some(Instant::now());
+                true
+            }
+            _ => false,
+        }
+    });
+    if !resumable {
+        app.app_state().in_flight.lock().unwrap().retain(|_, in_flight| in_flight.connection_id != id);
+    }
+    set_agent_activity(&app, AgentActivity::Idle);
+    track_turn(&app, |turns, now| turns.finish(turns::TurnOutcome::Failed, now));
+    let abandoned = app.app_state().tool_calls.lock().unwrap().abandon(id);
+    for update in abandoned {
+        emit_agent_event(&app, "agent-tool-call", update);
+    }
+    app.app_state().session.lock().unwrap().last_disconnect = Some(Instant::now());
+    tracing::info!(%reason, "Agent disconnected");
+    let (code, close_reason) = close.unzip();
+    emit_status(
+        &app,
+        ConnectionEvent::Disconnected {
+            id,
+            reason,
+            code,
+            code_label: code.map(close_code_label),
+            close_reason: close_reason.filter(|r| !r.is_empty()),
+        },
+    );
+}
+
+// Bind the TCP listener, retrying with exponential backoff and jitter while
+// the address is in use, e.g. by a previous instance still shutting down.
+// Other errors won't clear up by waiting and fail at once.
+async fn bind_with_retry(app: &impl EventSink, addr: SocketAddr, config: &config::ServerConfig) -> std::io::Result<TcpListener> {
+    let max_attempts = config.bind_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match TcpListener::bind(&addr).await {
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && attempt < max_attempts => {
+                let base = config.bind_retry_base_ms.saturating_mul(1 << (attempt - 1).min(16));
+                let delay = base + rand::random::<u64>() % (base / 2 + 1);
+                tracing::warn!(%addr, attempt, delay_ms = delay, "Address in use, retrying bind");
+                attempt += 1;
+                emit_status(
+                    app,
+                    ConnectionEvent::Binding { port: addr.port(), attempt, max_attempts, retry_in_ms: delay },
+                );
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+            }
+            result => return result,
+        }
     }
 }
 
-async fn start_ws_server(app: AppHandle, ws_writer: WsWriter) {
-    let addr: SocketAddr = format!("127.0.0.1:{}", WS_PORT).parse().unwrap();
+// Bind the server and spawn its accept loop. Returns the bound port, or 0
+// when listening on a Unix socket.
+async fn start_ws_server(app: AppHandle, port: u16) -> Result<u16, String> {
+    let config = app.state::<AppState>().config.lock().unwrap().clone();
+    let addr = SocketAddr::new(config.bind_address, port);
+    let remote = !addr.ip().is_loopback();
+    if remote {
+        let refusal = if !config.allow_remote {
+            Some(format!("{} is not a loopback address; set allow_remote to bind it", addr.ip()))
+        } else if app.state::<AppState>().ws_token.lock().unwrap().is_none() {
+            Some("No auth token; refusing to accept remote agents without one".to_string())
+        } else {
+            None
+        };
+        if let Some(reason) = refusal {
+            tracing::error!(%addr, "{}", reason);
+            let message = format!("Failed to start server: {}", reason);
+            emit_status(&app, ConnectionEvent::Error { message: message.clone() });
+            return Err(message);
+        }
+    }
+
+    let (use_tls, transport) = (config.tls, config.transport);
+    if transport == config::Transport::Unix {
+        #[cfg(unix)]
+        return start_unix_server(app).await;
+
+        #[cfg(not(unix))]
+        tracing::warn!("Unix socket transport is not available on this platform, using TCP");
+    }
+    let acceptor = if use_tls {
+        let loaded = app
+            .path()
+            .app_config_dir()
+            .map_err(|e| e.to_string())
+            .and_then(|dir| tls::load_or_generate(&dir));
+        match loaded {
+            Ok(ws_tls) => {
+                tracing::info!(fingerprint = %ws_tls.fingerprint, "WebSocket certificate loaded");
+                *app.state::<AppState>().ws_cert_fingerprint.lock().unwrap() = Some(ws_tls.fingerprint);
+                Some(ws_tls.acceptor)
+            }
+            Err(e) => {
+                // Don't silently downgrade to plain ws:// when TLS was asked for
+                tracing::error!(error = %e, "Failed to set up TLS");
+                let message = format!("Failed to start server: {}", e);
+                emit_status(&app, ConnectionEvent::Error { message: message.clone() });
+                return Err(message);
+            }
+        }
+    } else {
+        None
+    };
 
-    let listener = match TcpListener::bind(&addr).await {
+    let listener = match bind_with_retry(&app, addr, &config).await {
         Ok(l) => l,
         Err(e) => {
-            eprintln!("Failed to bind WebSocket server: {}", e);
-            let _ = app.emit("agent-error", format!("Failed to start server: {}", e));
-            return;
+            tracing::error!(port, error = %e, "Failed to bind WebSocket server");
+            let message = format!("Failed to start server: {}", e);
+            emit_status(&app, ConnectionEvent::Error { message: message.clone() });
+            return Err(message);
+        }
+    };
+    // Port 0 lets the OS pick
+    let addr = listener.local_addr().unwrap_or(addr);
+
+    let url = format!("{}://{}", if acceptor.is_some() { "wss" } else { "ws" }, addr);
+    tracing::info!(port = addr.port(), %url, "WebSocket server listening");
+    emit_status(&app, ConnectionEvent::Listening { port: addr.port(), url: url.clone(), socket_path: None });
+    if remote {
+        tracing::warn!(%url, "WebSocket server is reachable from other machines");
+        emit_status(&app, ConnectionEvent::RemoteExposed { url, tls: acceptor.is_some() });
+    }
+
+    let state = app.state::<AppState>();
+    *state.ws_port.lock().unwrap() = Some(addr.port());
+    let connections = state.connections.clone();
+    let shutdown = state.shutdown.subscribe();
+    let task = tauri::async_runtime::spawn(accept_loop(app.clone(), listener, acceptor, connections, shutdown));
+    *state.server_task.lock().unwrap() = Some(task);
+    Ok(addr.port())
+}
+
+async fn accept_loop(
+    app: impl EventSink,
+    mut listener: impl AgentListener,
+    acceptor: Option<tokio_rustls::TlsAcceptor>,
+    connections: WsConnections,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = stopped(&mut shutdown) => break,
+        };
+        let (stream, peer) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) if is_recoverable_accept_error(&e) => {
+                tracing::warn!(error = %e, "Transient accept error, continuing");
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "WebSocket listener failed");
+                emit_status(&app, ConnectionEvent::Error { message: format!("Server stopped accepting: {}", e) });
+                let Ok(addr) = listener.local_addr() else { break };
+                // Release the port before binding it again
+                drop(listener);
+                match rebind(&app, addr, acceptor.is_some(), &mut shutdown).await {
+                    Some(rebound) => {
+                        listener = rebound.into();
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+        };
+
+        let app_clone = app.clone();
+        let connections_clone = connections.clone();
+        let shutdown_clone = shutdown.clone();
+        let acceptor = acceptor.clone();
+        tokio::spawn(async move {
+            let stream: Box<dyn AgentStream> = match acceptor {
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(tls_stream) => Box::new(tls_stream),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "TLS handshake failed");
+                        return;
+                    }
+                },
+                None => Box::new(stream),
+            };
+            handle_connection(stream, Some(peer.to_string()), app_clone, connections_clone, shutdown_clone).await;
+        });
+    }
+    tracing::info!("WebSocket server stopped");
+}
+
+// Listen on a Unix socket in the runtime dir. Access is limited by the
+// socket file's permissions, so TLS is not used here.
+#[cfg(unix)]
+async fn start_unix_server(app: AppHandle) -> Result<u16, String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = app.path().runtime_dir().unwrap_or_else(|_| std::env::temp_dir());
+    let path = dir.join(SOCKET_FILE);
+    // A file left by a crashed instance blocks the bind; a live one must not be taken over
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            let message = format!("Failed to start server: {} is in use", path.display());
+            emit_status(&app, ConnectionEvent::Error { message: message.clone() });
+            return Err(message);
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    let listener = match tokio::net::UnixListener::bind(&path) {
+        Ok(l) => l,
+        Err(e) => {
+            tracing::error!(path = %path.display(), error = %e, "Failed to bind Unix socket");
+            let message = format!("Failed to start server: {}", e);
+            emit_status(&app, ConnectionEvent::Error { message: message.clone() });
+            return Err(message);
         }
     };
+    if let Err(e) = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)) {
+        tracing::warn!(error = %e, "Failed to restrict socket permissions");
+    }
+
+    let socket_path = path.to_string_lossy().into_owned();
+    let url = format!("ws+unix://{}", socket_path);
+    tracing::info!(%url, "WebSocket server listening");
+    emit_status(&app, ConnectionEvent::Listening { port: 0, url, socket_path: Some(socket_path) });
 
-    println!("WebSocket server listening on ws://{}", addr);
-    let _ = app.emit("agent-status", format!("Listening on port {}", WS_PORT));
+    let state = app.state::<AppState>();
+    *state.ws_port.lock().unwrap() = None;
+    *state.ws_socket_path.lock().unwrap() = Some(path);
+    let connections = state.connections.clone();
+    let shutdown = state.shutdown.subscribe();
+    let task = tauri::async_runtime::spawn(accept_unix_loop(app.clone(), listener, connections, shutdown));
+    *state.server_task.lock().unwrap() = Some(task);
+    Ok(0)
+}
+
+#[cfg(unix)]
+async fn accept_unix_loop(
+    app: impl EventSink,
+    listener: tokio::net::UnixListener,
+    connections: WsConnections,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = stopped(&mut shutdown) => break,
+        };
+        let stream = match accepted {
+            Ok((stream, _)) => stream,
+            Err(e) if is_recoverable_accept_error(&e) => {
+                tracing::warn!(error = %e, "Transient accept error, continuing");
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Unix socket listener failed");
+                emit_status(&app, ConnectionEvent::Error { message: format!("Server stopped accepting: {}", e) });
+                break;
+            }
+        };
 
-    while let Ok((stream, _)) = listener.accept().await {
         let app_clone = app.clone();
-        let ws_writer_clone = ws_writer.clone();
+        let connections_clone = connections.clone();
+        let shutdown_clone = shutdown.clone();
         tokio::spawn(async move {
-            handle_connection(stream, app_clone, ws_writer_clone).await;
+            handle_connection(Box::new(stream), None, app_clone, connections_clone, shutdown_clone).await;
         });
     }
+    tracing::info!("WebSocket server stopped");
+}
+
+// Whether accept() can simply be retried. Running out of file descriptors or
+// a peer resetting before the accept completes is transient; anything else
+// means the listener itself is broken.
+fn is_recoverable_accept_error(e: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+
+    if matches!(
+        e.kind(),
+        ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::OutOfMemory
+    ) {
+        return true;
+    }
+
+    // ENFILE / EMFILE (same numbers on Linux and macOS), WSAEMFILE on Windows
+    #[cfg(unix)]
+    return matches!(e.raw_os_error(), Some(23 | 24));
+    #[cfg(windows)]
+    return e.raw_os_error() == Some(10024);
+}
+
+// Bind a fresh listener on the failed one's address, backing off between
+// attempts. Returns None if the server is shut down first.
+async fn rebind(app: &impl EventSink, addr: SocketAddr, tls: bool, shutdown: &mut watch::Receiver<bool>) -> Option<TcpListener> {
+    let mut delay = REBIND_INITIAL_DELAY;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = stopped(shutdown) => return None,
+        }
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                let url = format!("{}://{}", if tls { "wss" } else { "ws" }, addr);
+                tracing::info!(port = addr.port(), %url, "WebSocket server rebound");
+                emit_status(app, ConnectionEvent::Listening { port: addr.port(), url, socket_path: None });
+                return Some(listener);
+            }
+            Err(e) => {
+                tracing::warn!(port = addr.port(), error = %e, ?delay, "Rebind failed, retrying");
+                delay = (delay * 2).min(REBIND_MAX_DELAY);
+            }
+        }
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logging::init();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(|app, _shortcut, event| {
+                    if event.state() == ShortcutState::Pressed {
+                        toggle_main_window(app);
+                    }
+                })
+                .build(),
+        )
         .manage(AppState::default())
-        .invoke_handler(tauri::generate_handler![send_to_agent, stop_agent, update_pending_queue])
+        .invoke_handler(tauri::generate_handler![
+            send_to_agent,
+            send_binary_to_agent,
+            start_audio_stream,
+            push_audio_chunk,
+            stop_audio_stream,
+            send_raw_to_agent,
+            ping_agent,
+            set_max_connections,
+            set_idle_disconnect,
+            stop_agent,
+            set_input_paused,
+            get_last_turn_metrics,
+            send_context_to_agent,
+            update_pending_queue,
+            get_agent_status,
+            get_agent_activity,
+            get_agent_latency,
+            get_protocol_version,
+            get_connection_stats,
+            restart_ws_server,
+            set_server_autostart,
+            start_server,
+            stop_server,
+            set_log_level,
+            get_message_history,
+            export_transcript,
+            set_transcript_logging,
+            load_transcript_page,
+            open_url,
+            inject_agent_message,
+            get_window_scale,
+            confirm_open,
+            get_recent_errors,
+            get_recent_warnings,
+            set_dnd,
+            replay_history,
+            set_history_replay_rate,
+            get_last_assistant_message,
+            copy_last_response,
+            get_ws_token_path,
+            rotate_ws_token,
+            get_ws_cert_fingerprint,
+            set_click_through,
+            set_idle_dimming,
+            set_window_level,
+            set_focus_policy,
+            set_join_all_spaces,
+            set_above_fullscreen,
+            set_toggle_hotkey,
+            minimize_to_tray,
+            reset_window_layout,
+            snap_window,
+            capture_overlay,
+            check_screen_permission,
+            request_screen_permission,
+            resize_to_content,
+            set_overlay_mode,
+            get_server_health,
+            run_self_test,
+            pulse_attention,
+            set_base_opacity,
+            set_animation_fps,
+            set_reduced_motion,
+            get_glass_backend,
+            list_connections,
+            disconnect_connection,
+            set_event_target,
+            export_app_state,
+            import_app_state,
+            create_overlay_window,
+            close_overlay_window,
+            start_window_drag,
+            start_window_resize,
+            set_glass_tint,
+            set_glass_intensity,
+            preview_glass,
+            set_corner_radius,
+            set_glass_inset,
+            set_window_shadow,
+            set_window_border,
+            set_window_glass,
+            list_glass_materials,
+            glass_capabilities,
+            save_glass_preset,
+            load_glass_preset,
+            list_glass_presets,
+            set_theme_override,
+            set_text_contrast_scrim,
+            get_text_contrast_scrim,
+        ])
         .setup(|app| {
             let app_handle = app.handle().clone();
             let state: State<AppState> = app.state();
-            let ws_writer = state.ws_writer.clone();
 
-            // Apply liquid glass effect to main window
+            // Load server overrides and saved preferences from the app config dir
+            let config_dir = app.path().app_config_dir()?;
+            *state.config.lock().unwrap() = config::load(&config_dir.join(config::CONFIG_FILE));
+            *state.prefs.lock().unwrap() = prefs::load(&config_dir.join(prefs::PREFS_FILE));
+            // Preferences and layout have files of their own; the rest comes from the snapshot
+            if let Some(snapshot) = app_snapshot::load(&config_dir.join(app_snapshot::SNAPSHOT_FILE)) {
+                snapshot.glass.apply_to(&mut state.glass.lock().unwrap());
+                *state.theme_override.lock().unwrap() = snapshot.theme;
+                state.overlay_mode.lock().unwrap().mode = snapshot.overlay_mode;
+                *state.event_target.lock().unwrap() = snapshot.event_target;
+            }
+            state.connection_limit.set_max(state.config.lock().unwrap().max_connections);
+            state.replay_pacer.lock().unwrap().set_rate(state.config.lock().unwrap().replay_rate_per_sec);
+
+            // Register the show/hide hotkey
+            let hotkey = state.prefs.lock().unwrap().toggle_hotkey.clone();
+            if let Err(e) = register_toggle_hotkey(app.handle(), hotkey.as_deref().unwrap_or(DEFAULT_TOGGLE_HOTKEY)) {
+                tracing::error!(error = %e, "Failed to register toggle hotkey");
+            }
+
+            // Generate the token agents must authenticate with
+            let token = auth::generate_token();
+            let token_path = auth::token_path(&app.path().home_dir()?);
+            if let Err(e) = auth::write_secret_file(&token_path, &token) {
+                tracing::error!(path = %token_path.display(), error = %e, "Failed to write WebSocket token");
+            }
+            *state.ws_token.lock().unwrap() = Some(auth::WsToken::new(token, token_path));
+
+            // Apply liquid glass effect to main window, matching the OS theme
             if let Some(window) = app.get_webview_window("main") {
-                liquid_glass::apply(&window);
+                // Nothing applied yet, so there is no fallback to clear
+                state.glass_windows.lock().unwrap().insert("main".to_string(), liquid_glass::GlassBackend::Vibrancy);
+                let pinned = *state.theme_override.lock().unwrap();
+                if pinned.is_some() {
+                    let _ = window.set_theme(pinned);
+                }
+                apply_theme(app.handle(), pinned.unwrap_or_else(|| liquid_glass::system_theme(&window)));
+
+                let base_opacity = state.prefs.lock().unwrap().base_opacity.clamp(liquid_glass::MIN_BASE_OPACITY, 1.0);
+                state.idle.lock().unwrap().set_now(&window, base_opacity);
+
+                let logging = state.prefs.lock().unwrap().transcript_logging;
+                if logging.enabled {
+                    let opened = app
+                        .path()
+                        .app_data_dir()
+                        .map_err(|e| e.to_string())
+                        .and_then(|dir| {
+                            transcript_log::TranscriptLog::open(&dir.join(transcript_log::LOG_DIR), logging.max_size_mb)
+                                .map_err(|e| e.to_string())
+                        });
+                    match opened {
+                        Ok(log) => *state.transcript_log.lock().unwrap() = Some(log),
+                        Err(e) => tracing::warn!(error = %e, "Failed to open transcript log"),
+                    }
+                }
+
+                let focus_policy = state.prefs.lock().unwrap().focus_policy;
+                if let Err(e) = focus::apply(&window, focus_policy) {
+                    tracing::error!(error = %e, "Failed to apply focus policy");
+                }
+
+                let click_through = state.prefs.lock().unwrap().click_through;
+                if click_through.enabled {
+                    if let Err(e) = apply_click_through(app.handle(), &window, click_through) {
+                        tracing::warn!(error = %e, "Failed to apply click-through");
+                    }
+                }
+
+                // Restore the saved placement before showing the window
+                let saved = window_layout::load(&config_dir.join(window_layout::LAYOUT_FILE));
+                if let Err(e) = window_layout::restore(&window, saved) {
+                    tracing::error!(error = %e, "Failed to restore window layout");
+                }
+                *state.window_scale.lock().unwrap() = window.scale_factor().ok();
+                window.show()?;
             }
 
+            // Theme changes not delivered as window events (Linux portal)
+            let theme_handle = app.handle().clone();
+            liquid_glass::watch_system_theme(move |theme| {
+                let app = theme_handle.clone();
+                let _ = theme_handle.run_on_main_thread(move || follow_system_theme(&app, theme));
+            });
+
             // Setup system tray
             setup_tray(app)?;
 
-            // Start WebSocket server in background
-            tauri::async_runtime::spawn(async move {
-                start_ws_server(app_handle, ws_writer).await;
-            });
+            tauri::async_runtime::spawn(watch_idle(app.handle().clone()));
+            tauri::async_runtime::spawn(watch_health(app.handle().clone()));
+            tauri::async_runtime::spawn(autosave_app_snapshot(app.handle().clone()));
+
+            // Start WebSocket server in background, unless it waits for start_server
+            if state.prefs.lock().unwrap().server_autostart {
+                tauri::async_runtime::spawn(async move {
+                    let _ = start_ws_server(app_handle, WS_PORT).await;
+                });
+            } else {
+                tracing::info!("Server autostart is off, waiting for start_server");
+                let _ = app.emit("server-stopped", ServerStoppedEvent { reason: "autostart_off" });
+            }
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .on_window_event(|window, event| {
+            if window.label() != "main" {
+                handle_overlay_window_event(window.app_handle(), window.label(), event);
+                return;
+            }
+            match event {
+                WindowEvent::ThemeChanged(theme) => follow_system_theme(window.app_handle(), *theme),
+                WindowEvent::Moved(_) => schedule_layout_save(window.app_handle()),
+                WindowEvent::Resized(_) => {
+                    refit_glass(window.app_handle());
+                    schedule_layout_save(window.app_handle());
+                }
+                WindowEvent::Focused(_) => note_activity(window.app_handle()),
+                WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size, .. } => {
+                    handle_scale_change(window.app_handle(), *scale_factor, *new_inner_size)
+                }
+                _ => {}
+            }
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                save_window_layout(app);
+                save_app_snapshot(app);
+                let labels: Vec<String> = app.state::<AppState>().overlay_windows.lock().unwrap().keys().cloned().collect();
+                for label in labels {
+                    save_layout_of(app, &label);
+                }
+                // Give connected agents a proper close instead of a reset
+                tauri::async_runtime::block_on(shutdown_ws_server(&app.state::<AppState>()));
+            }
+        });
 }
 
 fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     let show_item = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
     let hide_item = MenuItem::with_id(app, "hide", "Hide", true, None::<&str>)?;
+    let glass_item = CheckMenuItem::with_id(app, "glass", "Glass Effect", true, true, None::<&str>)?;
     let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
 
-    let menu = Menu::with_items(app, &[&show_item, &hide_item, &quit_item])?;
+    let menu = Menu::with_items(app, &[&show_item, &hide_item, &glass_item, &quit_item])?;
+    *app.state::<AppState>().glass_menu_item.lock().unwrap() = Some(glass_item);
 
-    let _tray = TrayIconBuilder::new()
+    let _tray = TrayIconBuilder::with_id(TRAY_ID)
         .icon(app.default_window_icon().unwrap().clone())
+        .tooltip("Jarvis")
         .menu(&menu)
         .show_menu_on_left_click(false)
-        .on_menu_event(|app, event| {
+        .on_menu_event(move |app, event| {
             match event.id.as_ref() {
-                "show" => {
+                "show" => show_main_window(app),
+                "hide" => hide_main_window(app),
+                "glass" => {
                     if let Some(window) = app.get_webview_window("main") {
-                        let _ = window.show();
-                        let _ = window.set_focus();
-                    }
-                }
-                "hide" => {
-                    if let Some(window) = app.get_webview_window("main") {
-                        let _ = window.hide();
+                        let state = app.state::<AppState>();
+                        let enabled = !state.glass_windows.lock().unwrap().contains_key("main");
+                        toggle_glass(app, &state, &window, enabled);
                     }
                 }
                 "quit" => {
@@ -261,10 +4926,9 @@ fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
                 let app = tray.app_handle();
                 if let Some(window) = app.get_webview_window("main") {
                     if window.is_visible().unwrap_or(false) {
-                        let _ = window.hide();
+                        hide_main_window(app);
                     } else {
-                        let _ = window.show();
-                        let _ = window.set_focus();
+                        show_main_window(app);
                     }
                 }
             }
@@ -273,3 +4937,6 @@ fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests;
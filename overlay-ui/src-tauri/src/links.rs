@@ -0,0 +1,57 @@
+//! Opening Links
+//!
+//! URLs and files opened for the agent go through the opener plugin here.
+//! Only schemes listed in the config's `open_url_schemes` are allowed, and
+//! an open can be held until the user confirms it in the overlay, so an
+//! agent can't quietly launch arbitrary links.
+
+use serde::Serialize;
+use tauri::{AppHandle, Url};
+use tauri_plugin_opener::OpenerExt;
+
+/// Default value of `open_url_schemes`. Web links only; local files need
+/// `file` added to the config.
+pub fn default_schemes() -> Vec<String> {
+    vec!["http".to_string(), "https".to_string()]
+}
+
+/// Sent as `open-confirm`; answered with `confirm_open`.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenConfirmEvent {
+    pub id: String,
+    pub url: String,
+}
+
+/// Parse `url` and check its scheme against the allowlist.
+pub fn check(url: &str, schemes: &[String]) -> Result<Url, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL {:?}: {}", url, e))?;
+    if !schemes.iter().any(|scheme| scheme.eq_ignore_ascii_case(parsed.scheme())) {
+        return Err(format!("Opening {}: URLs is not allowed", parsed.scheme()));
+    }
+    Ok(parsed)
+}
+
+/// Open with the system default handler; `file:` URLs as paths.
+pub fn open(app: &AppHandle, url: &Url) -> Result<(), String> {
+    let result = if url.scheme() == "file" {
+        let path = url.to_file_path().map_err(|_| format!("Not a local file: {}", url))?;
+        app.opener().open_path(path.to_string_lossy(), None::<&str>)
+    } else {
+        app.opener().open_url(url.as_str(), None::<&str>)
+    };
+    result.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_urls_need_opting_in() {
+        assert!(check("https://example.com", &default_schemes()).is_ok());
+        assert!(check("file:///etc/passwd", &default_schemes()).is_err());
+        let mut schemes = default_schemes();
+        schemes.push("file".to_string());
+        assert!(check("FILE:///tmp/notes.txt", &schemes).is_ok());
+    }
+}
@@ -2,12 +2,39 @@
 //!
 //! Linux vibrancy depends on the compositor (KWin, Mutter, Picom, etc.)
 //! The transparent window setting should work with compositors that support it.
+//! On Wayland nothing blurs a plain transparent surface, so a solid
+//! background is used instead of leaving the window see-through.
 //! TODO: Investigate compositor-specific APIs
 
-use tauri::WebviewWindow;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+use tauri::{Theme, WebviewWindow};
+
+use super::{GlassBackend, GlassCapabilities, GlassConfig, GlassMaterialInfo};
+
+const PORTAL_DEST: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// Whether GTK draws the window through Wayland rather than X11/XWayland.
+fn is_wayland() -> bool {
+    // GDK_BACKEND=x11 puts GTK on XWayland even inside a Wayland session
+    let forced_x11 = std::env::var("GDK_BACKEND").is_ok_and(|b| b.starts_with("x11"));
+    std::env::var_os("WAYLAND_DISPLAY").is_some() && !forced_x11
+}
 
 /// Apply vibrancy effect on Linux
-pub fn apply_effect(_window: &WebviewWindow) {
+///
+/// On X11 there is no way to ask the compositor whether it blurs the
+/// window, so this reports success. On Wayland it fails so the caller
+/// falls back to a solid background.
+pub fn apply_effect(_window: &WebviewWindow, _config: &GlassConfig) -> Result<GlassBackend, String> {
+    if is_wayland() {
+        // TODO: KWin exposes org_kde_kwin_blur on Wayland; binding it needs a
+        // Wayland client and the GTK window's wl_surface.
+        return Err("No blur protocol available on this Wayland compositor".to_string());
+    }
+
     // TODO: Implement Linux vibrancy
     //
     // Linux doesn't have a unified API for window vibrancy.
@@ -19,11 +46,103 @@ pub fn apply_effect(_window: &WebviewWindow) {
     // For now, rely on:
     // 1. transparent: true in tauri.conf.json
     // 2. Compositor settings (user must enable blur in their compositor)
-    eprintln!("[liquid_glass] Linux implementation relies on compositor settings");
+    tracing::info!("Linux implementation relies on compositor settings");
+    Ok(GlassBackend::Compositor)
 }
 
 /// Remove the vibrancy effect from the window
 pub fn remove_effect(_window: &WebviewWindow) {
     // TODO: Implement removal
-    eprintln!("[liquid_glass] Linux remove_effect not yet implemented");
+    tracing::warn!("Linux remove_effect not yet implemented");
+}
+
+/// No selectable materials; blur comes from the compositor, if at all
+pub fn materials() -> Vec<GlassMaterialInfo> {
+    Vec::new()
+}
+
+/// Blur only exists through an X11 compositor, and `remove_effect` is a no-op.
+pub fn capabilities() -> GlassCapabilities {
+    let wayland = is_wayland();
+    GlassCapabilities {
+        vibrancy: !wayland,
+        removal: false,
+        material_switch: false,
+        per_window: true,
+        intensity: false,
+        os_version: crate::self_test::os_version(),
+        linux_blur: Some(if wayland { "none" } else { "compositor" }),
+        linux_desktop: std::env::var("XDG_CURRENT_DESKTOP").ok(),
+    }
+}
+
+/// Set the opacity of the whole window
+pub fn set_opacity(window: &WebviewWindow, opacity: f64) {
+    use gtk::prelude::*;
+
+    // Only takes effect under a compositing window manager
+    match window.gtk_window() {
+        Ok(gtk_window) => gtk_window.set_opacity(opacity),
+        Err(e) => tracing::error!(error = %e, "Failed to set window opacity"),
+    }
+}
+
+/// Read `org.freedesktop.appearance color-scheme` from the settings portal.
+///
+/// Returns `None` when the portal is unavailable or reports no preference.
+pub fn portal_color_scheme() -> Option<Theme> {
+    let output = Command::new("gdbus")
+        .args([
+            "call", "--session",
+            "--dest", PORTAL_DEST,
+            "--object-path", PORTAL_PATH,
+            "--method", "org.freedesktop.portal.Settings.Read",
+            "org.freedesktop.appearance", "color-scheme",
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+    // Output looks like `(<<uint32 1>>,)`
+    parse_color_scheme(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Watch the settings portal for color-scheme changes on a background thread.
+pub fn watch_color_scheme<F: Fn(Theme) + Send + 'static>(on_change: F) {
+    std::thread::spawn(move || {
+        let child = Command::new("gdbus")
+            .args(["monitor", "--session", "--dest", PORTAL_DEST, "--object-path", PORTAL_PATH])
+            .stdout(Stdio::piped())
+            .spawn();
+
+        let Ok(mut child) = child else {
+            tracing::warn!("gdbus not available, theme changes won't be followed");
+            return;
+        };
+
+        if let Some(stdout) = child.stdout.take() {
+            // A change looks like:
+            // ...SettingChanged ('org.freedesktop.appearance', 'color-scheme', <uint32 1>)
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if line.contains("SettingChanged") && line.contains("'color-scheme'") {
+                    if let Some(theme) = parse_color_scheme(&line) {
+                        on_change(theme);
+                    }
+                }
+            }
+        }
+        let _ = child.wait();
+    });
+}
+
+/// Map the portal's color-scheme value (0 = no preference, 1 = dark, 2 = light).
+fn parse_color_scheme(output: &str) -> Option<Theme> {
+    let value = output.split("uint32").nth(1)?;
+    match value.trim_start().chars().next()? {
+        '1' => Some(Theme::Dark),
+        '2' => Some(Theme::Light),
+        _ => None,
+    }
 }
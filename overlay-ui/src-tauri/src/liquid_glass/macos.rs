@@ -6,6 +6,8 @@
 
 use tauri::WebviewWindow;
 
+use super::{GlassBackend, GlassCapabilities, GlassConfig, GlassMaterialInfo, WindowBorder};
+
 #[cfg(target_os = "macos")]
 use cocoa::appkit::NSColor;
 
@@ -15,43 +17,77 @@ use cocoa::appkit::NSColor;
 /// Note: We use NSVisualEffectView instead of NSGlassEffectView because
 /// NSGlassEffectView (macOS 26+) does not support the `state` property
 /// needed to keep the background updating when the window loses focus.
-pub fn apply_effect(window: &WebviewWindow) {
+pub fn apply_effect(window: &WebviewWindow, config: &GlassConfig) -> Result<GlassBackend, String> {
     use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial, NSVisualEffectState};
 
     // Set window properties FIRST (before applying vibrancy)
     set_window_appearance_active(window);
 
     // Ensure window is fully transparent
-    set_window_transparent(window);
+    set_window_transparent(window, config.shadow);
+
+    let radius = Some(config.corner_radius.clamp(0.0, super::MAX_CORNER_RADIUS));
 
     // Try FullScreenUI first for maximum transparency
     let result = apply_vibrancy(
         window,
         NSVisualEffectMaterial::FullScreenUI, // Most transparent material
         Some(NSVisualEffectState::Active),
-        Some(16.0),
+        radius,
     );
 
     match result {
         Ok(_) => {
-            println!("[liquid_glass] Applied FullScreenUI vibrancy");
+            tracing::info!(material = "FullScreenUI", "Applied vibrancy");
         }
         Err(_) => {
             // Fall back to HudWindow
-            let _ = apply_vibrancy(
+            apply_vibrancy(
                 window,
                 NSVisualEffectMaterial::HudWindow,
                 Some(NSVisualEffectState::Active),
-                Some(16.0),
-            );
-            println!("[liquid_glass] Applied HudWindow vibrancy");
+                radius,
+            )
+            .map_err(|e| e.to_string())?;
+            tracing::info!(material = "HudWindow", "Applied vibrancy");
+        }
+    }
+    inset_effect_view(window, config.inset.clamp(0.0, super::MAX_GLASS_INSET));
+    Ok(GlassBackend::Vibrancy)
+}
+
+/// NSView tag window-vibrancy gives the effect view it adds.
+const EFFECT_VIEW_TAG: i64 = 91376254;
+
+/// Shrink the effect view to leave an `inset` margin on every side. It
+/// keeps the margin as the window resizes, since it only stretches.
+#[cfg(target_os = "macos")]
+fn inset_effect_view(window: &WebviewWindow, inset: f64) {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::{NSPoint, NSRect, NSSize};
+    use objc::{msg_send, sel, sel_impl};
+
+    unsafe {
+        let Ok(ns_window_ptr) = window.ns_window() else { return };
+        let ns_window: id = ns_window_ptr as id;
+        let content: id = msg_send![ns_window, contentView];
+        let effect_view: id = msg_send![content, viewWithTag: EFFECT_VIEW_TAG];
+        if effect_view == nil {
+            return;
         }
+        let bounds: NSRect = msg_send![content, bounds];
+        let frame = NSRect::new(
+            NSPoint::new(inset, inset),
+            NSSize::new((bounds.size.width - 2.0 * inset).max(0.0), (bounds.size.height - 2.0 * inset).max(0.0)),
+        );
+        let _: () = msg_send![effect_view, setFrame: frame];
     }
 }
 
-/// Set window background to completely transparent
+/// Set window background to completely transparent. Leaves `alphaValue`
+/// alone; `liquid_glass::apply` restores the intended opacity afterwards.
 #[cfg(target_os = "macos")]
-fn set_window_transparent(window: &WebviewWindow) {
+fn set_window_transparent(window: &WebviewWindow, shadow: bool) {
     use cocoa::base::{id, nil};
     use objc::{msg_send, sel, sel_impl};
 
@@ -65,10 +101,10 @@ fn set_window_transparent(window: &WebviewWindow) {
             // Set opaque to false for full transparency
             let _: () = msg_send![ns_window, setOpaque: false];
 
-            // Remove any window shadow
-            let _: () = msg_send![ns_window, setHasShadow: false];
+            // The shadow is off unless asked for, so the glass blends in
+            let _: () = msg_send![ns_window, setHasShadow: shadow];
 
-            println!("[liquid_glass] Set window to fully transparent");
+            tracing::debug!("Set window to fully transparent");
         }
     }
 }
@@ -96,15 +132,99 @@ fn set_window_appearance_active(window: &WebviewWindow) {
             let behavior: u64 = (1 << 0) | (1 << 4) | (1 << 6);
             let _: () = msg_send![ns_window, setCollectionBehavior: behavior];
 
-            println!("[liquid_glass] Set window properties for background updates");
+            tracing::debug!("Set window properties for background updates");
+        }
+    }
+}
+
+/// Draw a border on the content view's layer, rounded like the vibrancy
+/// view. `None` removes it.
+pub fn set_border(window: &WebviewWindow, border: Option<WindowBorder>, radius: f64) {
+    use cocoa::base::{id, nil};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    unsafe {
+        let Ok(ns_window_ptr) = window.ns_window() else { return };
+        let ns_window: id = ns_window_ptr as id;
+        let view: id = msg_send![ns_window, contentView];
+        let _: () = msg_send![view, setWantsLayer: true];
+        let layer: id = msg_send![view, layer];
+        if layer == nil {
+            return;
         }
+        let WindowBorder { color: (r, g, b, a), width } = border.unwrap_or(WindowBorder { color: (0, 0, 0, 0), width: 0.0 });
+        let color: id = msg_send![class!(NSColor), colorWithSRGBRed: r as f64 / 255.0
+            green: g as f64 / 255.0
+            blue: b as f64 / 255.0
+            alpha: a as f64 / 255.0];
+        let cg_color: id = msg_send![color, CGColor];
+        let _: () = msg_send![layer, setBorderColor: cg_color];
+        let _: () = msg_send![layer, setBorderWidth: width];
+        let _: () = msg_send![layer, setCornerRadius: radius];
     }
 }
 
 /// Remove the vibrancy effect from the window
-#[allow(dead_code)]
 pub fn remove_effect(window: &WebviewWindow) {
     use window_vibrancy::clear_vibrancy;
 
     let _ = clear_vibrancy(window);
 }
+
+/// Set the opacity of the whole window, content included
+pub fn set_opacity(window: &WebviewWindow, opacity: f64) {
+    use cocoa::base::id;
+    use objc::{msg_send, sel, sel_impl};
+
+    unsafe {
+        if let Ok(ns_window_ptr) = window.ns_window() {
+            let ns_window: id = ns_window_ptr as id;
+            let _: () = msg_send![ns_window, setAlphaValue: opacity];
+        }
+    }
+}
+
+/// Vibrancy materials by minimum macOS version.
+///
+/// `liquid_glass` (NSGlassEffectView) needs macOS 26 and has no `state`
+/// property, so it stops updating when the window is unfocused; see the
+/// module docs.
+pub fn materials() -> Vec<GlassMaterialInfo> {
+    let material = |name, label, major, minor| GlassMaterialInfo {
+        name,
+        label,
+        available: os_at_least(major, minor),
+    };
+    vec![
+        material("liquid_glass", "Liquid Glass", 26, 0),
+        material("full_screen_ui", "Full Screen UI", 10, 14),
+        material("hud_window", "HUD Window", 10, 14),
+        material("popover", "Popover", 10, 11),
+        material("sidebar", "Sidebar", 10, 11),
+        material("under_window_background", "Under Window Background", 10, 14),
+    ]
+}
+
+/// The material is fixed and the tint is ignored by NSVisualEffectView.
+pub fn capabilities() -> GlassCapabilities {
+    GlassCapabilities {
+        vibrancy: os_at_least(10, 14),
+        removal: true,
+        material_switch: false,
+        per_window: true,
+        intensity: false,
+        os_version: crate::self_test::os_version(),
+        linux_blur: None,
+        linux_desktop: None,
+    }
+}
+
+fn os_at_least(major: u64, minor: u64) -> bool {
+    use cocoa::base::nil;
+    use cocoa::foundation::{NSOperatingSystemVersion, NSProcessInfo};
+
+    unsafe {
+        let info = NSProcessInfo::processInfo(nil);
+        info.isOperatingSystemAtLeastVersion(NSOperatingSystemVersion::new(major, minor, 0))
+    }
+}
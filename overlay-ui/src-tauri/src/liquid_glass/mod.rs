@@ -2,8 +2,10 @@
 //!
 //! Provides native transparent vibrancy effects across platforms.
 //! - macOS: NSVisualEffectView
-//! - Windows: Acrylic/Mica (TODO)
-//! - Linux: Compositor-based (TODO)
+//! - Windows: Acrylic, or Mica where Acrylic fails
+//! - Linux: Compositor-based on X11, solid background on Wayland
+//!
+//! When the native effect fails, a semi-opaque solid background is used.
 
 #[cfg(target_os = "macos")]
 mod macos;
@@ -14,24 +16,565 @@ mod windows;
 #[cfg(target_os = "linux")]
 mod linux;
 
-use tauri::WebviewWindow;
+use serde::{Deserialize, Serialize};
+use tauri::{window::Color, Theme, WebviewWindow};
+
+/// Highest tint alpha accepted for Acrylic. Above this the tint is
+/// effectively opaque and the glass effect is lost.
+pub const MAX_TINT_ALPHA: u8 = 220;
+
+/// What a window shows after `apply`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlassStatus {
+    /// The native effect is active.
+    Vibrancy,
+    /// The effect failed and a semi-opaque solid background is shown instead.
+    Fallback,
+}
+
+/// Which effect ended up behind a window. Each platform produces only some.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlassBackend {
+    /// NSVisualEffectView (macOS).
+    Vibrancy,
+    /// Acrylic blur (Windows 10 1803+).
+    Acrylic,
+    /// Mica backdrop (Windows 11), used when Acrylic fails.
+    Mica,
+    /// Transparent window; any blur comes from the compositor (Linux X11).
+    Compositor,
+    /// Semi-opaque solid background.
+    Fallback,
+}
+
+impl GlassBackend {
+    pub fn status(self) -> GlassStatus {
+        match self {
+            GlassBackend::Fallback => GlassStatus::Fallback,
+            _ => GlassStatus::Vibrancy,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GlassBackend::Vibrancy => "vibrancy",
+            GlassBackend::Acrylic => "acrylic",
+            GlassBackend::Mica => "mica",
+            GlassBackend::Compositor => "compositor",
+            GlassBackend::Fallback => "fallback",
+        }
+    }
+}
+
+/// A vibrancy material and whether this OS version supports it.
+#[derive(Debug, Clone, Serialize)]
+pub struct GlassMaterialInfo {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub available: bool,
+}
+
+/// What the glass effect can do on this platform, so the UI only offers
+/// settings that take effect.
+#[derive(Debug, Clone, Serialize)]
+pub struct GlassCapabilities {
+    /// A native or compositor effect can be applied at all.
+    pub vibrancy: bool,
+    /// `remove` actually takes the effect off.
+    pub removal: bool,
+    /// The material can be switched while running.
+    pub material_switch: bool,
+    /// Glass can be turned on and off per window.
+    pub per_window: bool,
+    /// Tint and intensity change how the effect looks.
+    pub intensity: bool,
+    pub os_version: String,
+    /// Linux only: the blur path in use ("compositor" on X11, "none" on
+    /// Wayland) and the desktop reported by `XDG_CURRENT_DESKTOP`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linux_blur: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linux_desktop: Option<String>,
+}
+
+/// Largest corner radius accepted, in logical pixels.
+pub const MAX_CORNER_RADIUS: f64 = 32.0;
+
+/// How a window decoration (corners, shadow, border) ends up drawn.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rendering {
+    /// By the OS, e.g. the vibrancy view on macOS or DWM on Windows.
+    Native,
+    /// Left to the webview, which rounds its own background with CSS.
+    Css,
+}
+
+/// Payload of `corner-radius-changed`. `radius` is what the window actually
+/// shows, which on Windows 11 is snapped to the sizes DWM offers.
+#[derive(Debug, Clone, Serialize)]
+pub struct CornerState {
+    pub label: String,
+    pub radius: f64,
+    pub rounding: Rendering,
+}
+
+/// Largest glass inset accepted, in logical pixels.
+pub const MAX_GLASS_INSET: f64 = 200.0;
+
+/// Payload of `glass-geometry-changed`: the card that has glass, in
+/// logical pixels relative to the window's top-left corner. Outside it the
+/// window is invisible and passes clicks through.
+#[derive(Debug, Clone, Serialize)]
+pub struct GlassGeometry {
+    pub label: String,
+    pub inset: f64,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Whether the glass itself is confined natively; the webview always
+    /// keeps its content inside the card.
+    pub rendering: Rendering,
+}
+
+/// Widest border accepted, in logical pixels.
+pub const MAX_BORDER_WIDTH: f64 = 8.0;
+
+/// Edge drawn around a window to set it apart from the desktop.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WindowBorder {
+    /// RGBA
+    pub color: (u8, u8, u8, u8),
+    /// Logical pixels.
+    pub width: f64,
+}
+
+/// Payload of `window-appearance-changed`.
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowAppearance {
+    pub label: String,
+    pub shadow: bool,
+    pub shadow_rendering: Rendering,
+    pub border: Option<WindowBorder>,
+    pub border_rendering: Rendering,
+}
+
+/// Tuning parameters for the glass effect.
+#[derive(Debug, Clone, Copy)]
+pub struct GlassConfig {
+    /// RGBA tint used by Acrylic. `None` uses the theme default.
+    pub tint: Option<(u8, u8, u8, u8)>,
+    /// Alpha for the theme default tint, set by intensity while `tint` is
+    /// unset so the color keeps following the theme. `None` is the default.
+    pub tint_alpha: Option<u8>,
+    /// Whether the dark variant of the effect is requested.
+    pub dark: bool,
+    /// Window opacity to restore once the effect is applied, since making
+    /// the window transparent can reset it. `None` leaves it alone.
+    pub opacity: Option<f64>,
+    /// Corner radius in logical pixels, 0 for square corners.
+    pub corner_radius: f64,
+    /// Drop shadow behind the window. Off by default so the glass blends in.
+    pub shadow: bool,
+    pub border: Option<WindowBorder>,
+    /// Margin between the window edge and the glass; 0 fills the window.
+    pub inset: f64,
+}
+
+impl Default for GlassConfig {
+    fn default() -> Self {
+        Self {
+            tint: None,
+            tint_alpha: None,
+            dark: true,
+            opacity: None,
+            corner_radius: 16.0,
+            shadow: false,
+            border: None,
+            inset: 0.0,
+        }
+    }
+}
+
+impl GlassConfig {
+    /// Set the RGBA tint. Alpha is clamped to `0..=MAX_TINT_ALPHA`.
+    pub fn set_tint(&mut self, r: u8, g: u8, b: u8, a: u8) {
+        self.tint = Some((r, g, b, a.min(MAX_TINT_ALPHA)));
+    }
+
+    /// Set how dense the glass looks, 0-100. Maps linearly onto the tint
+    /// alpha, so 0 is an untinted blur and 100 is `MAX_TINT_ALPHA`; a set
+    /// tint keeps its color, the theme default keeps following the theme.
+    /// Returns the clamped level.
+    pub fn set_intensity(&mut self, level: u8) -> u8 {
+        let level = level.min(100);
+        let alpha = (level as u16 * MAX_TINT_ALPHA as u16 / 100) as u8;
+        match &mut self.tint {
+            Some(tint) => tint.3 = alpha,
+            None => self.tint_alpha = Some(alpha),
+        }
+        level
+    }
+
+    /// Current intensity, 0-100, derived back from the tint alpha.
+    #[allow(dead_code)]
+    pub fn intensity(&self) -> u8 {
+        let (_, _, _, alpha) = self.effective_tint();
+        ((alpha as u16 * 100 + MAX_TINT_ALPHA as u16 / 2) / MAX_TINT_ALPHA as u16) as u8
+    }
+
+    /// The tint to apply, falling back to a subtle tint matching the theme.
+    #[allow(dead_code)]
+    pub fn effective_tint(&self) -> (u8, u8, u8, u8) {
+        let alpha = self.tint_alpha.unwrap_or(60);
+        match self.tint {
+            Some(tint) => tint,
+            None if self.dark => (20, 20, 20, alpha),
+            None => (240, 240, 240, alpha),
+        }
+    }
+
+    /// Solid background used when the native effect is unavailable.
+    /// Opaque enough to keep text legible over any desktop.
+    fn fallback_color(&self) -> Color {
+        if self.dark {
+            Color(24, 24, 24, 230)
+        } else {
+            Color(245, 245, 245, 230)
+        }
+    }
+}
+
+/// Glass settings to try out with `preview_glass`; unset fields keep the
+/// current value.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct GlassPreview {
+    pub tint: Option<(u8, u8, u8, u8)>,
+    /// 0-100; applied after `tint`, so it sets the tint alpha.
+    pub intensity: Option<u8>,
+    pub dark: Option<bool>,
+    pub corner_radius: Option<f64>,
+    pub inset: Option<f64>,
+    pub shadow: Option<bool>,
+}
+
+impl GlassPreview {
+    /// `base` with these settings laid over it, clamped like the setters do.
+    pub fn over(&self, base: &GlassConfig) -> GlassConfig {
+        let mut config = *base;
+        if let Some((r, g, b, a)) = self.tint {
+            config.set_tint(r, g, b, a);
+        }
+        if let Some(level) = self.intensity {
+            config.set_intensity(level);
+        }
+        if let Some(dark) = self.dark {
+            config.dark = dark;
+        }
+        if let Some(radius) = self.corner_radius.filter(|r| r.is_finite()) {
+            config.corner_radius = radius.clamp(0.0, MAX_CORNER_RADIUS);
+        }
+        if let Some(inset) = self.inset.filter(|i| i.is_finite()) {
+            config.inset = inset.clamp(0.0, MAX_GLASS_INSET);
+        }
+        if let Some(shadow) = self.shadow {
+            config.shadow = shadow;
+        }
+        config
+    }
+}
+
+/// Solid layer the webview draws behind its content so text stays legible
+/// over bright desktops, whatever the material. Rendered as CSS rather than
+/// natively so it behaves the same on every platform.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextScrim {
+    pub enabled: bool,
+    /// Opacity of the layer while enabled, 0.0-1.0.
+    pub min_opacity: f64,
+}
+
+impl Default for TextScrim {
+    fn default() -> Self {
+        Self { enabled: false, min_opacity: 0.35 }
+    }
+}
+
+/// Payload of `scrim-changed` and the result of the scrim commands.
+/// `opacity` is 0 while the scrim is off.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrimState {
+    pub opacity: f64,
+    pub color: (u8, u8, u8),
+}
+
+impl TextScrim {
+    /// Black under light text for the dark theme, white for the light one.
+    pub fn state(&self, dark: bool) -> ScrimState {
+        ScrimState {
+            opacity: if self.enabled { self.min_opacity } else { 0.0 },
+            color: if dark { (0, 0, 0) } else { (255, 255, 255) },
+        }
+    }
+}
+
+/// Detect the OS color scheme for a window.
+///
+/// Uses the freedesktop settings portal on Linux, and the window's
+/// native appearance (NSAppearance / AppsUseLightTheme) elsewhere.
+pub fn system_theme(window: &WebviewWindow) -> Theme {
+    #[cfg(target_os = "linux")]
+    if let Some(theme) = linux::portal_color_scheme() {
+        return theme;
+    }
+
+    window.theme().unwrap_or(Theme::Dark)
+}
+
+/// Subscribe to OS color-scheme changes that don't arrive as
+/// `WindowEvent::ThemeChanged`. Only Linux needs this; macOS and
+/// Windows report theme changes through the window event loop.
+pub fn watch_system_theme<F: Fn(Theme) + Send + 'static>(on_change: F) {
+    #[cfg(target_os = "linux")]
+    linux::watch_color_scheme(on_change);
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = on_change;
+}
 
 /// Apply liquid glass effect to a window.
 /// This creates a native transparent vibrancy background that shows
 /// content behind the window with blur/refraction effects.
-pub fn apply(window: &WebviewWindow) {
+///
+/// If the platform effect fails, a semi-opaque solid background is set
+/// instead so the UI stays legible. Either way the window ends with
+/// `config.opacity`. Returns the effect that took hold.
+#[tracing::instrument(name = "glass_apply", skip_all, fields(label = window.label()))]
+pub fn apply(window: &WebviewWindow, config: &GlassConfig) -> GlassBackend {
+    apply_to(window, config)
+}
+
+/// The window operations `apply` is made of, so their order can be
+/// checked without a native window.
+trait GlassTarget {
+    fn apply_effect(&self, config: &GlassConfig) -> Result<GlassBackend, String>;
+    fn set_fallback(&self, color: Color);
+    fn set_opacity(&self, opacity: f64);
+}
+
+impl GlassTarget for WebviewWindow {
+    fn apply_effect(&self, config: &GlassConfig) -> Result<GlassBackend, String> {
+        #[cfg(target_os = "macos")]
+        let result = macos::apply_effect(self, config);
+
+        #[cfg(target_os = "windows")]
+        let result = windows::apply_effect(self, config);
+
+        #[cfg(target_os = "linux")]
+        let result = linux::apply_effect(self, config);
+
+        result
+    }
+
+    fn set_fallback(&self, color: Color) {
+        if let Err(e) = self.set_background_color(Some(color)) {
+            tracing::error!(error = %e, "Failed to set fallback background");
+        }
+    }
+
+    fn set_opacity(&self, opacity: f64) {
+        set_opacity(self, opacity);
+    }
+}
+
+fn apply_to(target: &impl GlassTarget, config: &GlassConfig) -> GlassBackend {
+    let backend = match target.apply_effect(config) {
+        Ok(backend) => backend,
+        Err(e) => {
+            tracing::warn!(error = %e, "Effect unavailable, using solid background");
+            target.set_fallback(config.fallback_color());
+            GlassBackend::Fallback
+        }
+    };
+    if let Some(opacity) = config.opacity {
+        target.set_opacity(opacity);
+    }
+    backend
+}
+
+/// Round the window corners to `config.corner_radius`, natively where the
+/// platform can and otherwise by handing the radius to the webview.
+/// On macOS the radius belongs to the vibrancy view, so it only changes
+/// through `apply` and is lost on the solid fallback.
+pub fn round_corners(window: &WebviewWindow, config: &GlassConfig, backend: GlassBackend) -> CornerState {
+    let radius = config.corner_radius.clamp(0.0, MAX_CORNER_RADIUS);
+
+    #[cfg(target_os = "macos")]
+    let result = match backend {
+        GlassBackend::Fallback => Err("no vibrancy view to round".to_string()),
+        _ => Ok(radius),
+    };
+
+    #[cfg(target_os = "windows")]
+    let result = {
+        let _ = backend;
+        windows::round_corners(window, radius, config.inset.clamp(0.0, MAX_GLASS_INSET))
+    };
+
+    #[cfg(target_os = "linux")]
+    let result: Result<f64, String> = {
+        let _ = backend;
+        Err("no native corner rounding".to_string())
+    };
+
+    let label = window.label().to_string();
+    match result {
+        Ok(radius) => CornerState { label, radius, rounding: Rendering::Native },
+        Err(e) => {
+            tracing::debug!(reason = %e, "Rounding corners with CSS");
+            CornerState { label, radius, rounding: Rendering::Css }
+        }
+    }
+}
+
+/// Apply the shadow and border from `config`, natively where the platform
+/// can draw them as asked and otherwise by handing them to the webview.
+pub fn decorate(window: &WebviewWindow, config: &GlassConfig) -> WindowAppearance {
+    #[cfg(not(target_os = "linux"))]
+    let shadow_rendering = match window.set_shadow(config.shadow) {
+        Ok(()) => Rendering::Native,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to set window shadow");
+            Rendering::Css
+        }
+    };
+
+    #[cfg(target_os = "macos")]
+    let border: Result<(), String> = {
+        macos::set_border(window, config.border, config.corner_radius.clamp(0.0, MAX_CORNER_RADIUS));
+        Ok(())
+    };
+
+    #[cfg(target_os = "windows")]
+    let border = windows::set_border(window, config.border);
+
+    // GTK offers neither a shadow nor a border for an undecorated window
+    #[cfg(target_os = "linux")]
+    let (shadow_rendering, border): (_, Result<(), String>) =
+        (Rendering::Css, Err("no native window border".to_string()));
+
+    let border_rendering = match border {
+        Ok(()) => Rendering::Native,
+        Err(e) => {
+            tracing::debug!(reason = %e, "Drawing window border with CSS");
+            Rendering::Css
+        }
+    };
+    WindowAppearance {
+        label: window.label().to_string(),
+        shadow: config.shadow,
+        shadow_rendering,
+        border: config.border,
+        border_rendering,
+    }
+}
+
+/// Re-fit rounding and inset that are tied to the window size after a
+/// resize. Only the Windows region needs it; the macOS effect view
+/// resizes with the window.
+pub fn refit_to_size(window: &WebviewWindow, config: &GlassConfig) {
+    #[cfg(target_os = "windows")]
+    windows::refit_region(
+        window,
+        config.corner_radius.clamp(0.0, MAX_CORNER_RADIUS),
+        config.inset.clamp(0.0, MAX_GLASS_INSET),
+    );
+
+    #[cfg(not(target_os = "windows"))]
+    let _ = (window, config);
+}
+
+/// The card left after `config.inset`, and whether the platform confines
+/// the glass to it: the effect view on macOS, the window region on Windows.
+pub fn geometry(window: &WebviewWindow, config: &GlassConfig, backend: GlassBackend) -> GlassGeometry {
+    let inset = config.inset.clamp(0.0, MAX_GLASS_INSET);
+    let size = window
+        .inner_size()
+        .ok()
+        .zip(window.scale_factor().ok())
+        .map(|(size, scale)| size.to_logical::<f64>(scale))
+        .unwrap_or(tauri::LogicalSize::new(0.0, 0.0));
+
+    let native = cfg!(any(target_os = "macos", target_os = "windows")) && backend != GlassBackend::Fallback;
+    GlassGeometry {
+        label: window.label().to_string(),
+        inset,
+        x: inset,
+        y: inset,
+        width: (size.width - 2.0 * inset).max(0.0),
+        height: (size.height - 2.0 * inset).max(0.0),
+        rendering: if native || inset == 0.0 { Rendering::Native } else { Rendering::Css },
+    }
+}
+
+/// Drop the solid background set by a failed `apply`.
+pub fn clear_fallback(window: &WebviewWindow) {
+    let _ = window.set_background_color(Some(Color(0, 0, 0, 0)));
+}
+
+/// Lowest base opacity accepted; below this text over a busy desktop is
+/// no longer legible.
+pub const MIN_BASE_OPACITY: f64 = 0.3;
+
+/// Set the opacity of the whole window, content included (0.0-1.0).
+/// Must be called on the main thread.
+pub fn set_opacity(window: &WebviewWindow, opacity: f64) {
+    let opacity = opacity.clamp(0.0, 1.0);
+
+    #[cfg(target_os = "macos")]
+    macos::set_opacity(window, opacity);
+
+    #[cfg(target_os = "windows")]
+    windows::set_opacity(window, opacity);
+
+    #[cfg(target_os = "linux")]
+    linux::set_opacity(window, opacity);
+}
+
+/// Materials known on this platform, with availability checked against
+/// the running OS version.
+pub fn materials() -> Vec<GlassMaterialInfo> {
     #[cfg(target_os = "macos")]
-    macos::apply_effect(window);
+    return macos::materials();
 
     #[cfg(target_os = "windows")]
-    windows::apply_effect(window);
+    return windows::materials();
 
     #[cfg(target_os = "linux")]
-    linux::apply_effect(window);
+    return linux::materials();
+}
+
+/// Capabilities of the glass effect on the running OS.
+pub fn capabilities() -> GlassCapabilities {
+    #[cfg(target_os = "macos")]
+    return macos::capabilities();
+
+    #[cfg(target_os = "windows")]
+    return windows::capabilities();
+
+    #[cfg(target_os = "linux")]
+    return linux::capabilities();
 }
 
 /// Remove liquid glass effect from a window.
-#[allow(dead_code)]
+#[tracing::instrument(name = "glass_remove", skip_all, fields(label = window.label()))]
 pub fn remove(window: &WebviewWindow) {
     #[cfg(target_os = "macos")]
     macos::remove_effect(window);
@@ -42,3 +585,69 @@ pub fn remove(window: &WebviewWindow) {
     #[cfg(target_os = "linux")]
     linux::remove_effect(window);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    // Behaves like the macOS effect: making the window transparent resets its opacity
+    struct FakeWindow {
+        effect: Result<GlassBackend, String>,
+        opacity: Cell<f64>,
+        background: RefCell<Option<Color>>,
+    }
+
+    impl FakeWindow {
+        fn new(effect: Result<GlassBackend, String>) -> Self {
+            Self { effect, opacity: Cell::new(1.0), background: RefCell::new(None) }
+        }
+    }
+
+    impl GlassTarget for FakeWindow {
+        fn apply_effect(&self, _config: &GlassConfig) -> Result<GlassBackend, String> {
+            self.opacity.set(1.0);
+            self.effect.clone()
+        }
+
+        fn set_fallback(&self, color: Color) {
+            *self.background.borrow_mut() = Some(color);
+        }
+
+        fn set_opacity(&self, opacity: f64) {
+            self.opacity.set(opacity);
+        }
+    }
+
+    #[test]
+    fn reapplying_keeps_the_opacity() {
+        let config = GlassConfig { opacity: Some(0.6), ..GlassConfig::default() };
+        let before = format!("{config:?}");
+        for effect in [Ok(GlassBackend::Vibrancy), Err("unavailable".to_string())] {
+            let expected = effect.clone().unwrap_or(GlassBackend::Fallback);
+            let window = FakeWindow::new(effect);
+            for _ in 0..2 {
+                assert_eq!(apply_to(&window, &config), expected);
+                assert_eq!(window.opacity.get(), 0.6);
+            }
+            assert_eq!(window.background.borrow().is_some(), expected == GlassBackend::Fallback);
+        }
+        assert_eq!(format!("{config:?}"), before);
+    }
+
+    #[test]
+    fn intensity_keeps_the_default_tint_following_the_theme() {
+        let mut config = GlassConfig::default();
+        config.set_intensity(100);
+        assert_eq!(config.tint, None);
+        assert_eq!(config.effective_tint(), (20, 20, 20, MAX_TINT_ALPHA));
+        config.dark = false;
+        assert_eq!(config.effective_tint(), (240, 240, 240, MAX_TINT_ALPHA));
+        assert_eq!(config.intensity(), 100);
+
+        config.set_tint(10, 60, 90, 0);
+        config.set_intensity(50);
+        config.dark = true;
+        assert_eq!(config.effective_tint(), (10, 60, 90, MAX_TINT_ALPHA / 2));
+    }
+}
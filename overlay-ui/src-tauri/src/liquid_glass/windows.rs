@@ -1,28 +1,210 @@
 //! Windows Liquid Glass Implementation
 //!
-//! Uses Acrylic/Mica effects via window-vibrancy crate.
+//! Uses Acrylic/Mica effects via window-vibrancy crate. Acrylic is tried
+//! first since only it takes a tint; Mica is the Windows 11 fallback, with
+//! Mica Alt standing in for high intensities.
 
 use tauri::WebviewWindow;
 
+use super::{GlassBackend, GlassCapabilities, GlassConfig, GlassMaterialInfo, WindowBorder};
+
+/// Windows 10 1803, the first build with Acrylic.
+const ACRYLIC_MIN_BUILD: u32 = 17134;
+
+/// First Windows 11 build, where Mica is available.
+const MICA_MIN_BUILD: u32 = 22000;
+
+/// Windows 11 22H2, the first build with Mica Alt (the tabbed backdrop).
+const MICA_ALT_MIN_BUILD: u32 = 22523;
+
+/// Glass intensity at or above which the denser Mica Alt (tabbed)
+/// backdrop is used instead of plain Mica.
+const MICA_ALT_INTENSITY: u8 = 50;
+
 /// Apply Acrylic effect on Windows
-/// 
-/// Uses transparent Acrylic for true glass effect.
+///
+/// Uses transparent Acrylic for true glass effect, tinted with
+/// `config.tint` or the theme default when no tint is set.
 /// Note: Acrylic requires Windows 10 version 1803 or later.
-pub fn apply_effect(window: &WebviewWindow) {
-    use window_vibrancy::apply_acrylic;
+pub fn apply_effect(window: &WebviewWindow, config: &GlassConfig) -> Result<GlassBackend, String> {
+    use window_vibrancy::{apply_acrylic, apply_mica, apply_tabbed};
+
+    // Low tint alpha (~75% transparent by default) keeps the desktop
+    // visible behind the window for a true glass effect
+    let acrylic_error = match apply_acrylic(window, Some(config.effective_tint())) {
+        Ok(()) => {
+            tracing::info!(backend = "acrylic", "Applied glass effect");
+            return Ok(GlassBackend::Acrylic);
+        }
+        Err(e) => e,
+    };
+
+    let build = windows_version::OsVersion::current().build;
+    if build < MICA_MIN_BUILD {
+        return Err(format!("Failed to apply Acrylic effect: {}", acrylic_error));
+    }
+    tracing::warn!(error = %acrylic_error, "Acrylic unavailable, trying Mica");
+    // Mica takes no tint; the closest thing to a denser glass is Mica Alt,
+    // where the build has it
+    let alt = config.intensity() >= MICA_ALT_INTENSITY && build >= MICA_ALT_MIN_BUILD;
+    let result = if alt { apply_tabbed(window, Some(config.dark)) } else { apply_mica(window, Some(config.dark)) };
+    result.map_err(|e| format!("Failed to apply Acrylic ({}) or Mica effect: {}", acrylic_error, e))?;
+    tracing::info!(backend = "mica", alt, "Applied glass effect");
+    Ok(GlassBackend::Mica)
+}
+
+/// Round the window corners and return the radius shown. Windows 11 only
+/// offers DWM's small (4px) and default (8px) rounding, so the radius is
+/// snapped to one of those; earlier builds, and any window with an
+/// `inset`, are cut with a region instead.
+pub fn round_corners(window: &WebviewWindow, radius: f64, inset: f64) -> Result<f64, String> {
+    use windows::Win32::Graphics::Dwm::{
+        DwmSetWindowAttribute, DWMWA_WINDOW_CORNER_PREFERENCE, DWMWCP_DONOTROUND, DWMWCP_ROUND, DWMWCP_ROUNDSMALL,
+    };
+
+    refit_region(window, radius, inset);
+    if windows_version::OsVersion::current().build < MICA_MIN_BUILD || inset > 0.0 {
+        return Ok(radius);
+    }
+    let (preference, shown) = match radius {
+        r if r <= 0.0 => (DWMWCP_DONOTROUND, 0.0),
+        r if r <= 6.0 => (DWMWCP_ROUNDSMALL, 4.0),
+        _ => (DWMWCP_ROUND, 8.0),
+    };
+    let hwnd = window.hwnd().map_err(|e| e.to_string())?;
+    unsafe {
+        DwmSetWindowAttribute(
+            hwnd,
+            DWMWA_WINDOW_CORNER_PREFERENCE,
+            &preference as *const _ as *const std::ffi::c_void,
+            std::mem::size_of_val(&preference) as u32,
+        )
+    }
+    .map_err(|e| format!("Failed to set corner preference: {}", e))?;
+    Ok(shown)
+}
+
+/// Color the DWM window border (Windows 11). DWM draws it one pixel wide,
+/// so wider borders are refused and left to the webview; `None` restores
+/// the system border.
+pub fn set_border(window: &WebviewWindow, border: Option<WindowBorder>) -> Result<(), String> {
+    use windows::Win32::Graphics::Dwm::{DwmSetWindowAttribute, DWMWA_BORDER_COLOR, DWMWA_COLOR_DEFAULT};
+
+    if windows_version::OsVersion::current().build < MICA_MIN_BUILD {
+        return Err("border colors need Windows 11".to_string());
+    }
+    let color = match border {
+        Some(WindowBorder { width, .. }) if width > 1.0 => None,
+        // COLORREF is 0x00BBGGRR; DWM ignores alpha
+        Some(WindowBorder { color: (r, g, b, _), .. }) => Some(r as u32 | (g as u32) << 8 | (b as u32) << 16),
+        None => Some(DWMWA_COLOR_DEFAULT),
+    };
+    let hwnd = window.hwnd().map_err(|e| e.to_string())?;
+    let value = color.unwrap_or(DWMWA_COLOR_DEFAULT);
+    unsafe {
+        DwmSetWindowAttribute(
+            hwnd,
+            DWMWA_BORDER_COLOR,
+            &value as *const _ as *const std::ffi::c_void,
+            std::mem::size_of_val(&value) as u32,
+        )
+    }
+    .map_err(|e| format!("Failed to set border color: {}", e))?;
+    match color {
+        Some(_) => Ok(()),
+        None => Err("DWM borders are one pixel wide".to_string()),
+    }
+}
 
-    // Use Acrylic with very subtle dark tint
-    // RGB: 20, 20, 20 with 60 opacity (~75% transparent)
-    // This creates a true glass effect showing desktop behind
-    if let Err(e) = apply_acrylic(window, Some((20, 20, 20, 60))) {
-        eprintln!("Failed to apply Acrylic effect: {}", e);
+/// Clip the window to a rounded rectangle `inset` in from its edges. Used
+/// for rounding before Windows 11 and for any inset, which also makes the
+/// margin pass clicks through. Has to be rebuilt whenever the window is
+/// resized.
+pub fn refit_region(window: &WebviewWindow, radius: f64, inset: f64) {
+    use windows::Win32::Graphics::Gdi::{CreateRoundRectRgn, SetWindowRgn};
+
+    let rounds = windows_version::OsVersion::current().build < MICA_MIN_BUILD;
+    let (Ok(hwnd), Ok(size), Ok(scale)) = (window.hwnd(), window.outer_size(), window.scale_factor()) else {
+        return;
+    };
+    let diameter = (radius * 2.0 * scale).round() as i32;
+    let margin = (inset * scale).round() as i32;
+    unsafe {
+        // A full, square window drops the region; the window owns it once set
+        let region = ((rounds && diameter > 0) || margin > 0).then(|| {
+            CreateRoundRectRgn(
+                margin,
+                margin,
+                size.width as i32 - margin + 1,
+                size.height as i32 - margin + 1,
+                diameter,
+                diameter,
+            )
+        });
+        if SetWindowRgn(hwnd, region, true) == 0 {
+            tracing::error!("Failed to set window region");
+        }
     }
 }
 
 /// Remove the vibrancy effect from the window
-/// 
-/// Note: window-vibrancy doesn't provide a remove function,
-/// so this is a no-op for now.
-pub fn remove_effect(_window: &WebviewWindow) {
-    // window-vibrancy doesn't provide a remove function
+pub fn remove_effect(window: &WebviewWindow) {
+    use window_vibrancy::{clear_acrylic, clear_mica};
+
+    if let Err(e) = clear_acrylic(window) {
+        tracing::error!(error = %e, "Failed to clear Acrylic effect");
+    }
+    // Fails harmlessly when Mica was never applied
+    let _ = clear_mica(window);
+}
+
+/// Set the opacity of the whole window through a layered window alpha
+pub fn set_opacity(window: &WebviewWindow, opacity: f64) {
+    use windows::Win32::Foundation::COLORREF;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW, GWL_EXSTYLE, LWA_ALPHA, WS_EX_LAYERED,
+    };
+
+    let Ok(hwnd) = window.hwnd() else { return };
+    unsafe {
+        let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+        if style & WS_EX_LAYERED.0 as isize == 0 {
+            SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | WS_EX_LAYERED.0 as isize);
+        }
+        let alpha = (opacity * 255.0).round() as u8;
+        if let Err(e) = SetLayeredWindowAttributes(hwnd, COLORREF(0), alpha, LWA_ALPHA) {
+            tracing::error!(error = %e, "Failed to set window opacity");
+        }
+    }
+}
+
+/// System backdrop materials by minimum Windows build.
+pub fn materials() -> Vec<GlassMaterialInfo> {
+    let build = windows_version::OsVersion::current().build;
+    let material = |name, label, min_build| GlassMaterialInfo {
+        name,
+        label,
+        available: build >= min_build,
+    };
+    vec![
+        material("acrylic", "Acrylic", ACRYLIC_MIN_BUILD),
+        // Windows 11
+        material("mica", "Mica", MICA_MIN_BUILD),
+        material("tabbed", "Mica Alt", MICA_ALT_MIN_BUILD),
+    ]
+}
+
+/// Intensity drives the Acrylic tint, or picks between Mica and Mica Alt.
+pub fn capabilities() -> GlassCapabilities {
+    let acrylic = windows_version::OsVersion::current().build >= ACRYLIC_MIN_BUILD;
+    GlassCapabilities {
+        vibrancy: acrylic,
+        removal: true,
+        material_switch: false,
+        per_window: true,
+        intensity: acrylic,
+        os_version: crate::self_test::os_version(),
+        linux_blur: None,
+        linux_desktop: None,
+    }
 }
@@ -0,0 +1,40 @@
+//! Logging
+//!
+//! `tracing` subscriber writing to stderr. The filter comes from `JARVIS_LOG`,
+//! then `RUST_LOG`, then defaults to `info`, and can be swapped at runtime
+//! with `set_log_level` (e.g. `debug` or `jarvis_overlay_lib=trace`).
+
+use std::sync::OnceLock;
+
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+const DEFAULT_FILTER: &str = "info";
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Install the global subscriber. Call once, before anything logs.
+pub fn init() {
+    let directives = std::env::var("JARVIS_LOG")
+        .or_else(|_| std::env::var("RUST_LOG"))
+        .unwrap_or_else(|_| DEFAULT_FILTER.to_string());
+    let filter = EnvFilter::try_new(&directives).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    let (filter, handle) = reload::Layer::new(filter);
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+    if subscriber.try_init().is_ok() {
+        let _ = FILTER.set(handle);
+    }
+}
+
+/// Replace the active filter with new `EnvFilter` directives.
+pub fn set_level(directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(directives).map_err(|e| format!("Invalid log level {}: {}", directives, e))?;
+    FILTER
+        .get()
+        .ok_or("Logging not initialized")?
+        .reload(filter)
+        .map_err(|e| e.to_string())
+}
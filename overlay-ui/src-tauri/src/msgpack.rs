@@ -0,0 +1,84 @@
+//! MessagePack Wire Format
+//!
+//! Agents may negotiate MessagePack instead of JSON text in the auth frame
+//! (`"format": "msgpack"`). Protocol messages then travel as binary frames
+//! holding a MessagePack map; image and audio frames keep the binary.rs
+//! layout and are told apart by their first byte (a kind tag of 1-3 is
+//! never a map marker). Encoding and decoding is rmp-serde, so the same
+//! serde-derived types serialize to both formats.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+// Matches serde_json's recursion limit; rmp-serde allows 1024 levels,
+// more than a connection task's stack holds
+const MAX_DEPTH: usize = 128;
+
+/// How protocol messages are framed on one connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    Msgpack,
+}
+
+impl WireFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "msgpack" => Some(Self::Msgpack),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a binary frame holds a MessagePack map rather than a binary.rs frame.
+pub fn is_message(data: &[u8]) -> bool {
+    matches!(data.first(), Some(0x80..=0x8f | 0xde | 0xdf))
+}
+
+/// Structs go out as maps keyed by field name, as they are in JSON;
+/// `rmp_serde::to_vec` would write them as arrays.
+pub fn to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    rmp_serde::to_vec_named(value)
+}
+
+pub fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, rmp_serde::decode::Error> {
+    let mut deserializer = rmp_serde::Deserializer::from_read_ref(data);
+    deserializer.set_max_depth(MAX_DEPTH);
+    T::deserialize(&mut deserializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[test]
+    fn writes_structs_as_maps() {
+        assert_eq!(to_vec(&json!({"a": 1})).unwrap(), [0x81, 0xa1, b'a', 0x01]);
+        let event = crate::delta::DeltaEvent { message_id: "m1".into(), delta: "hi".into(), done: false };
+        let packed = to_vec(&event).unwrap();
+        assert!(is_message(&packed));
+        assert_eq!(from_slice::<Value>(&packed).unwrap(), serde_json::to_value(&event).unwrap());
+    }
+
+    #[test]
+    fn rejects_input_nested_too_deep() {
+        let mut deep = vec![0x91; MAX_DEPTH + 1];
+        deep.push(0xc0);
+        assert!(from_slice::<Value>(&deep).is_err());
+        let shallow = [0x91, 0x91, 0xc0];
+        assert_eq!(from_slice::<Value>(&shallow).unwrap(), json!([[null]]));
+    }
+
+    #[test]
+    fn tells_messages_from_binary_frames() {
+        assert!(is_message(&to_vec(&json!({"type": "message"})).unwrap()));
+        assert!(is_message(&[0xde, 0, 16]));
+        assert!(!is_message(&crate::binary::encode(crate::binary::BinaryKind::Image, &json!({}), b"png")));
+        assert!(!is_message(&[]));
+    }
+}
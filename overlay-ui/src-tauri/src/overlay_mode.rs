@@ -0,0 +1,66 @@
+//! Overlay Mode
+//!
+//! The overlay is either a small collapsed pill or the full chat panel.
+//! Switching animates the window between the two sizes, keeping the corner
+//! it is docked in. The expanded size is remembered on collapse so
+//! expanding returns to it, and the webview swaps its layout on the
+//! `overlay-mode-changed` event.
+
+use serde::{Deserialize, Serialize};
+
+/// Size of the collapsed pill, in logical pixels.
+pub const COLLAPSED_SIZE: (f64, f64) = (280.0, 56.0);
+
+/// Expanded size when none was remembered, in logical pixels.
+pub const DEFAULT_EXPANDED_SIZE: (f64, f64) = (380.0, 560.0);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverlayMode {
+    Collapsed,
+    #[default]
+    Expanded,
+}
+
+impl OverlayMode {
+    /// Parse `Collapsed` or `Expanded` (case-insensitive).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "collapsed" => Some(Self::Collapsed),
+            "expanded" => Some(Self::Expanded),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ModeState {
+    pub mode: OverlayMode,
+    /// Logical size to expand back to, taken when collapsing.
+    pub expanded_size: Option<(f64, f64)>,
+}
+
+impl ModeState {
+    /// Switch to `mode` from a window currently `size` logical pixels,
+    /// returning the logical size to animate to, or `None` if already in it.
+    pub fn switch(&mut self, mode: OverlayMode, size: (f64, f64)) -> Option<(f64, f64)> {
+        if mode == self.mode {
+            return None;
+        }
+        self.mode = mode;
+        Some(match mode {
+            OverlayMode::Collapsed => {
+                self.expanded_size = Some(size);
+                COLLAPSED_SIZE
+            }
+            OverlayMode::Expanded => self.expanded_size.take().unwrap_or(DEFAULT_EXPANDED_SIZE),
+        })
+    }
+}
+
+/// Payload of `overlay-mode-changed`.
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlayModeEvent {
+    pub mode: OverlayMode,
+    /// Expanded by an incoming message rather than the user.
+    pub automatic: bool,
+}
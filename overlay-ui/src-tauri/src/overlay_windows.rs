@@ -0,0 +1,57 @@
+//! Overlay Windows
+//!
+//! Extra floating panels next to the main chat window, such as a tools
+//! monitor or settings. They are created on demand by label, load a page
+//! of the app bundle, get the same glass as the main window and keep their
+//! own saved layout. Remote URLs are not allowed: the page gets the same
+//! command access as the main window.
+
+use serde::Serialize;
+use tauri::WebviewUrl;
+
+/// Size limits for new windows, in logical pixels.
+pub const MIN_SIZE: f64 = 120.0;
+pub const MAX_SIZE: f64 = 4096.0;
+
+/// Overlay window labels start with this; the capability in
+/// `capabilities/default.json` grants command access to `overlay-*` only.
+pub const LABEL_PREFIX: &str = "overlay-";
+
+/// Labels are also used in file names, so only `[A-Za-z0-9_-]` is allowed
+/// after `LABEL_PREFIX`.
+pub fn valid_label(label: &str) -> bool {
+    label.len() <= 64
+        && label.strip_prefix(LABEL_PREFIX).is_some_and(|name| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Page of the app bundle to load, such as `index.html?panel=tools`.
+pub fn page_url(url: &str) -> Result<WebviewUrl, String> {
+    if url.contains("://") || url.starts_with("//") {
+        return Err(format!("Only pages of the app can be opened, not {}", url));
+    }
+    Ok(WebviewUrl::App(url.trim_start_matches('/').into()))
+}
+
+/// Payload of `overlay-windows-changed`.
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlayWindowEvent {
+    pub label: String,
+    pub open: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_need_the_overlay_prefix() {
+        assert!(valid_label("overlay-tools"));
+        assert!(valid_label("overlay-settings_2"));
+        assert!(!valid_label("main"));
+        assert!(!valid_label("overlay-"));
+        assert!(!valid_label("tools"));
+        assert!(!valid_label("overlay-../main"));
+    }
+}
@@ -0,0 +1,97 @@
+//! Paced Delivery
+//!
+//! A burst of completed messages, such as `replay_history` re-emitting a
+//! long conversation, can freeze the webview if every message is emitted
+//! at once. The pacer lets a second's
+//! worth through immediately and queues the rest, to be drained at `rate`
+//! per second. A rate of 0 turns pacing off.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::rate_limit::TokenBucket;
+
+pub struct Pacer<T> {
+    rate: u32,
+    bucket: TokenBucket,
+    queue: VecDeque<T>,
+}
+
+impl<T> Default for Pacer<T> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<T> Pacer<T> {
+    pub fn new(rate: u32) -> Self {
+        Self { rate, bucket: TokenBucket::new(f64::from(rate), rate), queue: VecDeque::new() }
+    }
+
+    /// Change the rate. Returns everything queued when pacing is turned off,
+    /// in order, for the caller to deliver at once.
+    pub fn set_rate(&mut self, rate: u32) -> Vec<T> {
+        self.rate = rate;
+        self.bucket = TokenBucket::new(f64::from(rate), rate);
+        if rate == 0 {
+            self.queue.drain(..).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Hand back `item` if it can be delivered now, or queue it behind
+    /// earlier items so order is kept.
+    pub fn admit(&mut self, item: T) -> Option<T> {
+        if self.rate == 0 || (self.queue.is_empty() && self.bucket.try_take().is_ok()) {
+            return Some(item);
+        }
+        self.queue.push_back(item);
+        None
+    }
+
+    /// The next queued item, taken at most once per `interval`.
+    pub fn pop(&mut self) -> Option<T> {
+        self.queue.pop_front()
+    }
+
+    /// Time between queued deliveries.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / f64::from(self.rate.max(1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Drain the way the replay task does: one queued item per interval
+    #[tokio::test(start_paused = true)]
+    async fn large_replay_is_spread_over_time() {
+        let mut pacer = Pacer::new(10);
+        let start = tokio::time::Instant::now();
+        let mut delivered: Vec<(u32, Duration)> =
+            (0..50).filter_map(|i| pacer.admit(i)).map(|i| (i, start.elapsed())).collect();
+        assert_eq!(delivered.len(), 10);
+
+        loop {
+            tokio::time::sleep(pacer.interval()).await;
+            let Some(i) = pacer.pop() else { break };
+            delivered.push((i, start.elapsed()));
+        }
+
+        assert!(delivered.iter().map(|(i, _)| *i).eq(0..50));
+        assert!(delivered[..10].iter().all(|(_, at)| at.is_zero()));
+        assert_eq!(delivered[10].1, Duration::from_millis(100));
+        assert_eq!(delivered[49].1, Duration::from_secs(4));
+    }
+
+    #[test]
+    fn turning_pacing_off_flushes_in_order() {
+        let mut pacer = Pacer::new(2);
+        let admitted: Vec<_> = (0..5).filter_map(|i| pacer.admit(i)).collect();
+        assert_eq!(admitted, [0, 1]);
+        assert_eq!(pacer.set_rate(0), [2, 3, 4]);
+        assert_eq!(pacer.admit(5), Some(5));
+    }
+}
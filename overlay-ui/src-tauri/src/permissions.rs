@@ -0,0 +1,54 @@
+//! Screen Recording Permission
+//!
+//! macOS only lets an app read other windows' pixels once the user grants
+//! screen recording. CoreGraphics reports just granted or not, so whether
+//! the user already answered the prompt is inferred from whether the
+//! overlay ever showed it. Other platforms have no such permission.
+
+use serde::Serialize;
+
+/// Each platform produces only some of these.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionStatus {
+    Granted,
+    Denied,
+    /// The system prompt has not been shown yet.
+    Undetermined,
+    /// The platform has no screen recording permission.
+    NotApplicable,
+}
+
+/// Current screen recording status; `requested` is whether the prompt was
+/// shown before.
+pub fn screen_status(requested: bool) -> PermissionStatus {
+    #[cfg(target_os = "macos")]
+    return if core_graphics::access::ScreenCaptureAccess.preflight() {
+        PermissionStatus::Granted
+    } else if requested {
+        PermissionStatus::Denied
+    } else {
+        PermissionStatus::Undetermined
+    };
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = requested;
+        PermissionStatus::NotApplicable
+    }
+}
+
+/// Show the system prompt. macOS shows it only once per app; later calls
+/// just report the status. The grant takes effect after a relaunch.
+pub fn request_screen() -> PermissionStatus {
+    #[cfg(target_os = "macos")]
+    return if core_graphics::access::ScreenCaptureAccess.request() {
+        PermissionStatus::Granted
+    } else {
+        PermissionStatus::Denied
+    };
+
+    #[cfg(not(target_os = "macos"))]
+    return PermissionStatus::NotApplicable;
+}
@@ -0,0 +1,115 @@
+//! User Preferences
+//!
+//! UI preferences changed at runtime through commands. They are saved to
+//! `preferences.json` in the app config dir so they survive restarts.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::animation::AnimationSettings;
+use crate::click_through::ClickThrough;
+use crate::focus::FocusPolicy;
+use crate::idle::IdleDimming;
+use crate::liquid_glass::TextScrim;
+use crate::transcript_log::{self, TranscriptLogging};
+use crate::window_level::WindowLevel;
+
+/// File name of the preferences inside the app config dir.
+pub const PREFS_FILE: &str = "preferences.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub click_through: ClickThrough,
+    /// Accelerator for the show/hide hotkey; `None` uses the default.
+    pub toggle_hotkey: Option<String>,
+    pub idle_dimming: IdleDimming,
+    /// Do not disturb: agent messages are held back from the webview.
+    pub dnd: bool,
+    pub window_level: WindowLevel,
+    /// Keep the overlay on its own space instead of following every space.
+    pub current_space_only: bool,
+    /// Stay visible over fullscreen apps; see window_level.rs for the costs.
+    pub above_fullscreen: bool,
+    pub animation: AnimationSettings,
+    /// Window opacity when not dimmed; idle dimming scales down from it.
+    pub base_opacity: f64,
+    pub text_scrim: TextScrim,
+    /// The screen recording prompt was shown, so "not granted" means denied.
+    pub screen_permission_requested: bool,
+    pub focus_policy: FocusPolicy,
+    pub transcript_logging: TranscriptLogging,
+    /// Expand the collapsed overlay when an agent message arrives.
+    pub auto_expand: bool,
+    /// Start the WebSocket server at launch; otherwise only on `start_server`.
+    pub server_autostart: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            click_through: ClickThrough::default(),
+            toggle_hotkey: None,
+            idle_dimming: IdleDimming::default(),
+            dnd: false,
+            window_level: WindowLevel::default(),
+            current_space_only: false,
+            above_fullscreen: false,
+            animation: AnimationSettings::default(),
+            base_opacity: 1.0,
+            text_scrim: TextScrim::default(),
+            screen_permission_requested: false,
+            focus_policy: FocusPolicy::default(),
+            transcript_logging: TranscriptLogging::default(),
+            auto_expand: true,
+            server_autostart: true,
+        }
+    }
+}
+
+/// Load preferences, falling back to defaults when the file is missing or invalid.
+pub fn load(path: &Path) -> Preferences {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => return Preferences::default(),
+    };
+
+    let mut prefs: Preferences = serde_json::from_str(&text).unwrap_or_else(|e| {
+        tracing::warn!(path = %path.display(), error = %e, "Invalid preferences, using defaults");
+        Preferences::default()
+    });
+    // Zero would archive the transcript log on every message
+    let size = prefs.transcript_logging.max_size_mb;
+    let clamped = size.clamp(1, transcript_log::MAX_SIZE_MB);
+    if clamped != size {
+        tracing::warn!(size, clamped, "Transcript log size out of range, clamping");
+        prefs.transcript_logging.max_size_mb = clamped;
+    }
+    prefs
+}
+
+pub fn save(path: &Path, prefs: &Preferences) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(prefs).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamps_the_transcript_log_size() {
+        let path = std::env::temp_dir().join(format!("jarvis-prefs-{}.json", std::process::id()));
+        for (saved, loaded) in [(0, 1), (5, 5), (transcript_log::MAX_SIZE_MB + 1, transcript_log::MAX_SIZE_MB)] {
+            fs::write(&path, format!(r#"{{"transcript_logging": {{"enabled": true, "max_size_mb": {saved}}}}}"#)).unwrap();
+            assert_eq!(load(&path).transcript_logging.max_size_mb, loaded, "saved {saved}");
+        }
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,83 @@
+//! Rate Limiting
+//!
+//! Token bucket guarding each connection against floods: a runaway UI
+//! calling `send_to_agent` in a loop, or an agent spamming frames at the
+//! webview. Tokens refill at `rate` per second up to `burst`.
+
+use std::time::{Duration, Instant};
+
+/// No token was available. `first` is set for the first refusal after the
+/// bucket last admitted something, so callers can warn once per flood.
+pub struct Throttled {
+    pub first: bool,
+    /// Time until the next token is available.
+    pub retry_in: Duration,
+}
+
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    throttling: bool,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            rate: rate.max(0.0),
+            burst,
+            tokens: burst,
+            refilled_at: Instant::now(),
+            throttling: false,
+        }
+    }
+
+    /// Take a token for one message, or report that it must be dropped or delayed.
+    pub fn try_take(&mut self) -> Result<(), Throttled> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.throttling = false;
+            Ok(())
+        } else {
+            let first = !std::mem::replace(&mut self.throttling, true);
+            // A tiny rate can put the wait beyond what a Duration holds
+            let retry_in = Duration::try_from_secs_f64((1.0 - self.tokens) / self.rate).unwrap_or(Duration::MAX);
+            Err(Throttled { first, retry_in })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admits_burst_then_throttles() {
+        let burst = 5;
+        let mut bucket = TokenBucket::new(0.001, burst);
+        let results: Vec<_> = (0..burst + 3).map(|_| bucket.try_take()).collect();
+
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), burst as usize);
+        assert!(results[..burst as usize].iter().all(Result::is_ok));
+        let firsts: Vec<bool> = results[burst as usize..]
+            .iter()
+            .map(|r| r.as_ref().expect_err("throttled").first)
+            .collect();
+        assert_eq!(firsts, [true, false, false]);
+    }
+
+    #[test]
+    fn tiny_rate_does_not_overflow() {
+        let mut bucket = TokenBucket::new(f64::MIN_POSITIVE, 1);
+        assert!(bucket.try_take().is_ok());
+        let throttled = bucket.try_take().expect_err("throttled");
+        assert_eq!(throttled.retry_in, Duration::MAX);
+    }
+}
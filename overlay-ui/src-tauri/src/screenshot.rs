@@ -0,0 +1,123 @@
+//! Screenshot Thumbnails
+//!
+//! A full-resolution screenshot is too heavy to push through a Tauri event.
+//! The capture is written to `screenshots/` in the app data dir and the
+//! webview gets a downscaled JPEG thumbnail plus the file path. Captures
+//! older than the TTL are deleted as new ones arrive. A capture that looks
+//! the same as the last one shown, by difference hash, is dropped before
+//! it is stored so a static screen doesn't make the UI flicker.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::GenericImageView;
+use serde::Serialize;
+
+/// Directory inside the app data dir holding full captures.
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// Payload of `agent-screenshot` events. `width`/`height` describe the
+/// full capture.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenshotEvent {
+    pub thumb_base64: String,
+    pub full_path: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Outcome of `process`.
+pub enum Processed {
+    /// A new-looking capture, with its hash to compare the next one against.
+    Shown(ScreenshotEvent, u64),
+    /// Within the dedup distance of the previous capture; nothing stored.
+    Duplicate,
+}
+
+/// Store a capture in `dir` and build its thumbnail, at most `max_thumb`
+/// pixels on the long side, unless its hash is fewer than `dedup_distance`
+/// bits from `previous`. Decoding and scaling are CPU-bound; call this
+/// from a blocking task.
+pub fn process(
+    dir: &Path,
+    payload: &[u8],
+    max_thumb: u32,
+    previous: Option<u64>,
+    dedup_distance: u32,
+) -> Result<Processed, String> {
+    let format = image::guess_format(payload).map_err(|e| e.to_string())?;
+    let image = image::load_from_memory_with_format(payload, format).map_err(|e| e.to_string())?;
+    let (width, height) = image.dimensions();
+
+    let hash = dhash(&image);
+    if previous.is_some_and(|previous| (previous ^ hash).count_ones() < dedup_distance) {
+        return Ok(Processed::Duplicate);
+    }
+
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let extension = format.extensions_str().first().copied().unwrap_or("img");
+    let name = format!(
+        "screenshot-{}-{:08x}.{}",
+        chrono::Utc::now().timestamp_millis(),
+        rand::random::<u32>(),
+        extension
+    );
+    let full_path = dir.join(name);
+    fs::write(&full_path, payload).map_err(|e| e.to_string())?;
+
+    let thumb = if width > max_thumb || height > max_thumb {
+        image.thumbnail(max_thumb, max_thumb)
+    } else {
+        image
+    };
+    let mut jpeg = Cursor::new(Vec::new());
+    thumb
+        .to_rgb8()
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, THUMBNAIL_QUALITY))
+        .map_err(|e| e.to_string())?;
+
+    let event = ScreenshotEvent {
+        thumb_base64: base64::engine::general_purpose::STANDARD.encode(jpeg.into_inner()),
+        full_path: full_path.to_string_lossy().into_owned(),
+        width,
+        height,
+    };
+    Ok(Processed::Shown(event, hash))
+}
+
+/// 64-bit difference hash: the image shrunk to 9x8 grayscale, one bit per
+/// pixel set when it is brighter than its right neighbour. Small changes
+/// flip few bits, so the Hamming distance measures how different two
+/// captures look.
+fn dhash(image: &image::DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, image::imageops::FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    hash
+}
+
+/// Delete captures in `dir` last modified more than `ttl` ago.
+pub fn cleanup(dir: &Path, ttl: Duration) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() > ttl);
+        if expired {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
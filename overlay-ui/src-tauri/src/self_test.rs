@@ -0,0 +1,69 @@
+//! Self Test
+//!
+//! Platform facts for `run_self_test`, the first thing to run when the
+//! overlay seems to do nothing: which OS it is running on, and whether
+//! screen recording is granted on macOS.
+
+use serde::Serialize;
+
+/// One check of the self test; also the payload of `self-test-check` events.
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Human-readable OS version, e.g. "14.5.0", "10.0.22631" or the distro name.
+pub fn os_version() -> String {
+    #[cfg(target_os = "macos")]
+    return os_version_macos();
+
+    #[cfg(target_os = "windows")]
+    return os_version_windows();
+
+    #[cfg(target_os = "linux")]
+    return os_version_linux();
+}
+
+#[cfg(target_os = "macos")]
+fn os_version_macos() -> String {
+    use cocoa::base::nil;
+    use cocoa::foundation::NSProcessInfo;
+
+    let version = unsafe { NSProcessInfo::processInfo(nil).operatingSystemVersion() };
+    format!("{}.{}.{}", version.majorVersion, version.minorVersion, version.patchVersion)
+}
+
+#[cfg(target_os = "windows")]
+fn os_version_windows() -> String {
+    let version = windows_version::OsVersion::current();
+    format!("{}.{}.{}", version.major, version.minor, version.build)
+}
+
+#[cfg(target_os = "linux")]
+fn os_version_linux() -> String {
+    let kernel = std::fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default();
+    let distro = std::fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|text| {
+            text.lines()
+                .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+                .map(|name| name.trim_matches('"').to_string())
+        });
+    match distro {
+        Some(distro) => format!("{} (kernel {})", distro, kernel.trim()),
+        None => format!("kernel {}", kernel.trim()),
+    }
+}
+
+/// Whether screen recording is granted. `None` where the OS has no such
+/// permission. The overlay captures only its own window, which needs no
+/// permission, so this is informational for agents that capture the screen.
+pub fn screen_recording_granted() -> Option<bool> {
+    #[cfg(target_os = "macos")]
+    return Some(core_graphics::access::ScreenCaptureAccess.preflight());
+
+    #[cfg(not(target_os = "macos"))]
+    return None;
+}
@@ -0,0 +1,93 @@
+//! Connection Stability
+//!
+//! Counts how often agents come and go over an overlay session, so a
+//! flaky link can be told apart from a crashing agent. Downtime runs from
+//! the last agent leaving until the next one connects; time before the
+//! first connect isn't counted.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Result of `get_connection_stats` and payload of `connection-stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStats {
+    pub connects: u64,
+    pub disconnects: u64,
+    /// Connects within the resume window after the last disconnect.
+    pub reconnects: u64,
+    /// Includes the current outage, if no agent is connected.
+    pub downtime_ms: u64,
+    /// Seconds since the counters were last reset.
+    pub since_secs: u64,
+}
+
+pub struct StabilityTracker {
+    reset_at: Instant,
+    connects: u64,
+    disconnects: u64,
+    reconnects: u64,
+    downtime: Duration,
+    live: usize,
+    // Set while no agent is connected, after the first one left
+    down_since: Option<Instant>,
+}
+
+impl Default for StabilityTracker {
+    fn default() -> Self {
+        Self {
+            reset_at: Instant::now(),
+            connects: 0,
+            disconnects: 0,
+            reconnects: 0,
+            downtime: Duration::ZERO,
+            live: 0,
+            down_since: None,
+        }
+    }
+}
+
+impl StabilityTracker {
+    /// An agent connected. Counts as a reconnect if the last one left less
+    /// than `grace` ago.
+    pub fn connected(&mut self, grace: Duration, now: Instant) {
+        self.connects += 1;
+        self.live += 1;
+        if let Some(since) = self.down_since.take() {
+            let outage = now.saturating_duration_since(since);
+            self.downtime += outage;
+            if outage < grace {
+                self.reconnects += 1;
+            }
+        }
+    }
+
+    /// An agent disconnected. Connections from before a `reset` are ignored.
+    pub fn disconnected(&mut self, now: Instant) {
+        if self.live == 0 {
+            return;
+        }
+        self.disconnects += 1;
+        self.live -= 1;
+        if self.live == 0 {
+            self.down_since = Some(now);
+        }
+    }
+
+    /// Start counting afresh. Called as the session ends and every
+    /// connection is closed, so those closes aren't counted.
+    pub fn reset(&mut self, now: Instant) {
+        *self = Self { reset_at: now, ..Self::default() };
+    }
+
+    pub fn snapshot(&self, now: Instant) -> ConnectionStats {
+        let outage = self.down_since.map_or(Duration::ZERO, |since| now.saturating_duration_since(since));
+        ConnectionStats {
+            connects: self.connects,
+            disconnects: self.disconnects,
+            reconnects: self.reconnects,
+            downtime_ms: (self.downtime + outage).as_millis() as u64,
+            since_secs: now.saturating_duration_since(self.reset_at).as_secs(),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Status Debouncing
+//!
+//! Keeps agent-status from spamming the UI over a flaky link. An event
+//! identical to the previous one within `DEDUPE_WINDOW` is dropped, and
+//! once connect/disconnect transitions arrive faster than `FLAP_WINDOW`
+//! they are held back and collapsed into a single "unstable" event,
+//! followed by the state the link settled on.
+
+use std::time::{Duration, Instant};
+
+/// Repeats of the previous event within this long are dropped.
+pub const DEDUPE_WINDOW: Duration = Duration::from_secs(2);
+
+/// Transitions closer together than this count as flapping; the held
+/// events are flushed once this long passes without another one.
+pub const FLAP_WINDOW: Duration = Duration::from_secs(1);
+
+/// Transitions within `FLAP_WINDOW` after which further ones are held.
+const FLAP_TRANSITIONS: usize = 3;
+
+pub enum Verdict {
+    Emit,
+    Drop,
+    /// Held back; call `flush` once `FLAP_WINDOW` passes without another event.
+    Hold,
+}
+
+pub struct StatusFilter<E> {
+    last: Option<(E, Instant)>,
+    // Recent connect/disconnect times, pruned to FLAP_WINDOW
+    transitions: Vec<Instant>,
+    // Latest held transition and how many were held
+    held: Option<(E, u32)>,
+}
+
+impl<E> Default for StatusFilter<E> {
+    fn default() -> Self {
+        Self { last: None, transitions: Vec::new(), held: None }
+    }
+}
+
+impl<E: Clone + PartialEq> StatusFilter<E> {
+    /// Decide what to do with `event`. `transition` marks connect/disconnect events.
+    pub fn filter(&mut self, event: &E, transition: bool, now: Instant) -> Verdict {
+        if transition {
+            self.transitions.retain(|at| now.duration_since(*at) < FLAP_WINDOW);
+            self.transitions.push(now);
+            if self.held.is_some() || self.transitions.len() >= FLAP_TRANSITIONS {
+                let count = self.held.as_ref().map_or(0, |(_, count)| *count);
+                self.held = Some((event.clone(), count + 1));
+                return Verdict::Hold;
+            }
+        }
+
+        if let Some((last, at)) = &self.last {
+            if last == event && now.duration_since(*at) < DEDUPE_WINDOW {
+                return Verdict::Drop;
+            }
+        }
+        self.last = Some((event.clone(), now));
+        Verdict::Emit
+    }
+
+    /// Take the held transition and how many were collapsed into it.
+    pub fn flush(&mut self, now: Instant) -> Option<(E, u32)> {
+        let (event, count) = self.held.take()?;
+        self.transitions.clear();
+        self.last = Some((event.clone(), now));
+        Some((event, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_identical_statuses() {
+        let mut filter = StatusFilter::default();
+        let start = Instant::now();
+        let verdicts: Vec<_> = (0..10u64)
+            .map(|i| filter.filter(&"busy", false, start + Duration::from_millis(i * 10)))
+            .collect();
+
+        assert!(matches!(verdicts[0], Verdict::Emit));
+        assert!(verdicts[1..].iter().all(|v| matches!(v, Verdict::Drop)));
+        // Past the window the same status goes through again
+        assert!(matches!(filter.filter(&"busy", false, start + DEDUPE_WINDOW), Verdict::Emit));
+    }
+}
@@ -0,0 +1,628 @@
+use super::*;
+
+fn auth_frame(version: Option<u32>) -> AuthMessage {
+    let mut frame = serde_json::json!({"type": "auth", "token": "t"});
+    if let Some(version) = version {
+        frame["protocol_version"] = version.into();
+    }
+    serde_json::from_value(frame).unwrap()
+}
+
+#[test]
+fn protocol_version_matrix() {
+    // (version the agent sends, accepted, expects a hello frame)
+    let matrix = [
+        (None, true, false),
+        (Some(0), false, false),
+        (Some(MIN_PROTOCOL_VERSION), true, false),
+        (Some(PROTOCOL_VERSION), true, true),
+        (Some(PROTOCOL_VERSION + 1), false, true),
+        (Some(u32::MAX), false, true),
+    ];
+    for (sent, accepted, hello) in matrix {
+        let version = auth_frame(sent).protocol_version;
+        assert_eq!(protocol_supported(version), accepted, "version {sent:?}");
+        assert_eq!(version >= HELLO_PROTOCOL_VERSION, hello, "version {sent:?}");
+    }
+    assert_eq!(get_protocol_version(), PROTOCOL_VERSION);
+}
+
+#[test]
+fn sanitize_input_rejects_oversize() {
+    assert!(sanitize_input(&"a".repeat(10), 10).is_ok());
+    // Counted in characters, not bytes
+    assert!(sanitize_input(&"é".repeat(10), 10).is_ok());
+    assert!(matches!(sanitize_input(&"a".repeat(11), 10), Err(CommandError::InvalidArg(_))));
+}
+
+#[test]
+fn sanitize_input_strips_control_characters() {
+    let cleaned = sanitize_input("a\u{0}b\u{7}c\u{1b}[31md\u{7f}\nline\r\n\tend", 100).unwrap();
+    assert_eq!(cleaned, "abc[31md\nline\r\n\tend");
+}
+
+#[tokio::test]
+async fn health_reports_the_port_only_while_listening() {
+    let state = AppState::default();
+    *state.ws_port.lock().unwrap() = Some(9000);
+    let health = server_health(&state);
+    assert_eq!((health.listening, health.port), (false, None));
+
+    let task = tauri::async_runtime::spawn(std::future::pending::<()>());
+    *state.server_task.lock().unwrap() = Some(task);
+    let health = server_health(&state);
+    assert_eq!((health.listening, health.port), (true, Some(9000)));
+
+    // Stopped the way stop_server leaves it: task finished, port remembered
+    state.server_task.lock().unwrap().as_ref().unwrap().abort();
+    tokio::time::timeout(WAIT, async {
+        while server_listening(&state) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let health = server_health(&state);
+    assert_eq!((health.listening, health.port), (false, None));
+    assert_eq!(*state.ws_port.lock().unwrap(), Some(9000));
+}
+
+#[tokio::test]
+async fn a_replaced_glass_preview_does_not_revert() {
+    let mut timer = GlassPreviewTimer::default();
+    let (first, replaced) = timer.begin();
+    assert!(!replaced);
+    timer.task = Some(tauri::async_runtime::spawn(std::future::pending::<()>()));
+
+    // The first timer's sleep finished just as the second preview started
+    let (second, replaced) = timer.begin();
+    assert!(replaced);
+    timer.task = Some(tauri::async_runtime::spawn(std::future::pending::<()>()));
+    assert!(!timer.finish(first));
+    assert!(timer.task.is_some(), "the stale timer took the newer preview's handle");
+
+    assert!(timer.finish(second));
+    assert!(timer.task.is_none());
+}
+
+#[tokio::test]
+async fn injected_deltas_complete_across_calls() {
+    let sink = RecordingSink::new(config::ServerConfig { dev_commands: true, ..test_config() });
+    let delta = |text: &str, done| Some(InjectedDelta { delta: text.to_string(), done });
+    let id = || Some("m1".to_string());
+    inject_frame(&sink, None, None, None, id(), delta("Hel", false)).unwrap();
+    inject_frame(&sink, None, None, None, id(), delta("lo", false)).unwrap();
+    inject_frame(&sink, None, None, None, id(), delta("!", true)).unwrap();
+
+    let deltas: Vec<_> = sink.events("agent-message-delta").iter().map(|event| event["delta"].clone()).collect();
+    assert_eq!(deltas, ["Hel", "lo", "!"]);
+    assert_eq!(sink.app_state().last_streamed.lock().unwrap().as_deref(), Some("Hello!"));
+
+    let mixed = inject_frame(&sink, Some("assistant".to_string()), Some("x".to_string()), None, id(), delta("y", true));
+    assert!(matches!(mixed, Err(CommandError::InvalidArg(_))));
+    let incomplete = inject_frame(&sink, Some("assistant".to_string()), None, None, None, None);
+    assert!(matches!(incomplete, Err(CommandError::InvalidArg(_))));
+    assert_eq!(sink.events("agent-message-delta").len(), 3);
+}
+
+#[test]
+fn dnd_holds_a_bounded_backlog_and_replays_it_in_order() {
+    let sink = RecordingSink::new(test_config());
+    apply_dnd(&sink, true, true);
+    let message = |n: usize| AgentMessage {
+        role: "assistant".to_string(),
+        content: n.to_string(),
+        timestamp: String::new(),
+        tool_calls: None,
+        attachments: None,
+        id: None,
+        ttl_ms: None,
+        content_type: content_type::ContentType::Text,
+    };
+    for n in 0..DND_HELD_LIMIT + 5 {
+        emit_agent_message(&sink, message(n));
+    }
+    assert!(sink.events("agent-message").is_empty());
+    {
+        let held = sink.app_state().dnd_suppressed.lock().unwrap();
+        assert_eq!((held.messages.len(), held.count()), (DND_HELD_LIMIT, DND_HELD_LIMIT + 5));
+    }
+
+    apply_dnd(&sink, false, true);
+    let replayed: Vec<_> = sink.events("agent-message").iter().map(|message| message["content"].clone()).collect();
+    let expected: Vec<_> = (5..DND_HELD_LIMIT + 5).map(|n| serde_json::Value::from(n.to_string())).collect();
+    assert_eq!(replayed, expected);
+    assert_eq!(sink.app_state().dnd_suppressed.lock().unwrap().count(), 0);
+
+    emit_agent_message(&sink, message(0));
+    assert_eq!(sink.events("agent-message").len(), DND_HELD_LIMIT + 1);
+}
+
+// Serialize through both wire formats and check nothing changed on the way
+fn assert_round_trips<T: Serialize + serde::de::DeserializeOwned>(value: &T) {
+    let expected = serde_json::to_value(value).unwrap();
+    let json: T = serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap();
+    assert_eq!(serde_json::to_value(json).unwrap(), expected);
+    let packed: T = msgpack::from_slice(&msgpack::to_vec(value).unwrap()).unwrap();
+    assert_eq!(serde_json::to_value(packed).unwrap(), expected);
+}
+
+#[test]
+fn protocol_types_round_trip_through_both_formats() {
+    assert_round_trips(&UiMessage {
+        msg_type: "user_input".to_string(),
+        content: "héllo \"there\"\n".to_string(),
+        id: Some("u-1".to_string()),
+        kind: None,
+    });
+    for content_type in [
+        content_type::ContentType::Text,
+        content_type::ContentType::Markdown,
+        content_type::ContentType::Code { language: Some("rust".to_string()) },
+        content_type::ContentType::Code { language: None },
+    ] {
+        assert_round_trips(&AgentMessage {
+            role: "assistant".to_string(),
+            content: "done".to_string(),
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            tool_calls: Some(vec!["search".to_string()]),
+            attachments: None,
+            id: Some("m-1".to_string()),
+            ttl_ms: Some(u64::MAX),
+            content_type,
+        });
+    }
+    for activity in [AgentActivity::Idle, AgentActivity::Busy] {
+        assert_round_trips(&activity);
+    }
+    for format in [msgpack::WireFormat::Json, msgpack::WireFormat::Msgpack] {
+        assert_round_trips(&format);
+    }
+    assert_round_trips(&PendingQueueMessage {
+        msg_type: "pending_queue".to_string(),
+        messages: vec![PendingMessage { id: "p".to_string(), content: "queued".to_string(), timestamp: String::new() }],
+    });
+}
+
+// Connection harness: a real listener and tungstenite clients, with events
+// captured by a sink instead of a webview
+
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const TOKEN: &str = "test-token";
+const WAIT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Default)]
+struct RecordingSink {
+    state: Arc<AppState>,
+    events: Arc<std::sync::Mutex<Vec<(String, serde_json::Value)>>>,
+}
+
+impl EventSink for RecordingSink {
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        let payload = serde_json::to_value(payload).unwrap();
+        self.events.lock().unwrap().push((event.to_string(), payload));
+    }
+
+    fn app_state(&self) -> &AppState {
+        &self.state
+    }
+
+    fn app_handle(&self) -> Option<&AppHandle> {
+        None
+    }
+}
+
+impl RecordingSink {
+    fn new(config: config::ServerConfig) -> Self {
+        let sink = Self::default();
+        *sink.state.config.lock().unwrap() = config;
+        *sink.state.ws_token.lock().unwrap() =
+            Some(auth::WsToken::new(TOKEN.to_string(), std::env::temp_dir().join("jarvis-test-token")));
+        sink
+    }
+
+    fn events(&self, event: &str) -> Vec<serde_json::Value> {
+        let events = self.events.lock().unwrap();
+        events.iter().filter(|(name, _)| name == event).map(|(_, payload)| payload.clone()).collect()
+    }
+
+    // First `event` whose payload matches, waiting for it to be emitted
+    async fn wait_for(&self, event: &str, matches: impl Fn(&serde_json::Value) -> bool) -> serde_json::Value {
+        let found = tokio::time::timeout(WAIT, async {
+            loop {
+                if let Some(payload) = self.events(event).into_iter().find(&matches) {
+                    return payload;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        found.unwrap_or_else(|_| panic!("no matching {event} event in {:?}", self.events.lock().unwrap()))
+    }
+
+    async fn wait_for_status(&self, kind: &str) -> serde_json::Value {
+        self.wait_for("agent-status", |status| status["kind"] == kind).await
+    }
+}
+
+fn test_config() -> config::ServerConfig {
+    config::ServerConfig { bind_address: std::net::Ipv4Addr::LOCALHOST.into(), ..Default::default() }
+}
+
+// Run the accept loop on an ephemeral port
+async fn serve(sink: &RecordingSink, listener: impl AgentListener) {
+    let state = sink.app_state();
+    let serving = accept_loop(sink.clone(), listener, None, state.connections.clone(), state.shutdown.subscribe());
+    tokio::spawn(serving);
+}
+
+async fn listen(sink: &RecordingSink) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    serve(sink, listener).await;
+    addr
+}
+
+// Open a WebSocket without authenticating
+async fn open(addr: SocketAddr) -> Client {
+    let (client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}")).await.unwrap();
+    client
+}
+
+// Connect as a version 1 agent and return the client with its connection id
+async fn connect(sink: &RecordingSink, addr: SocketAddr) -> (Client, u64) {
+    let mut client = open(addr).await;
+    let auth = serde_json::json!({"type": "auth", "token": TOKEN, "protocol_version": 1});
+    client.send(Message::Text(auth.to_string())).await.unwrap();
+    let Message::Text(session) = next_frame(&mut client).await else { panic!("expected the session frame") };
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&session).unwrap()["type"], "session");
+
+    // Connections come up one at a time here, so the newest id is ours
+    let id = sink.app_state().connections.lock().await.entries.keys().copied().max().unwrap();
+    sink.wait_for("agent-status", |status| status["kind"] == "connected" && status["id"] == id).await;
+    (client, id)
+}
+
+// Next frame from the server, skipping keepalives
+async fn next_frame(client: &mut Client) -> Message {
+    loop {
+        match tokio::time::timeout(WAIT, client.next()).await.expect("no frame in time") {
+            Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+            Some(Ok(message)) => return message,
+            other => panic!("connection ended: {other:?}"),
+        }
+    }
+}
+
+async fn expect_close(client: &mut Client, code: CloseCode) -> String {
+    match next_frame(client).await {
+        Message::Close(Some(frame)) => {
+            assert_eq!(frame.code, code);
+            frame.reason.into_owned()
+        }
+        other => panic!("expected a close frame, got {other:?}"),
+    }
+}
+
+fn agent_message(content: &str) -> Message {
+    let message = serde_json::json!({
+        "role": "assistant",
+        "content": content,
+        "timestamp": "2026-01-01T00:00:00Z",
+    });
+    Message::Text(message.to_string())
+}
+
+async fn wait_for_message(sink: &RecordingSink, content: &str) {
+    sink.wait_for("agent-message", |message| message["content"] == content).await;
+}
+
+#[tokio::test]
+async fn routes_frames_to_events() {
+    let sink = RecordingSink::new(test_config());
+    let addr = listen(&sink).await;
+    let (mut client, id) = connect(&sink, addr).await;
+
+    client.send(agent_message("hello")).await.unwrap();
+    wait_for_message(&sink, "hello").await;
+    assert_eq!(sink.app_state().history.lock().unwrap().len(), 1);
+
+    let queue = serde_json::json!({"type": "pending_queue", "messages": []});
+    client.send(Message::Text(queue.to_string())).await.unwrap();
+    sink.wait_for("pending-messages", |messages| messages == &serde_json::json!([])).await;
+
+    client.send(Message::Text("{not json".to_string())).await.unwrap();
+    let error = sink.wait_for("agent-error", |error| !error["parse_error"].is_null()).await;
+    assert_eq!(error["parse_error"]["connection_id"], id);
+
+    client.close(None).await.unwrap();
+    let disconnected = sink.wait_for_status("disconnected").await;
+    assert_eq!(disconnected["reason"], "closed by agent");
+}
+
+#[tokio::test]
+async fn refuses_a_bad_token() {
+    let sink = RecordingSink::new(test_config());
+    let addr = listen(&sink).await;
+    let mut client = open(addr).await;
+    let auth = serde_json::json!({"type": "auth", "token": "wrong"});
+    client.send(Message::Text(auth.to_string())).await.unwrap();
+
+    assert_eq!(expect_close(&mut client, CloseCode::Policy).await, "authentication failed");
+    assert!(sink.events("agent-status").iter().all(|status| status["kind"] != "connected"));
+}
+
+#[tokio::test]
+async fn closes_on_an_oversize_frame() {
+    let sink = RecordingSink::new(config::ServerConfig { max_message_size: 1024, ..test_config() });
+    let addr = listen(&sink).await;
+    let (mut client, id) = connect(&sink, addr).await;
+
+    client.send(Message::Text("a".repeat(4096))).await.unwrap();
+    assert_eq!(expect_close(&mut client, CloseCode::Size).await, "message too large");
+    let warning = sink.wait_for("agent-warning", |warning| warning["kind"] == "oversize_frame").await;
+    assert_eq!((warning["id"].as_u64(), warning["limit"].as_u64()), (Some(id), Some(1024)));
+    assert_eq!(sink.wait_for_status("disconnected").await["reason"], "message too large");
+}
+
+#[tokio::test]
+async fn drops_a_peer_that_stopped_reading() {
+    let config = config::ServerConfig { send_timeout_ms: 100, send_timeouts_before_drop: 2, ..test_config() };
+    let sink = RecordingSink::new(config.clone());
+    let addr = listen(&sink).await;
+    // Never read from again, so the socket buffers fill up
+    let (_client, id) = connect(&sink, addr).await;
+
+    let chunk = "x".repeat(256 * 1024);
+    let mut timeouts = Vec::new();
+    for _ in 0..1000 {
+        let sent = sink.app_state().connections.lock().await.send(id, Message::Text(chunk.clone()), &config).await;
+        match check_send(&sink, sent) {
+            Ok(()) => {}
+            Err(SendError::TimedOut { timeouts: n, .. }) => timeouts.push(n),
+            Err(SendError::NotConnected) => break,
+            Err(SendError::Failed(e)) => panic!("send failed: {e}"),
+        }
+    }
+
+    assert_eq!(timeouts, [1, 2]);
+    let warning = sink.wait_for("agent-warning", |warning| warning["kind"] == "slow_consumer").await;
+    assert_eq!(warning["id"], id);
+    assert_eq!(sink.wait_for_status("disconnected").await["reason"], "send timed out");
+}
+
+// Fails the first accepts with the queued errors, then accepts for real
+struct FlakyListener {
+    inner: TcpListener,
+    errors: Vec<std::io::Error>,
+}
+
+impl From<TcpListener> for FlakyListener {
+    fn from(inner: TcpListener) -> Self {
+        Self { inner, errors: Vec::new() }
+    }
+}
+
+impl AgentListener for FlakyListener {
+    fn accept(&mut self) -> impl std::future::Future<Output = std::io::Result<(TcpStream, SocketAddr)>> + Send {
+        let error = self.errors.pop();
+        let inner = &self.inner;
+        async move {
+            match error {
+                Some(e) => Err(e),
+                None => inner.accept().await,
+            }
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+async fn listen_flaky(sink: &RecordingSink, errors: Vec<std::io::Error>) -> SocketAddr {
+    let inner = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = inner.local_addr().unwrap();
+    serve(sink, FlakyListener { inner, errors }).await;
+    addr
+}
+
+#[tokio::test]
+async fn keeps_accepting_after_transient_errors() {
+    use std::io::ErrorKind;
+
+    let sink = RecordingSink::new(test_config());
+    let errors = vec![ErrorKind::ConnectionAborted.into(), ErrorKind::ConnectionReset.into(), ErrorKind::Interrupted.into()];
+    let addr = listen_flaky(&sink, errors).await;
+
+    let (mut client, _) = connect(&sink, addr).await;
+    client.send(agent_message("after errors")).await.unwrap();
+    wait_for_message(&sink, "after errors").await;
+    assert!(sink.events("agent-status").iter().all(|status| status["kind"] != "error"));
+}
+
+#[tokio::test]
+async fn rebinds_after_a_fatal_accept_error() {
+    let sink = RecordingSink::new(test_config());
+    let addr = listen_flaky(&sink, vec![std::io::ErrorKind::PermissionDenied.into()]).await;
+
+    sink.wait_for_status("error").await;
+    let listening = sink.wait_for_status("listening").await;
+    assert_eq!(listening["port"], addr.port());
+    let (mut client, _) = connect(&sink, addr).await;
+    client.send(agent_message("after rebind")).await.unwrap();
+    wait_for_message(&sink, "after rebind").await;
+}
+
+#[tokio::test]
+async fn closes_on_a_text_frame_with_invalid_utf8() {
+    let sink = RecordingSink::new(test_config());
+    let addr = listen(&sink).await;
+    let (mut client, _) = connect(&sink, addr).await;
+
+    let invalid = Frame::message(vec![b'{', 0xff, 0xfe, b'}'], OpCode::Data(Data::Text), true);
+    client.send(Message::Frame(invalid)).await.unwrap();
+    assert_eq!(expect_close(&mut client, CloseCode::Invalid).await, "invalid UTF-8");
+    let error = sink.wait_for("agent-error", |error| error["message"].as_str().unwrap().contains("UTF-8")).await;
+    // Not mistaken for a JSON parse error
+    assert!(error["parse_error"].is_null());
+    assert!(sink.events("agent-message").is_empty());
+
+    let disconnected = sink.wait_for_status("disconnected").await;
+    assert_eq!((disconnected["reason"].as_str(), disconnected["code"].as_u64()), (Some("invalid UTF-8"), Some(1007)));
+}
+
+#[tokio::test]
+async fn closes_an_idle_connection() {
+    let sink = RecordingSink::new(config::ServerConfig { idle_disconnect_ms: 300, ..test_config() });
+    let addr = listen(&sink).await;
+    let (mut client, _) = connect(&sink, addr).await;
+
+    // Traffic pushes the deadline back
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    client.send(agent_message("keep alive")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(sink.events("agent-status").iter().all(|status| status["kind"] != "disconnected"));
+
+    let started = Instant::now();
+    assert_eq!(expect_close(&mut client, CloseCode::Normal).await, "idle");
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(sink.wait_for_status("disconnected").await["reason"], "idle");
+}
+
+#[tokio::test]
+async fn refuses_connections_over_the_limit() {
+    let sink = RecordingSink::new(test_config());
+    sink.app_state().connection_limit.set_max(2);
+    let addr = listen(&sink).await;
+    let (mut first, _) = connect(&sink, addr).await;
+    let (mut second, _) = connect(&sink, addr).await;
+
+    let mut third = open(addr).await;
+    assert_eq!(expect_close(&mut third, CloseCode::Again).await, "too many connections");
+    let warning = sink.wait_for("agent-warning", |warning| warning["kind"] == "connection_limit").await;
+    assert_eq!(warning["max"], 2);
+
+    // The connections already open are unaffected
+    first.send(agent_message("from first")).await.unwrap();
+    second.send(agent_message("from second")).await.unwrap();
+    wait_for_message(&sink, "from first").await;
+    wait_for_message(&sink, "from second").await;
+
+    // Closing one frees its slot
+    first.close(None).await.unwrap();
+    sink.wait_for_status("disconnected").await;
+    // Released once the handler returns, just after the status goes out
+    let limit = sink.app_state().connection_limit.clone();
+    tokio::time::timeout(WAIT, limit.acquire()).await.expect("slot not freed");
+    let (mut fourth, _) = connect(&sink, addr).await;
+    fourth.send(agent_message("from fourth")).await.unwrap();
+    wait_for_message(&sink, "from fourth").await;
+}
+
+#[tokio::test]
+async fn delays_frames_over_the_inbound_rate() {
+    let config = config::ServerConfig { rate_limit_per_sec: 10.0, rate_limit_burst: 1, ..test_config() };
+    let sink = RecordingSink::new(config);
+    let addr = listen(&sink).await;
+    let (mut client, _) = connect(&sink, addr).await;
+
+    let started = Instant::now();
+    for i in 0..4 {
+        client.send(agent_message(&format!("m{i}"))).await.unwrap();
+    }
+    wait_for_message(&sink, "m3").await;
+    // One from the burst, then one token every 100ms
+    assert!(started.elapsed() >= Duration::from_millis(250));
+    let contents: Vec<_> = sink.events("agent-message").iter().map(|m| m["content"].clone()).collect();
+    assert_eq!(contents, ["m0", "m1", "m2", "m3"]);
+    assert!(sink.events("agent-warning").iter().any(|w| w["kind"] == "rate_limited" && w["direction"] == "inbound"));
+}
+
+#[tokio::test]
+async fn bind_retries_until_the_port_is_released() {
+    let config = config::ServerConfig { bind_attempts: 10, bind_retry_base_ms: 50, ..test_config() };
+    let sink = RecordingSink::new(config.clone());
+    let occupied = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = occupied.local_addr().unwrap();
+    // Freed while the first backoff is pending
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        drop(occupied);
+    });
+
+    let listener = bind_with_retry(&sink, addr, &config).await.expect("bound once released");
+    assert_eq!(listener.local_addr().unwrap(), addr);
+    let binding = sink.wait_for_status("binding").await;
+    assert_eq!((binding["port"].as_u64(), binding["attempt"].as_u64()), (Some(addr.port().into()), Some(2)));
+
+    serve(&sink, listener).await;
+    let (mut client, _) = connect(&sink, addr).await;
+    client.send(agent_message("bound")).await.unwrap();
+    wait_for_message(&sink, "bound").await;
+}
+
+#[tokio::test]
+async fn bind_gives_up_after_its_attempts() {
+    let config = config::ServerConfig { bind_attempts: 3, bind_retry_base_ms: 10, ..test_config() };
+    let sink = RecordingSink::new(config.clone());
+    let occupied = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let error = bind_with_retry(&sink, occupied.local_addr().unwrap(), &config).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::AddrInUse);
+    assert_eq!(sink.events("agent-status").iter().filter(|status| status["kind"] == "binding").count(), 2);
+}
+
+#[tokio::test]
+async fn negotiates_messagepack_framing() {
+    let sink = RecordingSink::new(test_config());
+    let addr = listen(&sink).await;
+    let mut client = open(addr).await;
+    let auth = serde_json::json!({"type": "auth", "token": TOKEN, "protocol_version": 1, "format": "msgpack"});
+    client.send(Message::Text(auth.to_string())).await.unwrap();
+
+    let Message::Binary(session) = next_frame(&mut client).await else { panic!("expected a binary session frame") };
+    let session: serde_json::Value = msgpack::from_slice(&session).unwrap();
+    assert_eq!((session["type"].as_str(), session["format"].as_str()), (Some("session"), Some("msgpack")));
+    let connected = sink.wait_for_status("connected").await;
+    let id = connected["id"].as_u64().unwrap();
+
+    // Binary frames holding a map are protocol messages
+    let Message::Text(message) = agent_message("packed") else { unreachable!() };
+    let message: serde_json::Value = serde_json::from_str(&message).unwrap();
+    client.send(Message::Binary(msgpack::to_vec(&message).unwrap())).await.unwrap();
+    wait_for_message(&sink, "packed").await;
+
+    // Text frames from the overlay leave as MessagePack
+    let config = test_config();
+    let input = UiMessage { msg_type: "user_input".to_string(), content: "hi".to_string(), id: None, kind: None };
+    let text = Message::Text(serde_json::to_string(&input).unwrap());
+    assert!(sink.app_state().connections.lock().await.send(id, text, &config).await.is_ok());
+    let Message::Binary(frame) = next_frame(&mut client).await else { panic!("expected a binary frame") };
+    let received: UiMessage = msgpack::from_slice(&frame).unwrap();
+    assert_eq!((received.msg_type.as_str(), received.content.as_str()), ("user_input", "hi"));
+
+    // A map marker followed by garbage is reported, not taken for an image
+    client.send(Message::Binary(vec![0x81, 0xc1])).await.unwrap();
+    let error = sink.wait_for("agent-error", |error| error["message"].as_str().is_some()).await;
+    assert!(error["message"].as_str().unwrap().starts_with("Invalid MessagePack frame"), "{error}");
+}
+
+#[tokio::test]
+async fn falls_back_to_json_for_an_unknown_format() {
+    let sink = RecordingSink::new(test_config());
+    let addr = listen(&sink).await;
+    let mut client = open(addr).await;
+    let auth = serde_json::json!({"type": "auth", "token": TOKEN, "format": "cbor"});
+    client.send(Message::Text(auth.to_string())).await.unwrap();
+
+    let Message::Text(session) = next_frame(&mut client).await else { panic!("expected a text session frame") };
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&session).unwrap()["format"], "json");
+    client.send(agent_message("plain")).await.unwrap();
+    wait_for_message(&sink, "plain").await;
+}
@@ -0,0 +1,66 @@
+//! TLS for the Agent Socket
+//!
+//! Optional `wss://` mode for agents running across a trust boundary.
+//! A self-signed certificate for localhost is generated in the app config
+//! dir on first use and reused afterwards; agents pin it by fingerprint.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use tokio_rustls::rustls::{
+    self,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+};
+use tokio_rustls::TlsAcceptor;
+
+use crate::auth;
+
+/// File names of the certificate and key inside the app config dir.
+pub const CERT_FILE: &str = "ws_cert.pem";
+pub const KEY_FILE: &str = "ws_key.pem";
+
+/// A ready acceptor and the SHA-256 fingerprint of its certificate.
+pub struct WsTls {
+    pub acceptor: TlsAcceptor,
+    pub fingerprint: String,
+}
+
+/// Load the certificate and key from `dir`, generating them if missing.
+pub fn load_or_generate(dir: &Path) -> Result<WsTls, String> {
+    let cert_path = dir.join(CERT_FILE);
+    let key_path = dir.join(KEY_FILE);
+
+    if !cert_path.exists() || !key_path.exists() {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()])
+            .map_err(|e| format!("Failed to generate certificate: {}", e))?;
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        fs::write(&cert_path, generated.cert.pem()).map_err(|e| e.to_string())?;
+        auth::write_secret_file(&key_path, &generated.key_pair.serialize_pem()).map_err(|e| e.to_string())?;
+    }
+
+    let certs = CertificateDer::pem_file_iter(&cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read {}: {}", CERT_FILE, e))?;
+    let key = PrivateKeyDer::from_pem_file(&key_path).map_err(|e| format!("Failed to read {}: {}", KEY_FILE, e))?;
+    let fingerprint = certs.first().map(fingerprint).ok_or(format!("No certificate in {}", CERT_FILE))?;
+
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid certificate or key: {}", e))?;
+
+    Ok(WsTls { acceptor: TlsAcceptor::from(Arc::new(config)), fingerprint })
+}
+
+/// SHA-256 of the DER certificate as colon-separated uppercase hex.
+fn fingerprint(cert: &CertificateDer) -> String {
+    Sha256::digest(cert.as_ref())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
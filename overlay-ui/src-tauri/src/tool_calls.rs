@@ -0,0 +1,200 @@
+//! Tool Call Tracking
+//!
+//! Long-running tools report progress by id instead of resending the whole
+//! message. An agent announces a call with a `tool_call` frame, then sends
+//! `tool_call_update` frames that move it Pending → Running → Done/Error
+//! and append partial output. Each becomes an `agent-tool-call` event,
+//! a stream apart from `agent-message` text. Calls carry the `message_id`
+//! of the assistant message they belong to when the agent sends one.
+//!
+//! Tool calls listed as `toolCalls` summaries on a complete message are
+//! also sent on this stream, as finished calls, so the webview never has
+//! to pick them out of the message.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolStatus {
+    Pending,
+    Running,
+    Done,
+    Error,
+}
+
+impl ToolStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, ToolStatus::Done | ToolStatus::Error)
+    }
+}
+
+/// Where an `agent-tool-call` event falls in the call's lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolPhase {
+    Start,
+    Update,
+    /// Last event of the call, whether it succeeded or not.
+    Result,
+}
+
+/// Start of a tool call:
+/// `{"type": "tool_call", "id": "...", "name": "read_file", "arguments": {...}}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolCallMessage {
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+    #[serde(default)]
+    pub message_id: Option<String>,
+}
+
+/// Progress of a tool call:
+/// `{"type": "tool_call_update", "id": "...", "status": "running", "output": "..."}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolCallUpdateMessage {
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub id: String,
+    /// Omitted when only appending output.
+    #[serde(default)]
+    pub status: Option<ToolStatus>,
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+/// Payload of `agent-tool-call` events. `output` holds only the newly
+/// appended text; `arguments` is set on the first event of a call.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallUpdate {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub phase: ToolPhase,
+    pub name: String,
+    pub status: ToolStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<serde_json::Value>,
+}
+
+/// A tool call that hasn't finished yet.
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub name: String,
+    pub status: ToolStatus,
+    /// Connection that started it; the call fails if that agent disconnects.
+    pub connection_id: u64,
+    pub message_id: Option<String>,
+}
+
+/// Unfinished tool calls by id. Finished calls are dropped.
+#[derive(Default)]
+pub struct ToolCalls {
+    calls: HashMap<String, ToolCall>,
+}
+
+impl ToolCalls {
+    pub fn start(&mut self, connection_id: u64, msg: ToolCallMessage) -> ToolCallUpdate {
+        let call = ToolCall {
+            name: msg.name.clone(),
+            status: ToolStatus::Pending,
+            connection_id,
+            message_id: msg.message_id.clone(),
+        };
+        self.calls.insert(msg.id.clone(), call);
+        ToolCallUpdate {
+            id: msg.id,
+            message_id: msg.message_id,
+            phase: ToolPhase::Start,
+            name: msg.name,
+            status: ToolStatus::Pending,
+            output: None,
+            arguments: Some(msg.arguments),
+        }
+    }
+
+    /// Apply an update. Unknown ids and backward transitions are refused.
+    pub fn update(&mut self, msg: ToolCallUpdateMessage) -> Result<ToolCallUpdate, String> {
+        let call = self
+            .calls
+            .get_mut(&msg.id)
+            .ok_or_else(|| format!("Update for unknown tool call {}", msg.id))?;
+        let status = msg.status.unwrap_or(call.status);
+        if status < call.status {
+            return Err(format!("Tool call {} can't go from {:?} back to {:?}", msg.id, call.status, status));
+        }
+        call.status = status;
+        let update = ToolCallUpdate {
+            id: msg.id,
+            message_id: call.message_id.clone(),
+            phase: if status.is_finished() { ToolPhase::Result } else { ToolPhase::Update },
+            name: call.name.clone(),
+            status,
+            output: msg.output,
+            arguments: None,
+        };
+        if status.is_finished() {
+            self.calls.remove(&update.id);
+        }
+        Ok(update)
+    }
+
+    /// Fail every unfinished call started on `connection_id`.
+    pub fn abandon(&mut self, connection_id: u64) -> Vec<ToolCallUpdate> {
+        let ids: Vec<String> = self
+            .calls
+            .iter()
+            .filter(|(_, call)| call.connection_id == connection_id)
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.into_iter()
+            .filter_map(|id| {
+                let call = self.calls.remove(&id)?;
+                Some(ToolCallUpdate {
+                    id,
+                    message_id: call.message_id,
+                    phase: ToolPhase::Result,
+                    name: call.name,
+                    status: ToolStatus::Error,
+                    output: Some("Agent disconnected".to_string()),
+                    arguments: None,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Finished-call events for the `name(arguments)` summaries of a complete
+/// message, with ids derived from the message id.
+pub fn from_summaries(message_id: &str, summaries: &[String]) -> Vec<ToolCallUpdate> {
+    summaries
+        .iter()
+        .enumerate()
+        .map(|(i, summary)| {
+            let (name, arguments) = match summary.split_once('(') {
+                Some((name, rest)) => {
+                    let args = rest.strip_suffix(')').unwrap_or(rest);
+                    let value = serde_json::from_str(args).unwrap_or_else(|_| serde_json::Value::String(args.to_string()));
+                    (name.to_string(), Some(value))
+                }
+                None => (summary.clone(), None),
+            };
+            ToolCallUpdate {
+                id: format!("{}:{}", message_id, i),
+                message_id: Some(message_id.to_string()),
+                phase: ToolPhase::Result,
+                name,
+                status: ToolStatus::Done,
+                output: None,
+                arguments,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,80 @@
+//! Transcript Export
+//!
+//! Writes the message history to a file the user can keep: Markdown for
+//! reading, with tool calls as fenced blocks, or the raw JSON messages.
+
+use crate::AgentMessage;
+
+/// Directory inside the app data dir for exports without a path.
+pub const TRANSCRIPT_DIR: &str = "transcripts";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Json,
+}
+
+impl Format {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(Format::Markdown),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unsupported transcript format {:?}; use \"markdown\" or \"json\"", format)),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Json => "json",
+        }
+    }
+}
+
+pub fn render(format: Format, messages: &[AgentMessage]) -> Result<String, serde_json::Error> {
+    match format {
+        Format::Markdown => Ok(markdown(messages)),
+        Format::Json => serde_json::to_string_pretty(messages),
+    }
+}
+
+fn markdown(messages: &[AgentMessage]) -> String {
+    let mut out = format!("# Jarvis transcript\n\nExported {}\n", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+    for message in messages {
+        out.push_str(&format!("\n## {} · {}\n\n", role_label(&message.role), message.timestamp));
+        if !message.content.trim().is_empty() {
+            out.push_str(message.content.trim_end());
+            out.push('\n');
+        }
+        if let Some(calls) = message.tool_calls.as_ref().filter(|calls| !calls.is_empty()) {
+            let body = calls.join("\n");
+            let fence = fence_for(&body);
+            out.push_str(&format!("\n{}tool-calls\n{}\n{}\n", fence, body, fence));
+        }
+        if let Some(attachments) = message.attachments.as_ref().filter(|a| !a.is_empty()) {
+            out.push('\n');
+            for attachment in attachments {
+                out.push_str(&format!("- Attachment: `{}`\n", attachment));
+            }
+        }
+    }
+    out
+}
+
+fn role_label(role: &str) -> String {
+    let mut chars = role.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => "Unknown".to_string(),
+    }
+}
+
+/// A backtick fence longer than any run of backticks in `body`.
+fn fence_for(body: &str) -> String {
+    let longest = body
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
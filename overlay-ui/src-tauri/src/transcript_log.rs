@@ -0,0 +1,293 @@
+//! Transcript Log
+//!
+//! Optional on-disk copy of every completed message, so history older than
+//! the in-memory buffer can still be paged through. Messages are appended
+//! to `current.jsonl` as newline-delimited JSON. The file is gzipped into
+//! an archive when it passes the size limit and when logging starts again,
+//! e.g. in a new session. Only the newest `MAX_ARCHIVES` archives are kept.
+//!
+//! A crash can leave a partial last line; unreadable lines are skipped.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::AgentMessage;
+
+/// Directory of the log inside the app data dir.
+pub const LOG_DIR: &str = "transcript_log";
+
+/// Largest `max_size_mb` accepted.
+pub const MAX_SIZE_MB: u64 = 1024;
+
+const CURRENT_FILE: &str = "current.jsonl";
+const ARCHIVE_PREFIX: &str = "transcript-";
+const ARCHIVE_SUFFIX: &str = ".jsonl.gz";
+const MAX_ARCHIVES: usize = 20;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranscriptLogging {
+    pub enabled: bool,
+    /// Size of `current.jsonl` at which it is archived.
+    pub max_size_mb: u64,
+}
+
+impl Default for TranscriptLogging {
+    fn default() -> Self {
+        Self { enabled: false, max_size_mb: 10 }
+    }
+}
+
+pub struct TranscriptLog {
+    dir: PathBuf,
+    max_bytes: u64,
+    file: File,
+    size: u64,
+}
+
+impl TranscriptLog {
+    /// Start logging to `dir`, archiving whatever an earlier run left.
+    pub fn open(dir: &Path, max_size_mb: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let current = dir.join(CURRENT_FILE);
+        if fs::metadata(&current).is_ok_and(|meta| meta.len() > 0) {
+            archive(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&current)?;
+        Ok(Self { dir: dir.to_path_buf(), max_bytes: max_size_mb * 1024 * 1024, file, size: 0 })
+    }
+
+    pub fn set_max_size(&mut self, max_size_mb: u64) {
+        self.max_bytes = max_size_mb * 1024 * 1024;
+    }
+
+    pub fn append(&mut self, message: &AgentMessage) -> io::Result<()> {
+        let mut line = serde_json::to_string(message).map_err(io::Error::other)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        if self.size >= self.max_bytes {
+            archive(&self.dir)?;
+            self.size = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Gzip `current.jsonl` into a new archive, empty it and prune old archives.
+fn archive(dir: &Path) -> io::Result<()> {
+    let current = dir.join(CURRENT_FILE);
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let mut path = dir.join(format!("{}{}{}", ARCHIVE_PREFIX, stamp, ARCHIVE_SUFFIX));
+    // Names sort by time; a second archive within the same second gets a suffix
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("{}{}-{}{}", ARCHIVE_PREFIX, stamp, n, ARCHIVE_SUFFIX));
+        n += 1;
+    }
+
+    let mut encoder = GzEncoder::new(File::create(&path)?, Compression::default());
+    io::copy(&mut File::open(&current)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    // Truncate rather than remove: the log may still hold it open for appending
+    File::create(&current)?;
+
+    let archives = archives(dir)?;
+    for old in archives.iter().take(archives.len().saturating_sub(MAX_ARCHIVES)) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Archive paths, oldest first.
+fn archives(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(ARCHIVE_PREFIX) && name.ends_with(ARCHIVE_SUFFIX))
+            })
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    paths.sort_by_key(|path| archive_order(path));
+    Ok(paths)
+}
+
+/// Sort key of an archive: its timestamp, then the same-second suffix. Plain
+/// name order would put `-1` before the unsuffixed archive and `-10` before `-2`.
+fn archive_order(path: &Path) -> (String, u32) {
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let stem = name.trim_start_matches(ARCHIVE_PREFIX).trim_end_matches(ARCHIVE_SUFFIX);
+    // The stamp itself holds one dash, between date and time
+    match stem.rsplit_once('-').filter(|(stamp, _)| stamp.contains('-')) {
+        Some((stamp, n)) => (stamp.to_string(), n.parse().unwrap_or(0)),
+        None => (stem.to_string(), 0),
+    }
+}
+
+/// Up to `limit` messages in order, skipping the `offset` most recent ones,
+/// read from the current file and then the archives as far back as needed.
+/// Each file is streamed twice, once to count its messages and once to pick
+/// out the ones on the page, so only the page is held in memory.
+pub fn load_page(dir: &Path, offset: usize, limit: usize) -> io::Result<Vec<AgentMessage>> {
+    let mut files = archives(dir)?;
+    files.push(dir.join(CURRENT_FILE));
+
+    let mut skip = offset;
+    let mut found = 0;
+    // The page's messages from each file, newest file first
+    let mut runs = Vec::new();
+    for path in files.iter().rev() {
+        if found == limit {
+            break;
+        }
+        let mut count = 0;
+        read_messages(path, |_| {
+            count += 1;
+            ControlFlow::Continue(())
+        })?;
+        if count <= skip {
+            skip -= count;
+            continue;
+        }
+        let end = count - skip;
+        let start = end.saturating_sub(limit - found);
+        skip = 0;
+
+        let mut run = Vec::with_capacity(end - start);
+        let mut index = 0;
+        read_messages(path, |message| {
+            if index >= start {
+                run.push(message);
+            }
+            index += 1;
+            if index < end {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        })?;
+        found += run.len();
+        runs.push(run);
+    }
+    Ok(runs.into_iter().rev().flatten().collect())
+}
+
+/// Stream the readable messages in `path` to `visit`, oldest first, until
+/// it breaks.
+fn read_messages(path: &Path, mut visit: impl FnMut(AgentMessage) -> ControlFlow<()>) -> io::Result<()> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let reader: Box<dyn BufRead> = if path.extension().is_some_and(|ext| ext == "gz") {
+        Box::new(BufReader::new(GzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    for line in reader.lines() {
+        match line {
+            Ok(line) if line.trim().is_empty() => {}
+            Ok(line) => match serde_json::from_str(&line) {
+                Ok(message) => {
+                    if visit(message).is_break() {
+                        break;
+                    }
+                }
+                Err(e) => tracing::debug!(path = %path.display(), error = %e, "Skipping unreadable transcript line"),
+            },
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                tracing::debug!(path = %path.display(), "Skipping transcript line that is not UTF-8");
+            }
+            // A truncated archive: keep what could be read
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Transcript file is cut short");
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(i: usize) -> AgentMessage {
+        serde_json::from_value(serde_json::json!({
+            "role": "assistant",
+            "content": format!("message {i}"),
+            "timestamp": "2026-01-01T00:00:00Z",
+            "toolCalls": null,
+            "attachments": null,
+        }))
+        .unwrap()
+    }
+
+    fn contents(page: &[AgentMessage]) -> Vec<String> {
+        page.iter().map(|message| message.content.clone()).collect()
+    }
+
+    fn expected(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|i| contents(&[message(i)]).remove(0)).collect()
+    }
+
+    #[test]
+    fn pages_across_rotated_archives() {
+        let dir = std::env::temp_dir().join(format!("jarvis-transcript-{}", crate::auth::random_hex(8)));
+        let line_len = serde_json::to_string(&message(0)).unwrap().len() as u64 + 1;
+        let mut log = TranscriptLog::open(&dir, 1).unwrap();
+        // Five messages per archive; all within a second, so suffixes kick in
+        log.max_bytes = line_len * 5;
+        for i in 0..28 {
+            log.append(&message(i)).unwrap();
+        }
+        drop(log);
+        assert_eq!(archives(&dir).unwrap().len(), 5);
+
+        assert_eq!(contents(&load_page(&dir, 0, 10).unwrap()), expected(18..28));
+        assert_eq!(contents(&load_page(&dir, 3, 7).unwrap()), expected(18..25));
+        assert_eq!(contents(&load_page(&dir, 20, 100).unwrap()), expected(0..8));
+        assert!(load_page(&dir, 28, 10).unwrap().is_empty());
+        assert!(load_page(&dir, 0, 0).unwrap().is_empty());
+
+        // Reopening archives the leftover current file behind the others
+        let log = TranscriptLog::open(&dir, 1).unwrap();
+        drop(log);
+        assert_eq!(archives(&dir).unwrap().len(), 6);
+        assert_eq!(contents(&load_page(&dir, 0, 28).unwrap()), expected(0..28));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn orders_same_second_archives_by_suffix() {
+        let names = ["20260101-120000-10", "20260101-120000-2", "20260101-120000", "20260101-115959-1"];
+        let mut paths: Vec<PathBuf> =
+            names.iter().map(|stem| PathBuf::from(format!("{ARCHIVE_PREFIX}{stem}{ARCHIVE_SUFFIX}"))).collect();
+        paths.sort_by_key(|path| archive_order(path));
+        let sorted: Vec<_> = paths.iter().map(|path| archive_order(path)).collect();
+        assert_eq!(
+            sorted,
+            [
+                ("20260101-115959".to_string(), 1),
+                ("20260101-120000".to_string(), 0),
+                ("20260101-120000".to_string(), 2),
+                ("20260101-120000".to_string(), 10),
+            ]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Turn Metrics
+//!
+//! Times each user turn from the `user_input` frame to the agent's first
+//! output and to its end. The protocol doesn't tie output to an input, so
+//! output is credited to the latest input: the first delta or assistant
+//! message is the first token, and a `done` delta or the agent reporting
+//! idle ends the turn. A new input ends the turn before it.
+
+use std::time::Instant;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TurnOutcome {
+    Completed,
+    /// The user stopped the agent.
+    Cancelled,
+    /// The agent reported an error or disconnected.
+    Failed,
+    /// Another input was sent before this turn ended.
+    Superseded,
+}
+
+/// Payload of `turn-metrics` events. Times are missing for stages the
+/// turn never reached.
+#[derive(Debug, Clone, Serialize)]
+pub struct TurnMetrics {
+    pub input_id: String,
+    pub ttft_ms: Option<u64>,
+    pub total_ms: u64,
+    pub outcome: TurnOutcome,
+}
+
+struct Turn {
+    input_id: String,
+    sent_at: Instant,
+    first_token_at: Option<Instant>,
+}
+
+#[derive(Default)]
+pub struct TurnTracker {
+    current: Option<Turn>,
+    last: Option<TurnMetrics>,
+}
+
+impl TurnTracker {
+    /// Start timing a turn; returns the metrics of the one it supersedes.
+    pub fn start(&mut self, input_id: String, now: Instant) -> Option<TurnMetrics> {
+        let superseded = self.finish(TurnOutcome::Superseded, now);
+        self.current = Some(Turn { input_id, sent_at: now, first_token_at: None });
+        superseded
+    }
+
+    /// The agent produced output for the current turn.
+    pub fn output(&mut self, now: Instant) {
+        if let Some(turn) = &mut self.current {
+            turn.first_token_at.get_or_insert(now);
+        }
+    }
+
+    /// End the current turn, if any, and return its metrics.
+    pub fn finish(&mut self, outcome: TurnOutcome, now: Instant) -> Option<TurnMetrics> {
+        let turn = self.current.take()?;
+        let millis = |at: Instant| at.duration_since(turn.sent_at).as_millis() as u64;
+        let metrics = TurnMetrics {
+            ttft_ms: turn.first_token_at.map(millis),
+            total_ms: millis(now),
+            input_id: turn.input_id,
+            outcome,
+        };
+        self.last = Some(metrics.clone());
+        Some(metrics)
+    }
+
+    pub fn last(&self) -> Option<&TurnMetrics> {
+        self.last.as_ref()
+    }
+}
@@ -0,0 +1,351 @@
+//! Window Layout
+//!
+//! Saves the main window's position and size to `window_layout.json` in the
+//! app config dir and restores it on launch; other windows get a file of
+//! their own. Restored layouts are clamped to the connected monitors so a
+//! window saved on a now-disconnected display doesn't end up off-screen.
+//!
+//! `snap` docks the window to an edge or corner of a monitor's work area.
+//! `fit_to_content` sizes it to the webview's content, growing away from
+//! the corner it is docked in.
+//!
+//! On monitors with different scale factors the platform keeps the window's
+//! logical size as it moves between them. The saved layout records the
+//! scale it was taken at so `restore` can do the same, `snap` places the
+//! window by the size it will have on the target monitor, and
+//! `keep_on_monitor` pulls it back into the work area after it rescales.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::{Monitor, PhysicalPosition, PhysicalSize, WebviewWindow};
+use tauri_runtime::ResizeDirection;
+
+use crate::animation::Motion;
+
+/// File name of the layout inside the app config dir.
+pub const LAYOUT_FILE: &str = "window_layout.json";
+
+/// File name of the layout of the window with `label`.
+pub fn layout_file(label: &str) -> String {
+    if label == "main" {
+        LAYOUT_FILE.to_string()
+    } else {
+        format!("window_layout.{}.json", label)
+    }
+}
+
+/// Gap between the window and the work area edge for the default placement.
+const DEFAULT_MARGIN: f64 = 20.0;
+
+/// Size limits for `fit_to_content`, in logical pixels.
+const MIN_CONTENT_WIDTH: f64 = 280.0;
+const MIN_CONTENT_HEIGHT: f64 = 120.0;
+const MAX_CONTENT_WIDTH: f64 = 720.0;
+const MAX_CONTENT_HEIGHT: f64 = 900.0;
+
+/// Length of the `animate_to` transition.
+const RESIZE_DURATION: Duration = Duration::from_millis(150);
+
+/// Window geometry in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowLayout {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A layout as saved, with the scale factor it was taken at.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SavedLayout {
+    #[serde(flatten)]
+    pub layout: WindowLayout,
+    /// Missing from layouts saved before scale awareness.
+    #[serde(default)]
+    pub scale: Option<f64>,
+}
+
+pub fn load(path: &Path) -> Option<SavedLayout> {
+    let text = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+pub fn save(path: &Path, layout: &WindowLayout, scale: f64) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let saved = SavedLayout { layout: *layout, scale: Some(scale) };
+    let json = serde_json::to_string_pretty(&saved).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+/// Where `snap` places the window within a monitor's work area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl SnapPosition {
+    /// Parse a position name such as `TopRight` (case-insensitive).
+    pub fn parse(name: &str) -> Option<Self> {
+        const ALL: [(&str, SnapPosition); 9] = [
+            ("TopLeft", SnapPosition::TopLeft),
+            ("Top", SnapPosition::Top),
+            ("TopRight", SnapPosition::TopRight),
+            ("Left", SnapPosition::Left),
+            ("Center", SnapPosition::Center),
+            ("Right", SnapPosition::Right),
+            ("BottomLeft", SnapPosition::BottomLeft),
+            ("Bottom", SnapPosition::Bottom),
+            ("BottomRight", SnapPosition::BottomRight),
+        ];
+        ALL.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, p)| *p)
+    }
+
+    // Horizontal and vertical alignment: 0 = start, 1 = middle, 2 = end
+    fn alignment(self) -> (u8, u8) {
+        match self {
+            SnapPosition::TopLeft => (0, 0),
+            SnapPosition::Top => (1, 0),
+            SnapPosition::TopRight => (2, 0),
+            SnapPosition::Left => (0, 1),
+            SnapPosition::Center => (1, 1),
+            SnapPosition::Right => (2, 1),
+            SnapPosition::BottomLeft => (0, 2),
+            SnapPosition::Bottom => (1, 2),
+            SnapPosition::BottomRight => (2, 2),
+        }
+    }
+}
+
+/// Parse the edge or corner to resize from, such as `SouthEast` (case-insensitive).
+pub fn resize_direction(edge: &str) -> Option<ResizeDirection> {
+    const ALL: [(&str, ResizeDirection); 8] = [
+        ("North", ResizeDirection::North),
+        ("NorthEast", ResizeDirection::NorthEast),
+        ("East", ResizeDirection::East),
+        ("SouthEast", ResizeDirection::SouthEast),
+        ("South", ResizeDirection::South),
+        ("SouthWest", ResizeDirection::SouthWest),
+        ("West", ResizeDirection::West),
+        ("NorthWest", ResizeDirection::NorthWest),
+    ];
+    ALL.iter().find(|(n, _)| n.eq_ignore_ascii_case(edge)).map(|(_, d)| *d)
+}
+
+/// Read the window's current geometry.
+pub fn capture(window: &WebviewWindow) -> tauri::Result<WindowLayout> {
+    let position = window.outer_position()?;
+    let size = window.inner_size()?;
+    Ok(WindowLayout {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+/// Restore a saved layout, or place the window at the default position
+/// when there is none or it no longer fits on any monitor. The size is
+/// rescaled when the monitor's scale factor changed since it was saved.
+pub fn restore(window: &WebviewWindow, saved: Option<SavedLayout>) -> tauri::Result<()> {
+    match saved.and_then(|saved| clamp_to_monitors(window, saved)) {
+        // Moving first lets a scale change happen before the size is set
+        Some(layout) => {
+            window.set_position(PhysicalPosition::new(layout.x, layout.y))?;
+            window.set_size(PhysicalSize::new(layout.width, layout.height))
+        }
+        None => place_default(window),
+    }
+}
+
+/// Bottom-right corner of the primary monitor's work area.
+pub fn place_default(window: &WebviewWindow) -> tauri::Result<()> {
+    let Some(monitor) = window.primary_monitor()? else {
+        return window.center();
+    };
+    let area = monitor.work_area();
+    let size = window.outer_size()?;
+    let margin = (DEFAULT_MARGIN * monitor.scale_factor()) as i32;
+
+    let x = area.position.x + area.size.width as i32 - size.width as i32 - margin;
+    let y = area.position.y + area.size.height as i32 - size.height as i32 - margin;
+    window.set_position(PhysicalPosition::new(x, y))
+}
+
+/// Move the window to `position` within the work area (menu bar, dock and
+/// taskbar excluded) of a monitor. `monitor` indexes `available_monitors`;
+/// `None` uses the monitor under the cursor.
+pub fn snap(window: &WebviewWindow, position: SnapPosition, monitor: Option<usize>) -> Result<(), String> {
+    let monitor = match monitor {
+        Some(index) => {
+            let mut monitors = window.available_monitors().map_err(|e| e.to_string())?;
+            let count = monitors.len();
+            if index >= count {
+                return Err(format!("Monitor index {} out of range ({} connected)", index, count));
+            }
+            monitors.swap_remove(index)
+        }
+        None => cursor_monitor(window).ok_or("No monitor found")?,
+    };
+
+    // The size the window rescales to on a monitor of another scale
+    let ratio = monitor.scale_factor() / window.scale_factor().map_err(|e| e.to_string())?;
+    let area = monitor.work_area();
+    let size = rescale(window.outer_size().map_err(|e| e.to_string())?, ratio);
+    let margin = (DEFAULT_MARGIN * monitor.scale_factor()) as i32;
+    let free_x = area.size.width as i32 - size.width as i32 - 2 * margin;
+    let free_y = area.size.height as i32 - size.height as i32 - 2 * margin;
+
+    let (h, v) = position.alignment();
+    let x = area.position.x + margin + free_x * h as i32 / 2;
+    let y = area.position.y + margin + free_y * v as i32 / 2;
+    window.set_position(PhysicalPosition::new(x, y)).map_err(|e| e.to_string())
+}
+
+/// Geometry that fits content of `width` x `height` logical pixels, clamped
+/// to the size limits and the current monitor's work area. The edges
+/// nearest the work area's edges stay put, so a window docked top-right
+/// grows downward and one docked bottom-right grows upward.
+pub fn fit_to_content(window: &WebviewWindow, width: f64, height: f64) -> Result<WindowLayout, String> {
+    anchored(
+        window,
+        width.clamp(MIN_CONTENT_WIDTH, MAX_CONTENT_WIDTH),
+        height.clamp(MIN_CONTENT_HEIGHT, MAX_CONTENT_HEIGHT),
+    )
+}
+
+/// Geometry of `width` x `height` logical pixels, clamped to the current
+/// monitor's work area, keeping the corner nearest the work area's corner.
+pub fn anchored(window: &WebviewWindow, width: f64, height: f64) -> Result<WindowLayout, String> {
+    let current = capture(window).map_err(|e| e.to_string())?;
+    let monitor = window
+        .current_monitor()
+        .ok()
+        .flatten()
+        .or_else(|| window.primary_monitor().ok().flatten())
+        .ok_or("No monitor found")?;
+
+    let area = monitor.work_area();
+    let scale = monitor.scale_factor();
+    let width = ((width * scale) as u32).min(area.size.width);
+    let height = ((height * scale) as u32).min(area.size.height);
+
+    let anchor_right = current.x + current.width as i32 / 2 > area.position.x + area.size.width as i32 / 2;
+    let anchor_bottom = current.y + current.height as i32 / 2 > area.position.y + area.size.height as i32 / 2;
+    let x = if anchor_right { current.x + current.width as i32 - width as i32 } else { current.x };
+    let y = if anchor_bottom { current.y + current.height as i32 - height as i32 } else { current.y };
+
+    Ok(WindowLayout {
+        x: x.clamp(area.position.x, area.position.x + (area.size.width - width) as i32),
+        y: y.clamp(area.position.y, area.position.y + (area.size.height - height) as i32),
+        width,
+        height,
+    })
+}
+
+/// Ease the window from its current geometry to `target`.
+pub fn animate_to(window: WebviewWindow, target: WindowLayout, motion: Motion) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let Ok(start) = capture(&window) else { return };
+        let steps = motion.steps(RESIZE_DURATION);
+        let mut tick = tokio::time::interval(RESIZE_DURATION / steps);
+        for step in 1..=steps {
+            tick.tick().await;
+            // Ease out so the motion settles rather than stopping abruptly
+            let t = 1.0 - (1.0 - step as f64 / steps as f64).powi(3);
+            let lerp = |from: i64, to: i64| from + ((to - from) as f64 * t).round() as i64;
+            let _ = window.set_size(PhysicalSize::new(
+                lerp(start.width.into(), target.width.into()) as u32,
+                lerp(start.height.into(), target.height.into()) as u32,
+            ));
+            let _ = window.set_position(PhysicalPosition::new(
+                lerp(start.x.into(), target.x.into()) as i32,
+                lerp(start.y.into(), target.y.into()) as i32,
+            ));
+        }
+    })
+}
+
+/// Move the window back inside its monitor's work area after a scale
+/// change resized it to `inner`; it grows from its top-left corner and may
+/// spill past the edge it was docked to.
+pub fn keep_on_monitor(window: &WebviewWindow, inner: PhysicalSize<u32>) -> tauri::Result<()> {
+    let Some(monitor) = window.current_monitor()? else { return Ok(()) };
+    let (position, outer, current) = (window.outer_position()?, window.outer_size()?, window.inner_size()?);
+    // Decorations keep their physical size; only the content was rescaled
+    let width = inner.width + outer.width.saturating_sub(current.width);
+    let height = inner.height + outer.height.saturating_sub(current.height);
+
+    let area = monitor.work_area();
+    let x = position.x.min(area.position.x + area.size.width as i32 - width as i32).max(area.position.x);
+    let y = position.y.min(area.position.y + area.size.height as i32 - height as i32).max(area.position.y);
+    if (x, y) != (position.x, position.y) {
+        window.set_position(PhysicalPosition::new(x, y))?;
+    }
+    Ok(())
+}
+
+fn rescale(size: PhysicalSize<u32>, ratio: f64) -> PhysicalSize<u32> {
+    PhysicalSize::new((size.width as f64 * ratio).round() as u32, (size.height as f64 * ratio).round() as u32)
+}
+
+fn cursor_monitor(window: &WebviewWindow) -> Option<Monitor> {
+    let under_cursor = window
+        .cursor_position()
+        .ok()
+        .and_then(|cursor| window.monitor_from_point(cursor.x, cursor.y).ok().flatten());
+    under_cursor
+        .or_else(|| window.current_monitor().ok().flatten())
+        .or_else(|| window.primary_monitor().ok().flatten())
+}
+
+/// Fit the layout inside the monitor it overlaps most. Returns `None`
+/// when it doesn't overlap any connected monitor.
+fn clamp_to_monitors(window: &WebviewWindow, saved: SavedLayout) -> Option<WindowLayout> {
+    let monitors = window.available_monitors().ok()?;
+    let mut layout = saved.layout;
+
+    let monitor = monitors
+        .iter()
+        .map(|m| (overlap(&layout, m.position(), m.size()), m))
+        .filter(|(area, _)| *area > 0)
+        .max_by_key(|(area, _)| *area)
+        .map(|(_, m)| m)?;
+
+    if let Some(scale) = saved.scale.filter(|scale| *scale > 0.0) {
+        let size = rescale(PhysicalSize::new(layout.width, layout.height), monitor.scale_factor() / scale);
+        (layout.width, layout.height) = (size.width, size.height);
+    }
+
+    let (pos, size) = (monitor.position(), monitor.size());
+    let width = layout.width.min(size.width);
+    let height = layout.height.min(size.height);
+    Some(WindowLayout {
+        x: layout.x.clamp(pos.x, pos.x + (size.width - width) as i32),
+        y: layout.y.clamp(pos.y, pos.y + (size.height - height) as i32),
+        width,
+        height,
+    })
+}
+
+fn overlap(layout: &WindowLayout, pos: &PhysicalPosition<i32>, size: &PhysicalSize<u32>) -> i64 {
+    let left = layout.x.max(pos.x) as i64;
+    let top = layout.y.max(pos.y) as i64;
+    let right = (layout.x as i64 + layout.width as i64).min(pos.x as i64 + size.width as i64);
+    let bottom = (layout.y as i64 + layout.height as i64).min(pos.y as i64 + size.height as i64);
+    (right - left).max(0) * (bottom - top).max(0)
+}
@@ -0,0 +1,104 @@
+//! Window Level
+//!
+//! Where the overlay sits in the window stack and which spaces it shows on.
+//! macOS maps levels to `NSWindow` levels and rebuilds the collection
+//! behavior mask; Windows and Linux only distinguish normal from
+//! always-on-top.
+//!
+//! Staying above fullscreen apps is opt-in. On macOS it lifts the level to
+//! at least `Status` and marks the window a fullscreen auxiliary, so it also
+//! covers menus and popovers of other apps. On Windows it forces topmost,
+//! which covers borderless fullscreen but not exclusive-mode games.
+
+use serde::{Deserialize, Serialize};
+use tauri::WebviewWindow;
+
+/// Ordered from lowest to highest in the stack.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum WindowLevel {
+    Normal,
+    #[default]
+    Floating,
+    ModalPanel,
+    Status,
+    ScreenSaver,
+}
+
+impl WindowLevel {
+    /// Parse a level name such as `Floating` (case-insensitive).
+    pub fn parse(name: &str) -> Option<Self> {
+        const ALL: [(&str, WindowLevel); 5] = [
+            ("Normal", WindowLevel::Normal),
+            ("Floating", WindowLevel::Floating),
+            ("ModalPanel", WindowLevel::ModalPanel),
+            ("Status", WindowLevel::Status),
+            ("ScreenSaver", WindowLevel::ScreenSaver),
+        ];
+        ALL.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, l)| *l)
+    }
+
+    // NSWindowLevel values from NSWindow.h
+    #[cfg(target_os = "macos")]
+    fn ns_level(self) -> i64 {
+        match self {
+            WindowLevel::Normal => 0,
+            WindowLevel::Floating => 3,
+            WindowLevel::ModalPanel => 8,
+            WindowLevel::Status => 25,
+            WindowLevel::ScreenSaver => 1000,
+        }
+    }
+}
+
+/// The level actually used, raised when the window must stay above
+/// fullscreen apps.
+pub fn effective(level: WindowLevel, above_fullscreen: bool) -> WindowLevel {
+    if above_fullscreen && cfg!(target_os = "macos") {
+        level.max(WindowLevel::Status)
+    } else {
+        level
+    }
+}
+
+/// Apply the level and space behavior. Must run on the main thread.
+/// Returns the level applied; see `effective`.
+pub fn apply(
+    window: &WebviewWindow,
+    level: WindowLevel,
+    join_all_spaces: bool,
+    above_fullscreen: bool,
+) -> tauri::Result<WindowLevel> {
+    let level = effective(level, above_fullscreen);
+
+    #[cfg(target_os = "macos")]
+    {
+        use cocoa::base::id;
+        use objc::{msg_send, sel, sel_impl};
+
+        // NSWindowCollectionBehaviorCanJoinAllSpaces = 1 << 0
+        // NSWindowCollectionBehaviorStationary = 1 << 4
+        // NSWindowCollectionBehaviorIgnoresCycle = 1 << 6
+        // NSWindowCollectionBehaviorFullScreenAuxiliary = 1 << 8
+        let mut behavior: u64 = (1 << 4) | (1 << 6);
+        if join_all_spaces {
+            behavior |= 1 << 0;
+        }
+        if above_fullscreen {
+            behavior |= 1 << 8;
+        }
+
+        let ns_window = window.ns_window()? as id;
+        unsafe {
+            let _: () = msg_send![ns_window, setLevel: level.ns_level()];
+            let _: () = msg_send![ns_window, setCollectionBehavior: behavior];
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        window.set_always_on_top(above_fullscreen || level != WindowLevel::Normal)?;
+        // Not supported on Windows, where Tauri ignores it
+        window.set_visible_on_all_workspaces(join_all_spaces)?;
+    }
+    Ok(level)
+}
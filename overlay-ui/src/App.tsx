@@ -1,9 +1,8 @@
 import { useState, useEffect, useRef } from 'react'
-import { getCurrentWindow, currentMonitor } from '@tauri-apps/api/window'
+import { getCurrentWindow } from '@tauri-apps/api/window'
 import { listen } from '@tauri-apps/api/event'
 import { invoke, convertFileSrc } from '@tauri-apps/api/core'
-import { PhysicalPosition } from '@tauri-apps/api/dpi'
-import { marked } from 'marked'
+import { processCustomTags, renderAgentMarkdown } from './markdown'
 
 // Liquid Glass Input Component
 interface LiquidGlassInputProps {
@@ -115,8 +114,17 @@ interface Message {
   timestamp: string
   toolCalls?: string[]
   attachments?: string[]
+  // Set by the agent on messages it may replace or expire later
+  id?: string
+  // How to render content; plain text when missing
+  contentType?: ContentType
 }
 
+type ContentType =
+  | { kind: 'text' }
+  | { kind: 'markdown' }
+  | { kind: 'code'; language?: string }
+
 function formatTime(date: Date): string {
   return date.toLocaleTimeString('en-US', {
     hour12: false,
@@ -185,55 +193,12 @@ function AttachmentRenderer({ attachments }: { attachments: string[] }) {
 }
 
 // Component for rendering markdown content
-const CUSTOM_XML_TAGS = ['chat', 'quote', 'reminder', 'warning', 'thought', 'error', 'tui', 'gui']
-const CUSTOM_XML_RE = new RegExp(
-  `<(${CUSTOM_XML_TAGS.join('|')})>([\\s\\S]*?)<\\/\\1>`,
-  'gi'
-)
-
-// Escape HTML special chars for safe dangerouslySetInnerHTML usage
-function escapeHtml(text: string): string {
-  return text
-    .replace(/&/g, '&amp;')
-    .replace(/</g, '&lt;')
-    .replace(/>/g, '&gt;')
-    .replace(/"/g, '&quot;')
-}
-
-// Process plain text content: escape HTML first, then apply custom XML tag labels
-function processCustomTags(text: string): string {
-  let result = escapeHtml(text)
-  const escapedTagRe = new RegExp(
-    `&lt;(${CUSTOM_XML_TAGS.join('|')})&gt;([\\s\\S]*?)&lt;/\\1&gt;`,
-    'gi'
-  )
-  // Loop to handle nested tags (inner tags get replaced first in subsequent passes)
-  let prev = ''
-  while (prev !== result) {
-    prev = result
-    result = result.replace(
-      escapedTagRe,
-      (_match, tag, inner) => `${inner.replace(/[\s\n\r]+$/, '')}<sup class="xml-tag">${escapeHtml(tag)}</sup>`
-    )
-  }
-  return result
-}
-
+// Every message is agent-supplied, so markdown always goes through the sanitizing renderer
 function MarkdownContent({ content }: { content: string }) {
-  let htmlContent = marked.parse(content, { async: false }) as string
-  // Replace known custom XML tags with content + superscript tag label (loop for nesting)
-  let prev = ''
-  while (prev !== htmlContent) {
-    prev = htmlContent
-    htmlContent = htmlContent.replace(
-      CUSTOM_XML_RE,
-      (_match, tag, inner) => `${inner.replace(/[\s\n\r]+$/, '')}<sup class="xml-tag">${tag}</sup>`
-    )
-  }
   return (
     <div
       className="markdown-content"
-      dangerouslySetInnerHTML={{ __html: htmlContent }}
+      dangerouslySetInnerHTML={{ __html: renderAgentMarkdown(content) }}
     />
   )
 }
@@ -335,11 +300,19 @@ function MessageItem({ msg }: { msg: Message }) {
         className={`message-content ${isExpanded && msg.role !== 'computer' ? 'expanded' : ''} ${msg.role === 'tool' ? 'tool-content' : ''} ${msg.role === 'computer' ? 'computer-content' : ''} ${msg.role === 'computer' && computerAnimated ? 'animated' : ''} ${msg.role === 'computer' && isComputerFolded ? 'folded' : ''}`}
       >
         {msg.role === 'computer' ? (
-          <MarkdownContent content={
-            (msg.attachments && msg.attachments.length > 0
-              ? msg.attachments.map(f => `![screenshot](${convertFileSrc(f)})`).join('\n') + '\n\n'
-              : '') + msg.content
-          } />
+          <>
+            {/* Screenshot paths come from the overlay, not the markdown, so they stay outside it */}
+            {msg.attachments && msg.attachments.length > 0 && (
+              <div className="markdown-content">
+                {msg.attachments.map((f, i) => <img key={i} src={convertFileSrc(f)} alt="screenshot" />)}
+              </div>
+            )}
+            <MarkdownContent content={msg.content} />
+          </>
+        ) : msg.contentType?.kind === 'markdown' ? (
+          <MarkdownContent content={msg.content} />
+        ) : msg.contentType?.kind === 'code' ? (
+          <pre className="message-code"><code className={msg.contentType.language ? `language-${msg.contentType.language}` : undefined}>{msg.content}</code></pre>
         ) : msg.role === 'assistant' ? (
           <div dangerouslySetInnerHTML={{ __html: processCustomTags(msg.content) }} />
         ) : (
@@ -373,13 +346,143 @@ function MessageItem({ msg }: { msg: Message }) {
   )
 }
 
+// Payload of agent-warning events: protective measures against a slow or noisy agent
+type AgentWarning = { timestamp: string } & (
+  | { kind: 'rate_limited'; direction: 'inbound' | 'outbound' }
+  | { kind: 'slow_consumer'; id: number; timeouts: number }
+  | { kind: 'oversize_frame'; id: number; limit: number }
+  | { kind: 'queue_near_full'; depth: number; capacity: number }
+  | { kind: 'connection_limit'; max: number }
+)
+
+function describeAgentWarning(warning: AgentWarning): string {
+  switch (warning.kind) {
+    case 'rate_limited':
+      return warning.direction === 'inbound' ? 'Agent messages are being rate limited' : 'Input rate limited'
+    case 'slow_consumer':
+      return `Agent is falling behind (${warning.timeouts} sends timed out)`
+    case 'oversize_frame':
+      return `Agent sent a message over the ${warning.limit} byte limit`
+    case 'queue_near_full':
+      return `Agent has ${warning.depth} of ${warning.capacity} messages unacknowledged`
+    case 'connection_limit':
+      return `Refused an agent connection: ${warning.max} already open`
+  }
+}
+
+// Payload of agent-status events (versioned, switch on `kind`)
+type ConnectionEvent = { version: number } & (
+  | { kind: 'listening'; port: number; url: string; socket_path?: string }
+  | { kind: 'connected'; id: number }
+  | { kind: 'resumed'; id: number; session_id: string; resent: number }
+  | { kind: 'disconnected'; id: number; reason: string; code?: number; code_label?: string; close_reason?: string }
+  | { kind: 'degraded'; id: number; missed_pings: number }
+  | { kind: 'restarting'; port: number }
+  | { kind: 'binding'; port: number; attempt: number; max_attempts: number; retry_in_ms: number }
+  | { kind: 'unstable'; transitions: number }
+  | { kind: 'remote_exposed'; url: string; tls: boolean }
+  | { kind: 'error'; message: string }
+)
+
+function describeConnectionEvent(status: ConnectionEvent): string {
+  switch (status.kind) {
+    case 'listening':
+      return `Listening on ${status.url}`
+    case 'connected':
+      return 'Agent connected'
+    case 'resumed':
+      return status.resent > 0 ? `Agent resumed (${status.resent} messages resent)` : 'Agent resumed'
+    case 'disconnected':
+      return status.code !== undefined
+        ? `Agent disconnected (${status.reason}; ${status.code} ${status.code_label})`
+        : `Agent disconnected (${status.reason})`
+    case 'degraded':
+      return `Agent not responding (${status.missed_pings} pings missed)`
+    case 'restarting':
+      return `Restarting server on port ${status.port}`
+    case 'binding':
+      return `Port ${status.port} is busy, binding… (attempt ${status.attempt}/${status.max_attempts})`
+    case 'unstable':
+      return `Agent connection is unstable (${status.transitions} reconnects)`
+    case 'remote_exposed':
+      return status.tls
+        ? `Warning: accepting agents from other machines on ${status.url}`
+        : `Warning: accepting agents from other machines on ${status.url} without TLS`
+    case 'error':
+      return status.message
+  }
+}
+
+// Payload of agent-hello events
+type AgentHello = {
+  id: number
+  agent_name: string
+  agent_version: string
+  protocol_version: number
+  model?: string
+  capabilities: string[]
+}
+
+// Payload of scrim-changed events and get_text_contrast_scrim
+type ScrimState = { opacity: number; color: [number, number, number] }
+
+// Payload of corner-radius-changed events; "css" means the webview rounds itself
+type CornerState = { label: string; radius: number; rounding: 'native' | 'css' }
+
+// Payload of glass-geometry-changed events: the card that has glass, in logical px
+type GlassGeometry = {
+  label: string
+  inset: number
+  x: number
+  y: number
+  width: number
+  height: number
+  rendering: 'native' | 'css'
+}
+
+// Payload of window-appearance-changed events; "css" parts are drawn by the webview
+type WindowAppearance = {
+  label: string
+  shadow: boolean
+  shadow_rendering: 'native' | 'css'
+  border: { color: [number, number, number, number]; width: number } | null
+  border_rendering: 'native' | 'css'
+}
+
+// Error returned by every Tauri command (switch on `kind`)
+type CommandError = {
+  kind: 'not_connected' | 'send_timeout' | 'rate_limited' | 'serialize' | 'invalid_arg' | 'not_found' | 'io'
+  message: string
+}
+
+function describeCommandError(e: unknown): string {
+  const error = e as CommandError
+  switch (error?.kind) {
+    case 'not_connected':
+      return 'Agent is not connected'
+    case 'send_timeout':
+      return 'Agent is not responding'
+    case 'rate_limited':
+      return 'Sending too fast, try again in a moment'
+    case undefined:
+      return String(e)
+    default:
+      return error.message
+  }
+}
+
 function App() {
   const [messages, setMessages] = useState<Message[]>([])
   const [, setStatus] = useState({ text: 'Waiting for agent...', type: 'normal' as 'normal' | 'connected' })
   const [inputValue, setInputValue] = useState('')
   const [isConnected, setIsConnected] = useState(false)
   const [isAgentBusy, setIsAgentBusy] = useState(false)
-  const [theme] = useState<'light' | 'dark'>('dark')
+  const [theme, setTheme] = useState<'light' | 'dark'>('dark')
+  const [scrim, setScrim] = useState<ScrimState>({ opacity: 0, color: [0, 0, 0] })
+  const [cssCornerRadius, setCssCornerRadius] = useState(0)
+  const [appearance, setAppearance] = useState<WindowAppearance | null>(null)
+  const [glassInset, setGlassInset] = useState(0)
+  const [overlayMode, setOverlayMode] = useState<'Collapsed' | 'Expanded'>('Expanded')
   const [pendingMessages, setPendingMessages] = useState<Array<{id: string; content: string; timestamp: string}>>([])
   const messagesRef = useRef<HTMLDivElement>(null)
   const initialLoadDone = useRef(false)
@@ -397,7 +500,7 @@ function App() {
       console.error('Failed to send message:', e)
       setMessages(prev => [...prev, {
         role: 'status',
-        content: `Failed to send: ${e}`,
+        content: `Failed to send: ${describeCommandError(e)}`,
         timestamp: formatTime(new Date()),
       }])
     }
@@ -412,7 +515,7 @@ function App() {
       console.error('Failed to stop agent:', e)
       setMessages(prev => [...prev, {
         role: 'status',
-        content: `Failed to stop: ${e}`,
+        content: `Failed to stop: ${describeCommandError(e)}`,
         timestamp: formatTime(new Date()),
       }])
     }
@@ -427,35 +530,6 @@ function App() {
     await appWindow.minimize()
   }
 
-  // Position window
-  useEffect(() => {
-    async function positionWindow() {
-      try {
-        const appWindow = getCurrentWindow()
-        const monitor = await currentMonitor()
-
-        if (monitor) {
-          const screenWidth = monitor.size.width
-          const screenHeight = monitor.size.height
-          const scaleFactor = monitor.scaleFactor
-
-          const windowWidth = 400
-          const windowHeight = 500
-          const margin = 20
-
-          const x = Math.round((screenWidth / scaleFactor) - windowWidth - margin)
-          const y = Math.round((screenHeight / scaleFactor) - windowHeight - margin)
-
-          await appWindow.setPosition(new PhysicalPosition(x, y))
-        }
-      } catch (e) {
-        console.error('Failed to position window:', e)
-      }
-    }
-
-    positionWindow()
-  }, [])
-
   // Titlebar dragging
   useEffect(() => {
     const titlebar = document.getElementById('titlebar')
@@ -500,10 +574,16 @@ function App() {
         timestamp: payload.timestamp || formatTime(new Date()),
         toolCalls: payload.toolCalls || payload.tool_calls,
         attachments: payload.attachments,
+        id: payload.id,
+        contentType: payload.content_type,
       }
       
       console.log('[agent-message] Adding message:', validMessage)
-      setMessages(prev => [...prev, validMessage])
+      // A message with a known id replaces the earlier one in place
+      setMessages(prev => {
+        const index = validMessage.id ? prev.findIndex(m => m.id === validMessage.id) : -1
+        return index < 0 ? [...prev, validMessage] : prev.map((m, i) => i === index ? validMessage : m)
+      })
       setStatus({ text: 'Connected', type: 'connected' })
       setIsConnected(true)
 
@@ -518,14 +598,14 @@ function App() {
       }
     })
 
-    const unlistenStatus = listen<string>('agent-status', (event) => {
-      const content = event.payload
-      const lowerContent = content.toLowerCase()
+    const unlistenStatus = listen<ConnectionEvent>('agent-status', (event) => {
+      const status = event.payload
+      const content = describeConnectionEvent(status)
 
       // Check connection status
-      if (lowerContent.includes('connected') && !lowerContent.includes('disconnected')) {
+      if (status.kind === 'connected' || status.kind === 'resumed') {
         setIsConnected(true)
-      } else if (lowerContent.includes('disconnected')) {
+      } else if (status.kind === 'disconnected') {
         setIsConnected(false)
         setIsAgentBusy(false)
       }
@@ -540,18 +620,116 @@ function App() {
       setStatus({ text: content, type: 'connected' })
     })
 
-    const unlistenError = listen<string>('agent-error', (event) => {
+    const unlistenError = listen<{ message: string }>('agent-error', (event) => {
       // Show disconnection as status message (red)
       setMessages(prev => [...prev, {
         role: 'status',
-        content: event.payload,
+        content: event.payload.message,
         timestamp: formatTime(new Date()),
       }])
-      setStatus({ text: event.payload, type: 'normal' })
+      setStatus({ text: event.payload.message, type: 'normal' })
       setIsConnected(false)
       setIsAgentBusy(false)
     })
 
+    // Agent self-description, sent by protocol 2+ agents after auth
+    const unlistenHello = listen<AgentHello>('agent-hello', (event) => {
+      const { agent_name, agent_version, model } = event.payload
+      const content = `Connected to ${agent_name} v${agent_version}${model ? ` (${model})` : ''}`
+      setMessages(prev => [...prev, {
+        role: 'status',
+        content,
+        timestamp: formatTime(new Date()),
+      }])
+      setStatus({ text: content, type: 'connected' })
+    })
+
+    // Ephemeral notices remove themselves once their TTL elapses
+    const unlistenExpire = listen<{ message_id: string }>('agent-message-expire', (event) => {
+      setMessages(prev => prev.filter(m => m.id !== event.payload.message_id))
+    })
+
+    // A capture failed for want of a permission the user can grant
+    const unlistenPermission = listen<{ permission: string; status: 'denied' | 'undetermined' }>('permission-needed', () => {
+      setMessages(prev => [...prev, {
+        role: 'status',
+        content: 'Screen recording permission is needed: enable it in System Settings > Privacy & Security',
+        timestamp: formatTime(new Date()),
+      }])
+    })
+
+    // Delivery of user inputs: pending until the agent acks, failed on timeout
+    const unlistenAcked = listen<{ id: string; status: 'pending' | 'delivered' | 'failed' }>('agent-input-acked', (event) => {
+      if (event.payload.status === 'failed') {
+        setMessages(prev => [...prev, {
+          role: 'status',
+          content: 'Message was not acknowledged by the agent',
+          timestamp: formatTime(new Date()),
+        }])
+      }
+    })
+
+    const unlistenWarning = listen<AgentWarning>('agent-warning', (event) => {
+      setMessages(prev => [...prev, {
+        role: 'status',
+        content: describeAgentWarning(event.payload),
+        timestamp: formatTime(new Date()),
+      }])
+    })
+
+    // Agent-reported busy/idle; reset to idle by the backend on disconnect
+    const unlistenActivity = listen<'busy' | 'idle'>('agent-activity', (event) => {
+      setIsAgentBusy(event.payload === 'busy')
+    })
+
+    // Follow the native glass theme
+    const unlistenTheme = listen<'light' | 'dark'>('theme-changed', (event) => {
+      setTheme(event.payload)
+    })
+
+    // Solid layer behind the content for legibility; opacity 0 when off
+    invoke<ScrimState>('get_text_contrast_scrim').then(setScrim).catch(() => {})
+    const unlistenScrim = listen<ScrimState>('scrim-changed', (event) => {
+      setScrim(event.payload)
+    })
+
+    // Round our own corners where the OS can't
+    const unlistenCorners = listen<CornerState>('corner-radius-changed', (event) => {
+      if (event.payload.label !== getCurrentWindow().label) return
+      setCssCornerRadius(event.payload.rounding === 'css' ? event.payload.radius : 0)
+    })
+
+    const unlistenAppearance = listen<WindowAppearance>('window-appearance-changed', (event) => {
+      if (event.payload.label !== getCurrentWindow().label) return
+      setAppearance(event.payload)
+    })
+
+    // Keep the content inside the glass card; the margin stays empty
+    const unlistenGeometry = listen<GlassGeometry>('glass-geometry-changed', (event) => {
+      if (event.payload.label !== getCurrentWindow().label) return
+      setGlassInset(event.payload.inset)
+    })
+
+    // Ask before opening a link for the agent; unanswered prompts time out
+    const unlistenOpenConfirm = listen<{ id: string; url: string }>('open-confirm', (event) => {
+      const allow = window.confirm(`The agent wants to open:\n${event.payload.url}`)
+      invoke('confirm_open', { id: event.payload.id, allow }).catch(() => {})
+    })
+
+    // The window animates to the pill or panel size; swap the layout to match
+    const unlistenMode = listen<{ mode: 'Collapsed' | 'Expanded'; automatic: boolean }>('overlay-mode-changed', (event) => {
+      setOverlayMode(event.payload.mode)
+    })
+
+    // With autostart off the server waits for start_server; say so rather than "waiting for agent"
+    const unlistenServerStopped = listen<{ reason: 'autostart_off' | 'stopped' }>('server-stopped', () => {
+      setMessages(prev => [...prev, {
+        role: 'status',
+        content: 'Server is not running; agents cannot connect until it is started',
+        timestamp: formatTime(new Date()),
+      }])
+    })
+
     // Listen for pending messages queue updates
     const unlistenPending = listen<Array<{id: string; content: string; timestamp: string}>>('pending-messages', (event) => {
       console.log('[pending-messages] Updated:', event.payload)
@@ -575,6 +753,20 @@ function App() {
       unlistenStatus.then(fn => fn())
       unlistenError.then(fn => fn())
       unlistenPending.then(fn => fn())
+      unlistenAcked.then(fn => fn())
+      unlistenActivity.then(fn => fn())
+      unlistenWarning.then(fn => fn())
+      unlistenTheme.then(fn => fn())
+      unlistenScrim.then(fn => fn())
+      unlistenCorners.then(fn => fn())
+      unlistenAppearance.then(fn => fn())
+      unlistenGeometry.then(fn => fn())
+      unlistenOpenConfirm.then(fn => fn())
+      unlistenMode.then(fn => fn())
+      unlistenServerStopped.then(fn => fn())
+      unlistenHello.then(fn => fn())
+      unlistenPermission.then(fn => fn())
+      unlistenExpire.then(fn => fn())
     }
   }, [])
 
@@ -586,7 +778,22 @@ function App() {
   }, [messages, pendingMessages])
 
   return (
-    <div id="app" data-theme={theme} onContextMenu={handleContextMenu}>
+    <div
+      id="app"
+      data-theme={theme}
+      data-mode={overlayMode}
+      onContextMenu={handleContextMenu}
+      style={{
+        ...(scrim.opacity > 0 ? { background: `rgba(${scrim.color.join(', ')}, ${scrim.opacity})` } : {}),
+        ...(cssCornerRadius > 0 ? { borderRadius: cssCornerRadius, overflow: 'hidden' } : {}),
+        ...(glassInset > 0 ? { margin: glassInset, height: `calc(100% - ${2 * glassInset}px)` } : {}),
+        // A shadow can't leave the window, so the CSS one falls inward
+        ...(appearance?.shadow && appearance.shadow_rendering === 'css' ? { boxShadow: 'inset 0 0 12px rgba(0, 0, 0, 0.35)' } : {}),
+        ...(appearance?.border && appearance.border_rendering === 'css'
+          ? { border: `${appearance.border.width}px solid rgba(${appearance.border.color.slice(0, 3).join(', ')}, ${appearance.border.color[3] / 255})` }
+          : {}),
+      }}
+    >
       <div id="titlebar" data-tauri-drag-region>
         <span className="title">Jarvis</span>
       </div>
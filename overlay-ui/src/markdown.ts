@@ -0,0 +1,55 @@
+import { Marked } from 'marked'
+
+// Custom XML tags agents wrap parts of a message in, shown as a superscript label
+const CUSTOM_XML_TAGS = ['chat', 'quote', 'reminder', 'warning', 'thought', 'error', 'tui', 'gui']
+const ESCAPED_TAG_RE = new RegExp(
+  `&lt;(${CUSTOM_XML_TAGS.join('|')})&gt;([\\s\\S]*?)&lt;/\\1&gt;`,
+  'gi'
+)
+
+// Escape HTML special chars for safe dangerouslySetInnerHTML usage
+export function escapeHtml(text: string): string {
+  return text
+    .replace(/&/g, '&amp;')
+    .replace(/</g, '&lt;')
+    .replace(/>/g, '&gt;')
+    .replace(/"/g, '&quot;')
+}
+
+// Replace escaped custom XML tags with content + superscript tag label (loop for nesting)
+function labelCustomTags(html: string): string {
+  let result = html
+  let prev = ''
+  while (prev !== result) {
+    prev = result
+    result = result.replace(
+      ESCAPED_TAG_RE,
+      (_match, tag, inner) => `${inner.replace(/[\s\n\r]+$/, '')}<sup class="xml-tag">${escapeHtml(tag)}</sup>`
+    )
+  }
+  return result
+}
+
+// Process plain text content: escape HTML first, then apply custom XML tag labels
+export function processCustomTags(text: string): string {
+  return labelCustomTags(escapeHtml(text))
+}
+
+// Markdown from the agent: raw HTML is shown as text and only web links are kept
+const agentMarked = new Marked({
+  renderer: {
+    html({ text }) {
+      return escapeHtml(text)
+    },
+  },
+  walkTokens(token) {
+    if ((token.type === 'link' || token.type === 'image') && !/^(https?:|mailto:|#)/i.test(token.href)) {
+      token.href = '#'
+    }
+  },
+})
+
+// Render agent markdown to HTML that is safe to put in the webview
+export function renderAgentMarkdown(content: string): string {
+  return labelCustomTags(agentMarked.parse(content, { async: false }) as string)
+}
@@ -0,0 +1,72 @@
+/**
+ * Agent Markdown Tests
+ *
+ * Checks that agent-supplied markdown, including computer-role messages,
+ * can't put live HTML or script URLs into the webview.
+ * Run with: npx tsx test/markdown.test.ts
+ */
+
+import { processCustomTags, renderAgentMarkdown } from '../src/markdown.ts'
+
+const colors = {
+  green: '\x1b[32m',
+  red: '\x1b[31m',
+  reset: '\x1b[0m',
+  bold: '\x1b[1m',
+}
+
+let failures = 0
+
+function check(name: string, ok: boolean, detail: string) {
+  if (ok) {
+    console.log(`${colors.green}✓${colors.reset} ${name}`)
+  } else {
+    failures++
+    console.log(`${colors.red}✗${colors.reset} ${name}\n    ${detail}`)
+  }
+}
+
+// No element may carry an event handler or a script URL
+function isInert(html: string): boolean {
+  return !/<[^>]*\son\w+\s*=/i.test(html) && !/(href|src)\s*=\s*"\s*javascript:/i.test(html)
+}
+
+function testComputerMessageHtml() {
+  const content = 'Took a screenshot\n\n<img src=x onerror="alert(1)">'
+  const html = renderAgentMarkdown(content)
+  check('computer-role raw HTML is escaped', !html.includes('<img') && html.includes('&lt;img'), html)
+  check('computer-role output has no handlers', isInert(html), html)
+}
+
+function testInlineHtml() {
+  const html = renderAgentMarkdown('hi <span onmouseover="alert(1)">there</span> <script>alert(2)</script>')
+  check('inline HTML is escaped', !html.includes('<span') && !html.includes('<script'), html)
+}
+
+function testScriptLinks() {
+  const html = renderAgentMarkdown('[click](javascript:alert(1)) ![x](javascript:alert(2))')
+  check('script links are neutralized', isInert(html) && !html.includes('javascript:'), html)
+  const web = renderAgentMarkdown('[docs](https://example.com)')
+  check('web links are kept', web.includes('href="https://example.com"'), web)
+}
+
+function testCustomTags() {
+  const html = renderAgentMarkdown('<thought>checking</thought>')
+  check('custom tags still get their label', html.includes('checking<sup class="xml-tag">thought</sup>'), html)
+  const text = processCustomTags('<img src=x onerror=alert(1)>')
+  check('plain text is escaped', !text.includes('<img'), text)
+}
+
+console.log(`${colors.bold}Agent Markdown Tests${colors.reset}`)
+console.log('='.repeat(50))
+testComputerMessageHtml()
+testInlineHtml()
+testScriptLinks()
+testCustomTags()
+console.log('\n' + '='.repeat(50))
+if (failures > 0) {
+  console.log(`${failures} failed`)
+  process.exitCode = 1
+} else {
+  console.log('Tests completed')
+}
@@ -212,6 +212,7 @@ export class Agent {
 
           logger.info(`Received ${pendingMessages.length} new message(s)`)
           hasUserMessage = true
+          overlayClient.setState('busy')
         }
 
         // 重置无工具调用计数
@@ -221,6 +222,7 @@ export class Agent {
         // Idle wait: messages will be consumed immediately on break,
         // suppress push notifications to avoid flashing pending UI.
         messageManager.setPushNotify(false)
+        overlayClient.setState('idle')
 
         // Idle wait: poll every 1s for new messages AND AX diff on whitelisted focused apps
         const diffApps = (config.keys.notification as NotificationConfig)?.diffApps || []
@@ -2,14 +2,34 @@
  * Overlay UI Client
  *
  * WebSocket client for sending messages to the Jarvis overlay UI.
- * The overlay UI listens on ws://127.0.0.1:19823
+ * The overlay UI listens on ws://127.0.0.1:19823, or on a Unix socket when
+ * its transport is "unix" (set JARVIS_OVERLAY_SOCKET to the socket path).
+ *
+ * Text frames must be valid UTF-8. The overlay doesn't skip a frame that
+ * isn't: it reports an agent error and closes the connection with 1007
+ * (invalid payload), since its WebSocket stream can't read past the bad
+ * frame. The client reconnects as after any other close.
  */
 
 import WebSocket from 'ws'
+import * as crypto from 'crypto'
+import * as fs from 'fs'
+import * as os from 'os'
+import * as path from 'path'
 import { messageLayer } from '../message/index.js'
 
-const WS_URL = 'ws://127.0.0.1:19823'
+const WS_URL = process.env.JARVIS_OVERLAY_SOCKET
+  ? `ws+unix://${process.env.JARVIS_OVERLAY_SOCKET}`
+  : 'ws://127.0.0.1:19823'
 const RECONNECT_INTERVAL = 3000
+// Written by the overlay at startup; must be sent as the first frame
+const TOKEN_PATH = path.join(os.homedir(), '.jarvis', 'ws_token')
+// Overlay message protocol this client speaks, sent in the auth and hello frames
+const PROTOCOL_VERSION = 2
+const AGENT_NAME = 'jarvis'
+const AGENT_VERSION = process.env.npm_package_version || '0.0.0'
+// Kept across restarts so the overlay resumes our session after a crash
+const SESSION_PATH = path.join(os.homedir(), '.jarvis', 'overlay_session')
 
 export interface OverlayMessage {
   role: 'user' | 'assistant' | 'system' | 'tool' | 'computer' | 'error'
@@ -17,12 +37,23 @@ export interface OverlayMessage {
   timestamp: string
   toolCalls?: string[]
   attachments?: string[]
+  // Rendering hint; the overlay treats anything it doesn't know as plain text
+  content_type?: 'text' | 'markdown' | { kind: 'code'; language?: string }
 }
 
 // Message from UI to Agent
 interface UiMessage {
-  type: string  // "user_input" | "stop_agent"
+  type: string  // "user_input" | "stop_agent" | "session" | "context" | "echo"
   content: string
+  // Set on "user_input"; acknowledged with an "ack" frame carrying it back
+  id?: string
+  // Set on "context": "selection" | "active_window" | "clipboard"
+  kind?: string
+  // Set on "echo"; answered with an "echo_reply" carrying it back
+  nonce?: string
+  // Set on the "session" frame sent right after auth
+  session_id?: string
+  is_resume?: boolean
 }
 
 // Stop signal callback
@@ -35,6 +66,8 @@ class OverlayClient {
   private messageQueue: OverlayMessage[] = []
   private stopCallback: StopCallback | null = null
   private exitCallback: StopCallback | null = null
+  // Latest ambient context from the UI, by kind; not a user turn
+  private context = new Map<string, string>()
 
   /**
    * Enable the overlay client and connect to the UI
@@ -77,6 +110,7 @@ class OverlayClient {
 
       this.ws.on('open', () => {
         console.log('[Overlay] Connected to UI')
+        this.authenticate()
         // Send queued messages
         while (this.messageQueue.length > 0) {
           const msg = this.messageQueue.shift()
@@ -90,12 +124,19 @@ class OverlayClient {
           const msg: UiMessage = JSON.parse(data.toString())
           if (msg.type === 'user_input' && msg.content) {
             messageLayer.push('gui', msg.content)
+            if (msg.id) this.ws?.send(JSON.stringify({ type: 'ack', id: msg.id }))
             console.log(`[Overlay] Received from UI: ${msg.content}`)
           } else if (msg.type === 'stop_agent') {
             console.log('[Overlay] Received stop signal from UI')
             if (this.stopCallback) {
               this.stopCallback()
             }
+          } else if (msg.type === 'echo' && msg.nonce) {
+            this.ws?.send(JSON.stringify({ type: 'echo_reply', nonce: msg.nonce }))
+          } else if (msg.type === 'context' && msg.kind) {
+            this.context.set(msg.kind, msg.content)
+          } else if (msg.type === 'session') {
+            console.log(`[Overlay] ${msg.is_resume ? 'Resumed' : 'Started'} session ${msg.session_id}`)
           } else if (msg.type === 'exit_agent') {
             console.log('[Overlay] Received exit signal from UI')
             if (this.exitCallback) {
@@ -124,6 +165,50 @@ class OverlayClient {
     }
   }
 
+  /**
+   * Send the auth frame with the token the overlay wrote at startup
+   */
+  private authenticate(): void {
+    try {
+      const token = fs.readFileSync(TOKEN_PATH, 'utf-8').trim()
+      this.ws?.send(JSON.stringify({
+        type: 'auth',
+        token,
+        protocol_version: PROTOCOL_VERSION,
+        agent_name: AGENT_NAME,
+        session_id: this.sessionId(),
+      }))
+      // Describes this agent to the overlay; required from protocol 2
+      this.ws?.send(JSON.stringify({
+        type: 'hello',
+        agent_name: AGENT_NAME,
+        agent_version: AGENT_VERSION,
+        protocol_version: PROTOCOL_VERSION,
+        capabilities: ['tool_calls', 'state', 'attention'],
+      }))
+    } catch (e) {
+      console.error(`[Overlay] Failed to read auth token from ${TOKEN_PATH}:`, e)
+    }
+  }
+
+  /**
+   * Session id sent in the auth frame, created on first use
+   */
+  private sessionId(): string {
+    try {
+      return fs.readFileSync(SESSION_PATH, 'utf-8').trim()
+    } catch {
+      const id = crypto.randomUUID()
+      try {
+        fs.mkdirSync(path.dirname(SESSION_PATH), { recursive: true })
+        fs.writeFileSync(SESSION_PATH, id)
+      } catch (e) {
+        console.error(`[Overlay] Failed to save session id to ${SESSION_PATH}:`, e)
+      }
+      return id
+    }
+  }
+
   /**
    * Disconnect from the overlay UI
    */
@@ -251,6 +336,29 @@ class OverlayClient {
     }
   }
 
+  /**
+   * Announce a long-running tool call; follow up with updateToolCall.
+   * messageId ties the call to the assistant message it belongs to.
+   */
+  startToolCall(id: string, name: string, args: Record<string, unknown> = {}, messageId?: string): void {
+    if (!this.enabled) return
+
+    if (this.isConnected()) {
+      this.ws!.send(JSON.stringify({ type: 'tool_call', id, name, arguments: args, message_id: messageId }))
+    }
+  }
+
+  /**
+   * Report tool call progress: a status change and/or output to append
+   */
+  updateToolCall(id: string, status?: 'running' | 'done' | 'error', output?: string): void {
+    if (!this.enabled) return
+
+    if (this.isConnected()) {
+      this.ws!.send(JSON.stringify({ type: 'tool_call_update', id, status, output }))
+    }
+  }
+
   /**
    * Send a computer message (system feedback)
    */
@@ -285,6 +393,39 @@ class OverlayClient {
     }
   }
 
+  /**
+   * Tell the overlay whether the agent is working, for its spinner and stop button
+   */
+  setState(value: 'busy' | 'idle'): void {
+    if (!this.enabled) return
+
+    if (this.isConnected()) {
+      this.ws!.send(JSON.stringify({ type: 'state', value }))
+    }
+  }
+
+  /**
+   * Ask the overlay to blink and request the user's attention
+   */
+  requestAttention(times: number = 2): void {
+    if (!this.enabled) return
+
+    if (this.isConnected()) {
+      this.ws!.send(JSON.stringify({ type: 'attention', times }))
+    }
+  }
+
+  /**
+   * Ask the overlay to open a URL; the user confirms it first
+   */
+  openUrl(url: string): void {
+    if (!this.enabled) return
+
+    if (this.isConnected()) {
+      this.ws!.send(JSON.stringify({ type: 'open_url', url }))
+    }
+  }
+
   /**
    * Send an error message
    */
@@ -304,6 +445,13 @@ class OverlayClient {
     }
   }
 
+  /**
+   * Latest context of the given kind sent by the UI, if any
+   */
+  getContext(kind: string): string | undefined {
+    return this.context.get(kind)
+  }
+
   /**
    * Register a callback to be called when stop signal is received from UI
    */